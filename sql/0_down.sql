@@ -0,0 +1,24 @@
+-- Reverts 0.sql
+-- This removes *all* data in the database so be careful
+
+DROP INDEX IF EXISTS audit_log_entry_id_idx;
+DROP INDEX IF EXISTS audit_log_account_id_idx;
+DROP TABLE IF EXISTS audit_log;
+
+DROP INDEX IF EXISTS session_api_key_idx;
+DROP INDEX IF EXISTS session_account_id_idx;
+DROP TABLE IF EXISTS session;
+
+DROP TABLE IF EXISTS storage;
+
+DROP INDEX IF EXISTS directory_entry_tmdb_id_idx;
+DROP INDEX IF EXISTS directory_entry_creator_id_idx;
+DROP INDEX IF EXISTS directory_entry_anilist_id_idx;
+DROP INDEX IF EXISTS directory_entry_flags_idx;
+DROP INDEX IF EXISTS directory_entry_path_idx;
+DROP TABLE IF EXISTS directory_entry;
+
+DROP INDEX IF EXISTS account_name_idx;
+DROP TABLE IF EXISTS account;
+
+PRAGMA user_version = 0;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS ban (
+  account_id INTEGER PRIMARY KEY REFERENCES account(id) ON DELETE CASCADE,
+  reason TEXT,
+  banned_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  banned_at INTEGER NOT NULL DEFAULT (unixepoch()),
+  expires_at INTEGER NOT NULL
+);
+
+PRAGMA user_version = 11;
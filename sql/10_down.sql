@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS ban;
+
+PRAGMA user_version = 10;
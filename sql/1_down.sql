@@ -0,0 +1,3 @@
+ALTER TABLE account DROP COLUMN anilist_username;
+
+PRAGMA user_version = 1;
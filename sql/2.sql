@@ -0,0 +1,14 @@
+-- Emails are stored as an HMAC so they can only be used for lookups
+ALTER TABLE account ADD COLUMN email TEXT;
+ALTER TABLE account ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 0;
+
+CREATE UNIQUE INDEX IF NOT EXISTS account_email_idx ON account(email);
+
+CREATE TABLE IF NOT EXISTS email_verification (
+  account_id INTEGER PRIMARY KEY REFERENCES account(id) ON DELETE CASCADE,
+  email TEXT NOT NULL,
+  code TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+PRAGMA user_version = 3;
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS email_verification;
+DROP INDEX IF EXISTS account_email_idx;
+
+ALTER TABLE account DROP COLUMN email_verified;
+ALTER TABLE account DROP COLUMN email;
+
+PRAGMA user_version = 2;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS invitation (
+  code TEXT PRIMARY KEY,
+  created_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  used_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  expires_at INTEGER
+);
+
+PRAGMA user_version = 4;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS invitation;
+
+PRAGMA user_version = 3;
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS report (
+  id INTEGER PRIMARY KEY,
+  entry_id INTEGER REFERENCES directory_entry(id) ON DELETE SET NULL,
+  user_id INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  reason TEXT NOT NULL,
+  files TEXT NOT NULL DEFAULT '[]', -- JSON array
+  status TEXT NOT NULL DEFAULT 'pending',
+  created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+  resolved_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  resolved_at INTEGER,
+  response TEXT
+);
+
+CREATE INDEX IF NOT EXISTS report_status_idx ON report(status);
+
+CREATE TABLE IF NOT EXISTS notification (
+  id INTEGER PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+  ts INTEGER NOT NULL DEFAULT (unixepoch()),
+  data TEXT NOT NULL -- JSON data
+);
+
+CREATE INDEX IF NOT EXISTS notification_user_id_idx ON notification(user_id);
+
+PRAGMA user_version = 5;
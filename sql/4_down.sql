@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS notification;
+DROP TABLE IF EXISTS report;
+
+PRAGMA user_version = 4;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS pending_entry (
+  id INTEGER PRIMARY KEY,
+  submitted_by INTEGER REFERENCES account(id) ON DELETE CASCADE,
+  data TEXT NOT NULL, -- JSON data
+  submitted_at INTEGER NOT NULL DEFAULT (unixepoch()),
+  reviewed_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  status TEXT NOT NULL DEFAULT 'pending'
+);
+
+CREATE INDEX IF NOT EXISTS pending_entry_status_idx ON pending_entry(status);
+
+PRAGMA user_version = 6;
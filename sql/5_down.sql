@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS pending_entry;
+
+PRAGMA user_version = 5;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS entry_tag (
+  entry_id INTEGER NOT NULL REFERENCES directory_entry(id) ON DELETE CASCADE,
+  tag TEXT NOT NULL,
+  created_by INTEGER REFERENCES account(id) ON DELETE SET NULL,
+  PRIMARY KEY(entry_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS entry_tag_tag_idx ON entry_tag(tag);
+
+PRAGMA user_version = 7;
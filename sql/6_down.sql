@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS entry_tag;
+
+PRAGMA user_version = 6;
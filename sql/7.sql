@@ -0,0 +1,5 @@
+ALTER TABLE notification ADD COLUMN read_at INTEGER;
+
+CREATE INDEX IF NOT EXISTS notification_unread_idx ON notification(user_id) WHERE read_at IS NULL;
+
+PRAGMA user_version = 8;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS notification_unread_idx;
+ALTER TABLE notification DROP COLUMN read_at;
+
+PRAGMA user_version = 7;
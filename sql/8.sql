@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS notification_preferences (
+  user_id INTEGER NOT NULL REFERENCES account(id) ON DELETE CASCADE,
+  event_type TEXT NOT NULL,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  PRIMARY KEY(user_id, event_type)
+);
+
+PRAGMA user_version = 9;
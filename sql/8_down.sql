@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS notification_preferences;
+
+PRAGMA user_version = 8;
@@ -0,0 +1,4 @@
+-- Kept up to date by the upload and delete handlers, recounted from disk on startup
+ALTER TABLE directory_entry ADD COLUMN file_count INTEGER NOT NULL DEFAULT 0;
+
+PRAGMA user_version = 10;
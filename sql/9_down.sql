@@ -0,0 +1,3 @@
+ALTER TABLE directory_entry DROP COLUMN file_count;
+
+PRAGMA user_version = 9;
@@ -5,7 +5,7 @@ use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE, RETRY_AFTER};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::warn;
 
-use crate::borrowed::MaybeBorrowedString;
+use crate::{borrowed::MaybeBorrowedString, japanese::katakana_to_hiragana};
 
 const FIXTURE_SEARCH_QUERY: &str = r#"
 query ($id: Int, $page: Int, $perPage: Int, $search: String) {
@@ -19,6 +19,7 @@ query ($id: Int, $page: Int, $perPage: Int, $search: String) {
       }
       isAdult
       format
+      episodes
     }
   }
 }
@@ -162,6 +163,9 @@ pub struct Media {
     #[serde(rename = "isAdult")]
     pub adult: bool,
     pub format: MediaFormat,
+    /// The number of episodes, if known. This is null for ongoing series.
+    #[serde(default)]
+    pub episodes: Option<u32>,
 }
 
 impl Media {
@@ -258,6 +262,23 @@ pub async fn search(client: &reqwest::Client, query: impl Into<String>) -> anyho
     .media)
 }
 
+/// Searches the AniList API for the first page of media whose native title matches a Japanese query
+///
+/// AniList can't restrict a search to the native title, so the results are reordered
+/// to put the media whose native title contains the query first.
+pub async fn search_native(client: &reqwest::Client, query: impl Into<String>) -> anyhow::Result<Vec<Media>> {
+    let query = query.into();
+    let needle = katakana_to_hiragana(&query).into_owned();
+    let mut result = search(client, query).await?;
+    result.sort_by_key(|m| {
+        !m.title
+            .native
+            .as_deref()
+            .is_some_and(|native| katakana_to_hiragana(native).contains(needle.as_str()))
+    });
+    Ok(result)
+}
+
 /// Searches the AniList API for the media that matches the given ID
 pub async fn search_by_id(client: &reqwest::Client, id: u32) -> anyhow::Result<Option<Media>> {
     Ok(send_request::<PageResult>(
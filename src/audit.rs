@@ -40,6 +40,9 @@ pub struct ScrapeDirectory {
     /// The TMDB ID of the entry
     #[serde(default)]
     pub tmdb_id: Option<tmdb::Id>,
+    /// Whether the directory failed to be scraped
+    #[serde(default, skip_serializing_if = "crate::utils::is_false")]
+    pub failed: bool,
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -81,12 +84,30 @@ impl Into<u8> for ScrapeSource {
     }
 }
 
+/// Audit log data for a single entry that received files from a scrape
+///
+/// For this data, `entry_id` is only null if the entry is deleted and `account_id` is always null.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrapeEntry {
+    pub source: ScrapeSource,
+    /// The original name of the scraped directory
+    pub original_name: String,
+    #[serde(default)]
+    pub anilist_id: Option<u32>,
+    #[serde(default)]
+    pub tmdb_id: Option<tmdb::Id>,
+    /// The number of new files that were downloaded into the entry
+    pub files_downloaded: usize,
+}
+
 /// Audit log data for a successful scrape attempt
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ScrapeResult {
     /// The directories that were scraped
     pub directories: Vec<ScrapeDirectory>,
     /// Whether the scrape errored out
+    ///
+    /// Individual directories failing to be scraped does not count, see [`ScrapeDirectory::failed`].
     pub error: bool,
     /// The date that has been scraped up to.
     #[serde(with = "time::serde::rfc3339::option")]
@@ -282,6 +303,95 @@ pub struct EditEntry {
     pub changed: Vec<String>,
 }
 
+/// Audit log data for a successful login
+///
+/// For this data, `entry_id` is always null.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Login {
+    /// Whether the session created was an API key
+    pub api_key: bool,
+    /// The IP address the login came from
+    #[serde(default)]
+    pub ip: Option<String>,
+}
+
+/// Audit log data for a logout
+///
+/// For this data, `entry_id` is always null.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Logout {
+    /// Whether every session of the account was logged out
+    pub all_sessions: bool,
+}
+
+/// Audit log data for a failed login attempt
+///
+/// For this data, `entry_id` is always null and `account_id` is only
+/// set if the username belongs to an existing account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginFailed {
+    pub reason: String,
+}
+
+/// Audit log data for adding or removing tags from an entry
+///
+/// For this data, `entry_id` and `account_id` are only null if the data is deleted.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditTags {
+    /// The tags that were added
+    #[serde(default)]
+    pub added: Vec<String>,
+    /// The tags that were removed
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+/// A single field that differs between two [`EntrySnapshot`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDiff {
+    /// The name of the field that changed
+    pub field: String,
+    /// The value before the edit, `null` if it was unset
+    pub before: serde_json::Value,
+    /// The value after the edit, `null` if it was unset
+    pub after: serde_json::Value,
+}
+
+impl EditEntry {
+    /// Computes the fields that differ between the before and after snapshots.
+    pub fn diff(&self) -> serde_json::Result<Vec<FieldDiff>> {
+        let serde_json::Value::Object(mut before) = serde_json::to_value(&self.before)? else {
+            return Ok(Vec::new());
+        };
+        let serde_json::Value::Object(mut after) = serde_json::to_value(&self.after)? else {
+            return Ok(Vec::new());
+        };
+
+        let fields = [
+            "name",
+            "japanese_name",
+            "english_name",
+            "anilist_id",
+            "notes",
+            "tmdb_id",
+            "flags",
+        ];
+        let mut result = Vec::new();
+        for field in fields {
+            let before = before.remove(field).unwrap_or_default();
+            let after = after.remove(field).unwrap_or_default();
+            if before != after {
+                result.push(FieldDiff {
+                    field: field.to_owned(),
+                    before,
+                    after,
+                });
+            }
+        }
+        Ok(result)
+    }
+}
+
 /// Audit log data for things related to the trash
 ///
 /// For this data, `account_id` is never null but `entry_id` is.
@@ -298,21 +408,99 @@ impl TrashAction {
     }
 }
 
+/// Audit log data for files restored from the trash
+///
+/// Older restorations are logged as a [`TrashAction`] with `restore` set instead.
+/// For this data, `account_id` is never null.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreFiles {
+    pub files: Vec<FileOperation>,
+    /// The entry the files were restored to
+    pub entry_id: i64,
+}
+
+impl RestoreFiles {
+    pub fn new(entry_id: i64) -> Self {
+        Self {
+            files: Vec::new(),
+            entry_id,
+        }
+    }
+
+    pub fn add_file(&mut self, name: String, failed: bool) {
+        self.files.push(FileOperation { name, failed });
+    }
+}
+
+/// Audit log data for a flag change applied to multiple entries at once
+///
+/// For this data, `entry_id` is always null and `account_id` is only null if the data is deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkEdit {
+    /// A human readable description of the change, e.g. `set hidden, unset adult`
+    pub sql_template: String,
+    /// The entries that were modified
+    pub affected_ids: Vec<i64>,
+    /// The flags of the modified entries before the change, if they all shared the same flags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before_flags: Option<EntryFlags>,
+    /// The flags of the modified entries after the change, if they all share the same flags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_flags: Option<EntryFlags>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum AuditLogData {
     CreateEntry(CreateEntry),
     ScrapeResult(ScrapeResult),
+    ScrapeEntry(ScrapeEntry),
     MoveEntry(MoveEntry),
     RenameFiles(RenameFiles),
     Upload(Upload),
     DeleteFiles(DeleteFiles),
     DeleteEntry(DeleteEntry),
     TrashAction(TrashAction),
+    RestoreFiles(RestoreFiles),
     EditEntry(EditEntry),
     ReportFiles(ReportFiles),
     ReportEntry(ReportEntry),
+    Login(Login),
+    Logout(Logout),
+    LoginFailed(LoginFailed),
+    EditTags(EditTags),
+    BulkEdit(BulkEdit),
+}
+
+impl From<BulkEdit> for AuditLogData {
+    fn from(v: BulkEdit) -> Self {
+        Self::BulkEdit(v)
+    }
+}
+
+impl From<EditTags> for AuditLogData {
+    fn from(v: EditTags) -> Self {
+        Self::EditTags(v)
+    }
+}
+
+impl From<Login> for AuditLogData {
+    fn from(v: Login) -> Self {
+        Self::Login(v)
+    }
+}
+
+impl From<Logout> for AuditLogData {
+    fn from(v: Logout) -> Self {
+        Self::Logout(v)
+    }
+}
+
+impl From<LoginFailed> for AuditLogData {
+    fn from(v: LoginFailed) -> Self {
+        Self::LoginFailed(v)
+    }
 }
 
 impl From<ReportEntry> for AuditLogData {
@@ -333,6 +521,12 @@ impl From<TrashAction> for AuditLogData {
     }
 }
 
+impl From<RestoreFiles> for AuditLogData {
+    fn from(v: RestoreFiles) -> Self {
+        Self::RestoreFiles(v)
+    }
+}
+
 impl From<EditEntry> for AuditLogData {
     fn from(v: EditEntry) -> Self {
         Self::EditEntry(v)
@@ -375,6 +569,12 @@ impl From<ScrapeResult> for AuditLogData {
     }
 }
 
+impl From<ScrapeEntry> for AuditLogData {
+    fn from(v: ScrapeEntry) -> Self {
+        Self::ScrapeEntry(v)
+    }
+}
+
 impl From<CreateEntry> for AuditLogData {
     fn from(v: CreateEntry) -> Self {
         Self::CreateEntry(v)
@@ -444,6 +644,11 @@ impl AuditLogEntry {
         self
     }
 
+    pub fn with_entry(mut self, entry_id: i64) -> Self {
+        self.entry_id = Some(entry_id);
+        self
+    }
+
     pub fn created_at(&self) -> OffsetDateTime {
         OffsetDateTime::from_unix_timestamp_nanos(self.id as i128 * 1_000_000).unwrap_or(OffsetDateTime::UNIX_EPOCH)
     }
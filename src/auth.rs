@@ -1,17 +1,90 @@
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
-    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
 };
 
-pub fn hash_password(password: &str) -> anyhow::Result<String> {
-    let argon2 = Argon2::default();
+use crate::config::Argon2Params;
+
+/// The result of a successful [`validate_password`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordStatus {
+    /// The hash was made with the current parameters.
+    Current,
+    /// The hash was made with outdated parameters and should be re-hashed.
+    Outdated,
+}
+
+impl PasswordStatus {
+    /// Returns `true` if the password is [`Outdated`].
+    ///
+    /// [`Outdated`]: PasswordStatus::Outdated
+    #[must_use]
+    pub fn is_outdated(&self) -> bool {
+        matches!(self, Self::Outdated)
+    }
+}
+
+fn hasher(params: &Argon2Params) -> anyhow::Result<Argon2<'static>> {
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params.to_params()?))
+}
+
+/// Checks whether the hash was made with anything other than the given parameters.
+fn is_outdated(hash: &PasswordHash<'_>, params: &Params) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13 as u32) {
+        return true;
+    }
+
+    match Params::try_from(hash) {
+        Ok(used) => {
+            used.m_cost() != params.m_cost() || used.t_cost() != params.t_cost() || used.p_cost() != params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+pub fn hash_password(password: &str, params: &Argon2Params) -> anyhow::Result<String> {
+    let argon2 = hasher(params)?;
     let salt = SaltString::generate(&mut OsRng);
     Ok(argon2.hash_password(password.as_bytes(), &salt)?.to_string())
 }
 
-pub fn validate_password(password: &str, password_hash: &str) -> anyhow::Result<()> {
-    let argon2 = Argon2::default();
+/// Validates the password against the stored hash.
+///
+/// The hash is verified using the parameters it was made with, which are then
+/// compared against the given parameters to tell whether it needs to be re-hashed.
+pub fn validate_password(password: &str, password_hash: &str, params: &Argon2Params) -> anyhow::Result<PasswordStatus> {
+    let argon2 = hasher(params)?;
     let hash = PasswordHash::new(password_hash)?;
     argon2.verify_password(password.as_bytes(), &hash)?;
-    Ok(())
+    if is_outdated(&hash, argon2.params()) {
+        Ok(PasswordStatus::Outdated)
+    } else {
+        Ok(PasswordStatus::Current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outdated_parameters() {
+        let old = Argon2Params {
+            m_cost: 8192,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        let current = Argon2Params::default();
+        let hash = hash_password("password123", &old).unwrap();
+
+        assert_eq!(
+            validate_password("password123", &hash, &old).unwrap(),
+            PasswordStatus::Current
+        );
+        assert_eq!(
+            validate_password("password123", &hash, &current).unwrap(),
+            PasswordStatus::Outdated
+        );
+        assert!(validate_password("password321", &hash, &current).is_err());
+    }
 }
@@ -3,68 +3,91 @@
 //! This is opt-in per route and only for unauthenticated requests.
 
 use std::{
+    hash::Hash,
     io::Write,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use axum::{
     body::Body,
     http::{
-        header::{CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE},
+        header::{CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, VARY},
         HeaderValue, StatusCode,
     },
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
-use quick_cache::sync::Cache;
-use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use dashmap::DashMap;
 
 use crate::headers::AcceptEncoding;
 
-/// A timed cache value that only lasts for a specified duration before expiring.
+/// A timed cache where every value only lasts for a specified duration before expiring.
+///
+/// Values are stored behind an [`Arc`] so retrieving them is cheap regardless of their size.
 #[derive(Debug)]
-pub struct TimedCachedValue<T> {
-    value: RwLock<Option<(T, Instant)>>,
+pub struct TimedCache<K: Eq + Hash, V> {
+    values: DashMap<K, (Arc<V>, Instant)>,
     ttl: Duration,
 }
 
-impl<T> TimedCachedValue<T> {
+/// A timed cache that only holds a single value.
+pub type TimedCachedValue<T> = TimedCache<(), T>;
+
+impl<K: Eq + Hash, V> TimedCache<K, V> {
     pub fn new(ttl: Duration) -> Self {
         Self {
-            value: RwLock::new(None),
+            values: DashMap::new(),
             ttl,
         }
     }
 
+    /// Returns the cached value for the key, or [`None`] if it cannot be found or is expired
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        let entry = self.values.get(key)?;
+        let (value, created) = entry.value();
+        if created.elapsed() >= self.ttl {
+            None
+        } else {
+            Some(value.clone())
+        }
+    }
+
+    /// Sets the value of the key in the cache and returns the value
+    pub fn insert(&self, key: K, value: V) -> Arc<V> {
+        let value = Arc::new(value);
+        self.values.insert(key, (value.clone(), Instant::now()));
+        value
+    }
+
+    /// Removes the value of the key from the cache
+    pub fn remove(&self, key: &K) {
+        self.values.remove(key);
+    }
+
+    /// Removes every value from the cache
+    pub fn clear(&self) {
+        self.values.clear();
+    }
+}
+
+impl<V> TimedCache<(), V> {
     /// Returns the cached value, or [`None`] if it cannot be found or is expired
-    pub async fn get(&self) -> Option<RwLockReadGuard<'_, T>> {
-        let guard = self.value.read().await;
-        RwLockReadGuard::try_map(guard, |f| {
-            if let Some((value, exp)) = f {
-                if exp.elapsed() >= self.ttl {
-                    None
-                } else {
-                    Some(value)
-                }
-            } else {
-                None
-            }
-        })
-        .ok()
+    pub fn value(&self) -> Option<Arc<V>> {
+        self.get(&())
     }
 
-    /// Sets the value in the cache and returns a read guard to the value
-    pub async fn set(&self, value: T) -> RwLockReadGuard<'_, T> {
-        let mut guard = self.value.write().await;
-        *guard = Some((value, Instant::now()));
-        RwLockWriteGuard::downgrade_map(guard, |f| &f.as_ref().unwrap().0)
+    /// Sets the value in the cache and returns it
+    pub fn set(&self, value: V) -> Arc<V> {
+        self.insert((), value)
     }
 
     /// Invalidates the cache
-    pub async fn invalidate(&self) {
-        let mut guard = self.value.write().await;
-        *guard = None;
+    pub fn invalidate(&self) {
+        self.remove(&())
     }
 }
 
@@ -91,15 +114,53 @@ impl CachedBody {
     }
 }
 
+/// Controls how a cached response is stored and what headers it is sent with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// How long the response is cached for, both server side and client side.
+    pub max_age: Duration,
+    /// Whether the response is cached separately per negotiated encoding.
+    ///
+    /// The `Vary: Accept-Encoding` header is sent regardless, since a cached body
+    /// can always be served brotli compressed.
+    pub vary_by_accept_encoding: bool,
+    /// Whether the response differs depending on the logged in account.
+    ///
+    /// This sends a `Vary: Cookie` header.
+    pub vary_by_account: bool,
+}
+
+impl CachePolicy {
+    pub const fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            vary_by_accept_encoding: false,
+            vary_by_account: false,
+        }
+    }
+
+    fn vary(&self) -> &'static str {
+        if self.vary_by_account {
+            "Accept-Encoding, Cookie"
+        } else {
+            "Accept-Encoding"
+        }
+    }
+}
+
 /// Implements a cache for a response
 #[derive(Clone)]
 pub struct BodyCache {
-    templates: Arc<Cache<&'static str, Option<CachedBody>>>,
+    templates: Arc<DashMap<String, CachedBody>>,
     ttl: Duration,
+    /// The number of responses served from the cache.
+    hits: Arc<AtomicU64>,
+    /// The number of responses that had to be rendered, including bypassed ones.
+    misses: Arc<AtomicU64>,
 }
 
 pub enum CachedTemplateResponse {
-    Cached(Duration, Bytes, bool),
+    Cached(CachePolicy, Bytes, bool),
     Bypass(Response),
     Error,
 }
@@ -107,59 +168,85 @@ pub enum CachedTemplateResponse {
 impl BodyCache {
     pub fn new(ttl: Duration) -> Self {
         Self {
-            templates: Arc::new(Cache::new(10)),
+            templates: Arc::new(DashMap::new()),
             ttl,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    fn get_cached(&self, key: &'static str) -> Option<CachedBody> {
-        let item = self.templates.get(&key)?;
-        if let Some(body) = item {
-            if body.expiry.elapsed() >= self.ttl {
-                None
-            } else {
-                Some(body)
-            }
-        } else {
+    /// Returns the number of cache hits and misses since the server started.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+
+    fn get_cached(&self, key: &str, ttl: Duration) -> Option<CachedBody> {
+        let body = self.templates.get(key)?;
+        if body.expiry.elapsed() >= ttl {
             None
+        } else {
+            Some(body.clone())
         }
     }
 
     pub fn invalidate(&self, key: &'static str) {
         self.templates.remove(key);
+        self.templates.remove(&format!("{key}:br"));
+        self.templates.remove(&format!("{key}:identity"));
+    }
+
+    /// Invalidates every cached body whose key starts with the given prefix.
+    ///
+    /// For example, `entry-42` would invalidate every cached variant of entry 42.
+    pub fn invalidate_prefix(&self, prefix: &str) {
+        self.templates.retain(|key, _| !key.starts_with(prefix));
     }
 
     pub fn invalidate_all(&self) {
         self.templates.clear();
     }
 
+    /// Renders the template and caches it, or returns the cached body if it's available.
+    ///
+    /// If no policy is given then the response is cached for the default TTL of the cache.
     pub async fn cache_template<T: askama::Template + IntoResponse>(
         &self,
         key: &'static str,
         template: T,
         encoding: AcceptEncoding,
         bypass_cache: bool,
+        policy: Option<CachePolicy>,
     ) -> CachedTemplateResponse {
         if bypass_cache {
+            self.misses.fetch_add(1, Ordering::Relaxed);
             return CachedTemplateResponse::Bypass(template.into_response());
         }
 
-        if let Some(cached) = self.get_cached(key) {
+        let policy = policy.unwrap_or(CachePolicy::new(self.ttl));
+        let key = if policy.vary_by_accept_encoding {
+            format!("{key}:{}", if encoding.brotli { "br" } else { "identity" })
+        } else {
+            key.to_owned()
+        };
+
+        if let Some(cached) = self.get_cached(&key, policy.max_age) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
             return if encoding.brotli {
                 match cached.brotli {
-                    Some(bytes) => CachedTemplateResponse::Cached(self.ttl, bytes, true),
-                    None => CachedTemplateResponse::Cached(self.ttl, cached.decompressed, false),
+                    Some(bytes) => CachedTemplateResponse::Cached(policy, bytes, true),
+                    None => CachedTemplateResponse::Cached(policy, cached.decompressed, false),
                 }
             } else {
-                CachedTemplateResponse::Cached(self.ttl, cached.decompressed, false)
+                CachedTemplateResponse::Cached(policy, cached.decompressed, false)
             };
         }
 
         // Cache miss
+        self.misses.fetch_add(1, Ordering::Relaxed);
         if let Ok(rendered) = template.render() {
             let bytes = Bytes::from(rendered);
-            self.templates.insert(key, Some(CachedBody::new(bytes.clone())));
-            CachedTemplateResponse::Cached(self.ttl, bytes, false)
+            self.templates.insert(key, CachedBody::new(bytes.clone()));
+            CachedTemplateResponse::Cached(policy, bytes, false)
         } else {
             CachedTemplateResponse::Error
         }
@@ -169,12 +256,13 @@ impl BodyCache {
 impl IntoResponse for CachedTemplateResponse {
     fn into_response(self) -> Response {
         match self {
-            CachedTemplateResponse::Cached(ttl, bytes, brotli) => {
+            CachedTemplateResponse::Cached(policy, bytes, brotli) => {
                 let mut resp = Response::new(Body::from(bytes));
                 resp.headers_mut().insert(
                     CACHE_CONTROL,
-                    HeaderValue::from_str(&format!("private, max-age={}", ttl.as_secs())).unwrap(),
+                    HeaderValue::from_str(&format!("public, max-age={}", policy.max_age.as_secs())).unwrap(),
                 );
+                resp.headers_mut().insert(VARY, HeaderValue::from_static(policy.vary()));
                 resp.headers_mut()
                     .insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
                 if brotli {
@@ -185,7 +273,7 @@ impl IntoResponse for CachedTemplateResponse {
             }
             CachedTemplateResponse::Bypass(mut resp) => {
                 resp.headers_mut()
-                    .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+                    .insert(CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
                 resp
             }
             CachedTemplateResponse::Error => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
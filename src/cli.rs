@@ -6,7 +6,7 @@
 
 use std::{io::Write, path::PathBuf};
 
-use crate::{auth::hash_password, models::is_valid_username};
+use crate::{auth::hash_password, config::Argon2Params, models::is_valid_username};
 
 pub const PROGRAM_NAME: &str = "jimaku";
 
@@ -15,9 +15,11 @@ pub const PROGRAM_NAME: &str = "jimaku";
 pub enum Command {
     Run,
     Admin,
-    Scrape { path: Option<PathBuf> },
+    Scrape { path: Option<PathBuf>, append: bool },
     Fixtures { path: PathBuf },
     Move { path: PathBuf },
+    Vacuum,
+    Rollback { steps: usize },
 }
 
 macro_rules! quick_exit {
@@ -33,8 +35,11 @@ commands:
   run               Runs the server
   admin             Interactively creates an admin user
   scrape   [path]   Scrapes and creates a fixture file from kitsunekko
-  fixtures <path>   Loads a fixture from the given path
+                    With --append the fixtures are appended to the file as NDJSON
+  fixtures <path>   Loads a fixture file from the given path (JSON array or NDJSON)
   move     [path]   Move directory entry paths to a new location
+  vacuum            Reclaims unused disk space from the databases
+  rollback [steps]  Rolls back the given number of migrations (default: 1)
 
 options:
   -h, --help   Prints this help output
@@ -81,10 +86,10 @@ fn prompt_password() -> std::io::Result<String> {
     }
 }
 
-pub fn prompt_admin_account() -> anyhow::Result<AdminCredentials> {
+pub fn prompt_admin_account(params: &Argon2Params) -> anyhow::Result<AdminCredentials> {
     let username = prompt_username()?;
     let password = prompt_password()?;
-    let password_hash = hash_password(&password)?;
+    let password_hash = hash_password(&password, params)?;
     Ok(AdminCredentials {
         username,
         password_hash,
@@ -105,9 +110,21 @@ impl Command {
             Some(s) => match s.as_str() {
                 "run" => Self::Run,
                 "admin" => Self::Admin,
-                "scrape" => Self::Scrape {
-                    path: args.next().map(PathBuf::from),
-                },
+                "scrape" => {
+                    let mut path = None;
+                    let mut append = false;
+                    for arg in args {
+                        if arg == "--append" {
+                            append = true;
+                        } else if path.is_none() {
+                            path = Some(PathBuf::from(arg));
+                        } else {
+                            quick_exit!("unexpected argument: {}", arg.to_string_lossy());
+                        }
+                    }
+
+                    Self::Scrape { path, append }
+                }
                 "fixtures" => {
                     let Some(path) = args.next().map(PathBuf::from) else {
                         quick_exit!("missing path parameter");
@@ -122,6 +139,20 @@ impl Command {
 
                     Self::Move { path }
                 }
+                "vacuum" => Self::Vacuum,
+                "rollback" => {
+                    let steps = match args.next() {
+                        None => 1,
+                        Some(s) => match s.to_str().and_then(|s| s.parse().ok()) {
+                            Some(steps) => steps,
+                            None => {
+                                quick_exit!("steps must be a positive number");
+                            }
+                        },
+                    };
+
+                    Self::Rollback { steps }
+                }
                 "-h" | "--help" | "help" => {
                     println!("{}", HELP_OUTPUT);
                     std::process::exit(0);
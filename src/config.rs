@@ -6,8 +6,9 @@ use std::{
 
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::key::SecretKey;
+use crate::key::{to_hex, SecretKey};
 use crate::{cli::PROGRAM_NAME, discord::Webhook};
 
 /// The server configuration.
@@ -49,8 +50,86 @@ pub struct Config {
     ///
     /// Microbenching makes it evident that cloning this without an Arc is around ~4x faster.
     pub secret_key: SecretKey,
+    /// Bearer tokens for trusted integrations that are exempt from rate limits.
+    ///
+    /// These are stored as the lowercase hex SHA-256 digest of the token rather
+    /// than the token itself.
+    #[serde(default)]
+    pub trusted_tokens: Vec<String>,
+    /// The SMTP configuration used for sending emails.
+    ///
+    /// If this is not set then email related functionality is disabled.
+    #[serde(default)]
+    pub smtp_config: Option<SmtpConfig>,
+    /// The maximum number of accounts that can be registered from a single IP in a day.
+    ///
+    /// This is tracked in memory so it resets on server restart.
+    #[serde(default = "default_max_registrations_per_ip_per_day")]
+    pub max_registrations_per_ip_per_day: u32,
+    /// Controls who is allowed to register new accounts.
+    #[serde(default)]
+    pub registration_mode: RegistrationMode,
+    /// The number of days audit log entries are kept for.
+    ///
+    /// If this is not set then audit log entries are kept forever.
+    #[serde(default)]
+    pub audit_log_retention_days: Option<u32>,
+    /// The maximum number of files a single entry can have.
+    ///
+    /// Uploads that would go over this limit are rejected. If this is not set
+    /// then there is no limit.
+    #[serde(default)]
+    pub max_upload_files_per_entry: Option<usize>,
+    /// Whether editors are exempt from upload quotas such as [`Self::max_upload_files_per_entry`].
+    #[serde(default)]
+    pub quota_exempt_editors: bool,
+    /// The file extensions, without the leading dot, that can be uploaded.
+    ///
+    /// If this is not set then [`DEFAULT_SUBTITLE_EXTENSIONS`] is used.
+    #[serde(default)]
+    pub allowed_subtitle_extensions: Option<Vec<String>>,
+    /// The file extensions, without the leading dot, that the scrapers download.
+    ///
+    /// These must also be in [`Self::allowed_subtitle_extensions`]. If this is not set
+    /// then [`SCRAPER_EXTENSIONS`] is used.
+    #[serde(default)]
+    pub scraper_allowed_extensions: Option<Vec<String>>,
+    /// The directory names that the scrapers skip.
+    ///
+    /// These are compared case insensitively.
+    #[serde(default)]
+    pub scraper_blocklist: Vec<String>,
+    /// The latency, in milliseconds, after which a request is logged as slow.
+    #[serde(default = "default_slow_request_threshold_ms")]
+    pub slow_request_threshold_ms: u64,
+    /// The delay, in milliseconds, between directory listing requests when scraping jpsubbers.
+    #[serde(default = "default_jpsubbers_request_delay_ms")]
+    pub jpsubbers_request_delay_ms: u64,
+    /// The number of seconds a database call can run before its query is interrupted.
+    ///
+    /// If this is 0 then queries are never interrupted.
+    #[serde(default = "default_db_query_timeout_secs")]
+    pub db_query_timeout_secs: u64,
+    /// Whether to trust the `X-Forwarded-For` header for the client IP.
+    ///
+    /// The rightmost address in the header is used, so this should only be set when the server is
+    /// behind exactly one reverse proxy that appends the connecting IP to the header. Without a
+    /// proxy, or with more than one, clients can spoof their IP.
+    #[serde(default)]
+    pub trust_x_forwarded_for: bool,
+    /// The Argon2id parameters that passwords are hashed with.
+    ///
+    /// Passwords hashed with different parameters are re-hashed on login.
+    #[serde(default)]
+    pub argon2_params: Argon2Params,
 }
 
+/// The file extensions that can be uploaded by default.
+pub const DEFAULT_SUBTITLE_EXTENSIONS: [&str; 8] = ["srt", "ass", "ssa", "zip", "sub", "sup", "idx", "7z"];
+
+/// The file extensions that are scraped by default.
+pub const SCRAPER_EXTENSIONS: [&str; 5] = ["zip", "ass", "srt", "7z", "sup"];
+
 impl Config {
     pub fn new() -> anyhow::Result<Self> {
         Ok(Self {
@@ -63,6 +142,21 @@ impl Config {
             webhook: None,
             server: ServerConfig::default(),
             secret_key: SecretKey::random()?,
+            trusted_tokens: Vec::new(),
+            smtp_config: None,
+            max_registrations_per_ip_per_day: default_max_registrations_per_ip_per_day(),
+            registration_mode: RegistrationMode::default(),
+            audit_log_retention_days: None,
+            max_upload_files_per_entry: None,
+            quota_exempt_editors: false,
+            allowed_subtitle_extensions: None,
+            scraper_allowed_extensions: None,
+            scraper_blocklist: Vec::new(),
+            slow_request_threshold_ms: DEFAULT_SLOW_REQUEST_THRESHOLD_MS,
+            jpsubbers_request_delay_ms: default_jpsubbers_request_delay_ms(),
+            db_query_timeout_secs: default_db_query_timeout_secs(),
+            trust_x_forwarded_for: false,
+            argon2_params: Argon2Params::default(),
         })
     }
 
@@ -115,11 +209,263 @@ impl Config {
         url
     }
 
+    /// Checks if the given bearer token is one of the configured trusted tokens.
+    pub fn is_trusted_token(&self, token: &str) -> bool {
+        if self.trusted_tokens.is_empty() {
+            return false;
+        }
+        let digest = to_hex(&Sha256::digest(token.as_bytes()));
+        self.trusted_tokens.iter().any(|t| t.eq_ignore_ascii_case(&digest))
+    }
+
     pub fn url_to(&self, url: impl Into<std::borrow::Cow<'static, str>>) -> String {
         let mut base = self.canonical_url();
         base.push_str(&url.into());
         base
     }
+
+    /// Returns the file extensions that can be uploaded.
+    pub fn allowed_extensions(&self) -> &[String] {
+        static DEFAULT: OnceLock<Vec<String>> = OnceLock::new();
+        match &self.allowed_subtitle_extensions {
+            Some(extensions) => extensions,
+            None => DEFAULT.get_or_init(|| DEFAULT_SUBTITLE_EXTENSIONS.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    /// Checks if a file with the given extension can be uploaded.
+    pub fn is_allowed_extension(&self, extension: &str) -> bool {
+        self.allowed_extensions().iter().any(|ext| ext == extension)
+    }
+
+    /// Checks if the scrapers should skip the directory with the given name.
+    pub fn is_scraper_blocked(&self, name: &str) -> bool {
+        self.scraper_blocklist.iter().any(|s| s.eq_ignore_ascii_case(name))
+    }
+
+    /// Updates the settings that are safe to change while the server is running.
+    ///
+    /// Every other setting in `loaded` is ignored. Returns the names of the settings that changed.
+    pub fn apply_reloadable(&mut self, loaded: Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.webhook != loaded.webhook {
+            self.webhook = loaded.webhook;
+            changed.push("discord_webhook_url");
+        }
+        if self.smtp_config != loaded.smtp_config {
+            self.smtp_config = loaded.smtp_config;
+            changed.push("smtp_config");
+        }
+        if self.allowed_subtitle_extensions != loaded.allowed_subtitle_extensions {
+            self.allowed_subtitle_extensions = loaded.allowed_subtitle_extensions;
+            changed.push("allowed_subtitle_extensions");
+        }
+        if self.scraper_blocklist != loaded.scraper_blocklist {
+            self.scraper_blocklist = loaded.scraper_blocklist;
+            changed.push("scraper_blocklist");
+        }
+        changed
+    }
+
+    /// Returns the file extensions that the scrapers download.
+    pub fn scraper_extensions(&self) -> &[String] {
+        static DEFAULT: OnceLock<Vec<String>> = OnceLock::new();
+        match &self.scraper_allowed_extensions {
+            Some(extensions) => extensions,
+            None => DEFAULT.get_or_init(|| SCRAPER_EXTENSIONS.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    /// Checks if a file with the given name should be downloaded by the scrapers.
+    pub fn is_scraper_file(&self, name: &str) -> bool {
+        match name.rsplit_once('.') {
+            Some((_, ext)) => self.scraper_extensions().iter().any(|e| e == ext),
+            None => false,
+        }
+    }
+
+    /// Validates the loaded configuration.
+    ///
+    /// Unlike [`Self::load`], which only fails if the file is malformed, this checks
+    /// that the values themselves make sense. Every failure is returned rather than
+    /// stopping at the first one.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        // The key is always 32 bytes due to its type, but an all zero key is a sign
+        // that it was filled in by hand incorrectly.
+        if self.secret_key.iter().all(|b| *b == 0) {
+            errors.push(ConfigError::error("secret_key", "secret key must be 32 random bytes"));
+        }
+
+        match std::fs::metadata(&self.subtitle_path) {
+            Ok(metadata) if !metadata.is_dir() => {
+                errors.push(ConfigError::error("subtitle_path", "subtitle path is not a directory"));
+            }
+            Ok(metadata) if metadata.permissions().readonly() => {
+                errors.push(ConfigError::error("subtitle_path", "subtitle path is not writable"));
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(ConfigError::error(
+                "subtitle_path",
+                format!("could not access subtitle path {}: {e}", self.subtitle_path.display()),
+            )),
+        }
+
+        if self.tmdb_api_key.trim().is_empty() {
+            errors.push(ConfigError::warning(
+                "tmdb_api_key",
+                "TMDB API key is empty, TMDB lookups will not work",
+            ));
+        }
+
+        if let Some(webhook) = &self.webhook {
+            if webhook.url().scheme() != "https" {
+                errors.push(ConfigError::error("discord_webhook_url", "webhook URL must use HTTPS"));
+            }
+        }
+
+        for domain in &self.domains {
+            if !is_valid_hostname(domain) {
+                errors.push(ConfigError::error(
+                    "domains",
+                    format!("{domain:?} is not a valid hostname"),
+                ));
+            }
+        }
+
+        for ext in self.scraper_allowed_extensions.iter().flatten() {
+            if !self.is_allowed_extension(ext) {
+                errors.push(ConfigError::error(
+                    "scraper_allowed_extensions",
+                    format!("{ext:?} is not an allowed upload extension"),
+                ));
+            }
+        }
+
+        if let Err(e) = self.argon2_params.to_params() {
+            errors.push(ConfigError::error(
+                "argon2_params",
+                format!("invalid Argon2 parameters: {e}"),
+            ));
+        }
+
+        if self.production && self.domains.is_empty() {
+            errors.push(ConfigError::warning(
+                "domains",
+                "no domains are configured in production",
+            ));
+        }
+
+        errors
+    }
+}
+
+/// Checks if the given string is a valid hostname as described in RFC 1123.
+fn is_valid_hostname(host: &str) -> bool {
+    if host.is_empty() || host.len() > 253 {
+        return false;
+    }
+
+    host.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    })
+}
+
+/// The severity of a [`ConfigError`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// The server can still run but some functionality might not work.
+    Warning,
+    /// The server cannot run with this configuration.
+    Error,
+}
+
+/// A validation failure returned by [`Config::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The name of the offending field, as it appears in the config file.
+    pub field: &'static str,
+    /// A human readable description of the problem.
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl ConfigError {
+    fn error(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+            severity: Severity::Error,
+        }
+    }
+
+    fn warning(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+            severity: Severity::Warning,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}: {}: {}", self.field, self.message)
+    }
+}
+
+fn default_max_registrations_per_ip_per_day() -> u32 {
+    3
+}
+
+/// The default value of [`Config::slow_request_threshold_ms`].
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD_MS: u64 = 1000;
+
+fn default_slow_request_threshold_ms() -> u64 {
+    DEFAULT_SLOW_REQUEST_THRESHOLD_MS
+}
+
+fn default_jpsubbers_request_delay_ms() -> u64 {
+    200
+}
+
+fn default_db_query_timeout_secs() -> u64 {
+    30
+}
+
+/// Controls how new accounts are allowed to register.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// Anyone can register an account.
+    #[default]
+    Open,
+    /// Registration requires a valid invite code created by an admin.
+    InviteOnly,
+    /// Registration is disabled entirely.
+    Closed,
+}
+
+impl RegistrationMode {
+    pub fn is_invite_only(&self) -> bool {
+        matches!(self, Self::InviteOnly)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Self::Closed)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -147,13 +493,162 @@ impl Default for ServerConfig {
     }
 }
 
+/// The SMTP server configuration used for sending emails.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    /// The SMTP server hostname, e.g. `smtp.example.com`.
+    pub host: String,
+    /// The SMTP server port.
+    ///
+    /// Port 465 uses implicit TLS, every other port uses STARTTLS.
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    /// The username to authenticate with.
+    pub username: String,
+    /// The password to authenticate with.
+    pub password: String,
+    /// The mailbox that emails are sent from, e.g. `Jimaku <noreply@example.com>`.
+    pub from: String,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// The Argon2id parameters used for hashing passwords.
+///
+/// The defaults are the ones recommended by OWASP. Any parameter that is left
+/// out uses its default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Argon2Params {
+    /// The memory cost in KiB.
+    pub m_cost: u32,
+    /// The number of iterations.
+    pub t_cost: u32,
+    /// The degree of parallelism.
+    pub p_cost: u32,
+}
+
+impl Argon2Params {
+    /// Converts these into the parameters used by the hasher, failing if they're out of range.
+    pub fn to_params(&self) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+    }
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            m_cost: argon2::Params::DEFAULT_M_COST,
+            t_cost: argon2::Params::DEFAULT_T_COST,
+            p_cost: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
 impl ServerConfig {
     pub fn address(&self) -> SocketAddr {
         SocketAddr::from((self.ip, self.port))
     }
 }
 
-/// A global variable for the loaded config.
-///
-/// Currently mainly used for templates
-pub static CONFIG: OnceLock<Config> = OnceLock::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_allowed_extensions() {
+        let config = Config::new().unwrap();
+        assert_eq!(config.allowed_extensions(), DEFAULT_SUBTITLE_EXTENSIONS);
+        assert!(config.is_allowed_extension("ass"));
+        assert!(config.is_allowed_extension("7z"));
+        assert!(!config.is_allowed_extension("vtt"));
+    }
+
+    #[test]
+    fn test_overridden_allowed_extensions() {
+        let mut config = Config::new().unwrap();
+        config.allowed_subtitle_extensions = Some(vec!["srt".into(), "vtt".into()]);
+        assert_eq!(config.allowed_extensions(), ["srt", "vtt"]);
+        assert!(config.is_allowed_extension("vtt"));
+        assert!(!config.is_allowed_extension("7z"));
+        assert!(!config.is_allowed_extension("ass"));
+    }
+
+    #[test]
+    fn test_valid_hostnames() {
+        assert!(is_valid_hostname("jimaku.cc"));
+        assert!(is_valid_hostname("localhost"));
+        assert!(is_valid_hostname("sub-domain.example.com"));
+        assert!(!is_valid_hostname("https://jimaku.cc"));
+        assert!(!is_valid_hostname("jimaku..cc"));
+        assert!(!is_valid_hostname("-jimaku.cc"));
+        assert!(!is_valid_hostname(""));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::new().unwrap();
+        config.tmdb_api_key = "key".into();
+        assert_eq!(config.validate(), Vec::new());
+
+        config.tmdb_api_key.clear();
+        config.domains.push("https://jimaku.cc".into());
+        config.secret_key = SecretKey([0; 32]);
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().any(|e| e.field == "tmdb_api_key" && !e.is_error()));
+        assert!(errors.iter().any(|e| e.field == "domains" && e.is_error()));
+        assert!(errors.iter().any(|e| e.field == "secret_key" && e.is_error()));
+    }
+
+    #[test]
+    fn test_scraper_extensions() {
+        let mut config = Config::new().unwrap();
+        config.tmdb_api_key = "key".into();
+        assert!(config.is_scraper_file("[Group] Show - 01.srt"));
+        assert!(config.is_scraper_file("batch.7z"));
+        assert!(!config.is_scraper_file("batch.rar"));
+        assert!(!config.is_scraper_file("readme.txt"));
+        assert!(!config.is_scraper_file("srt"));
+        assert!(config.validate().is_empty());
+
+        config.scraper_allowed_extensions = Some(vec!["srt".into(), "rar".into()]);
+        assert!(!config.is_scraper_file("batch.zip"));
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].field == "scraper_allowed_extensions" && errors[0].is_error());
+    }
+
+    #[test]
+    fn test_partial_argon2_params() {
+        let params: Argon2Params = serde_json::from_str(r#"{"t_cost": 3}"#).unwrap();
+        assert_eq!(
+            params,
+            Argon2Params {
+                t_cost: 3,
+                ..Argon2Params::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_reloadable() {
+        let mut config = Config::new().unwrap();
+        let mut loaded = config.clone();
+        assert!(config.apply_reloadable(loaded.clone()).is_empty());
+
+        loaded.allowed_subtitle_extensions = Some(vec!["srt".into()]);
+        loaded.scraper_blocklist = vec!["Blocked Show".into()];
+        loaded.tmdb_api_key = "changed".into();
+        loaded.server.port = 8080;
+        let changed = config.apply_reloadable(loaded);
+        assert_eq!(changed, ["allowed_subtitle_extensions", "scraper_blocklist"]);
+        assert_eq!(config.allowed_extensions(), ["srt"]);
+        assert!(config.is_scraper_blocked("blocked show"));
+        assert!(!config.is_scraper_blocked("Other Show"));
+        assert!(config.tmdb_api_key.is_empty());
+        assert_ne!(config.server.port, 8080);
+    }
+}
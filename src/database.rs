@@ -1,12 +1,14 @@
 use std::{
     borrow::Cow,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Condvar, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::{Receiver, Sender};
 use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, trace, warn};
 
 /// A trait that all table-like types must meet.
@@ -45,6 +47,36 @@ pub trait Table: Sized {
         query.push_str(" WHERE id = ?");
         query
     }
+
+    /// Creates a select query for every column with the given `WHERE` clause.
+    ///
+    /// If the clause is empty then every row is selected.
+    fn select_query(where_clause: &str) -> String {
+        let mut query = format!("SELECT {} FROM {}", Self::COLUMNS.join(", "), Self::NAME);
+        if !where_clause.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(where_clause);
+        }
+        query
+    }
+
+    /// Creates an insert query with the given column names.
+    ///
+    /// Every column is bound to a parameter in the order given.
+    ///
+    /// Panics if the column is not in the COLUMNS array.
+    fn insert_query(columns: impl AsRef<[&'static str]>) -> String {
+        let columns = columns.as_ref();
+        if let Some(column) = columns.iter().find(|c| !Self::COLUMNS.contains(c)) {
+            panic!("Column {} is not in the COLUMNS array", column);
+        }
+        format!(
+            "INSERT INTO {}({}) VALUES ({})",
+            Self::NAME,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        )
+    }
 }
 
 type SqliteCall = Box<dyn FnOnce(&mut rusqlite::Connection) + Send + 'static>;
@@ -53,7 +85,10 @@ type InitFn = Arc<dyn Fn(&mut rusqlite::Connection) -> rusqlite::Result<()> + Se
 const UNREACHABLE: &str = "connection communication channels unexpectedly terminated";
 
 enum Message {
+    /// A call that is interrupted once it runs for longer than the query timeout, if any.
     Call(SqliteCall),
+    /// A call that is never interrupted, e.g. for maintenance.
+    UnboundedCall(SqliteCall),
     Terminate,
 }
 
@@ -64,6 +99,7 @@ pub struct DatabaseBuilder {
     path: PathBuf,
     max_connections: usize,
     init: Option<InitFn>,
+    query_timeout: Option<Duration>,
 }
 
 impl DatabaseBuilder {
@@ -72,9 +108,20 @@ impl DatabaseBuilder {
             path,
             max_connections: 10,
             init: None,
+            query_timeout: Some(Duration::from_secs(30)),
         }
     }
 
+    /// Configure how long a call can run before its connection is interrupted.
+    ///
+    /// Interrupting a connection makes the currently running query fail with
+    /// `SQLITE_INTERRUPT`, see [`is_interrupted`]. A zero duration disables this.
+    /// Defaults to 30 seconds.
+    pub fn query_timeout(mut self, timeout: Duration) -> Self {
+        self.query_timeout = Some(timeout).filter(|t| !t.is_zero());
+        self
+    }
+
     /// Configure how many connections to open.
     ///
     /// These connections are each a separate thread.
@@ -125,6 +172,7 @@ impl DatabaseBuilder {
                 self.path.clone(),
                 result_sender.clone(),
                 self.init.clone(),
+                self.query_timeout,
                 receiver.clone(),
             ));
         }
@@ -182,6 +230,55 @@ impl Database {
         receiver.await.expect(UNREACHABLE)
     }
 
+    /// Call a function in a background thread with a connection and get the result asynchronously.
+    ///
+    /// Unlike [`Database::call`] this is never interrupted by the query timeout, so it should
+    /// only be used for maintenance that is expected to take a while, e.g. `VACUUM`.
+    pub async fn call_unbounded<F, R>(&self, func: F) -> R
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+
+        self.sender
+            .send(Message::UnboundedCall(Box::new(move |conn| {
+                let _ = sender.send(func(conn));
+            })))
+            .expect(UNREACHABLE);
+
+        receiver.await.expect(UNREACHABLE)
+    }
+
+    /// Call a function in a background thread with a connection and get the result asynchronously,
+    /// unless the operation is cancelled.
+    ///
+    /// The token is cancelled if the returned future is dropped, e.g. when a request times out.
+    /// If the token is cancelled before a connection picks up the call then the function is never
+    /// run and `None` is returned. Long running functions should check the token passed to them
+    /// periodically and bail out early once it's cancelled.
+    pub async fn call_cancellable<F, R>(&self, func: F, cancel: CancellationToken) -> Option<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection, &CancellationToken) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let guard = cancel.clone().drop_guard();
+
+        self.sender
+            .send(Message::Call(Box::new(move |conn| {
+                if cancel.is_cancelled() {
+                    return;
+                }
+                let _ = sender.send(func(conn, &cancel));
+            })))
+            .expect(UNREACHABLE);
+
+        let result = receiver.await.ok();
+        guard.disarm();
+        result
+    }
+
     /// Execute the given query with the given parameters with a connection from the pool.
     pub async fn execute<Q, P>(&self, query: Q, params: P) -> rusqlite::Result<usize>
     where
@@ -192,6 +289,31 @@ impl Database {
         self.call(move |conn| conn.execute(query.as_ref(), params)).await
     }
 
+    /// Execute the given upsert query with the given parameters with a connection from the pool.
+    ///
+    /// This is functionally identical to [`Database::execute`] except it signals that
+    /// the query is expected to have an `ON CONFLICT` clause.
+    pub async fn upsert<Q, P>(&self, query: Q, params: P) -> rusqlite::Result<usize>
+    where
+        Q: Into<Cow<'static, str>> + Send,
+        P: rusqlite::Params + Send + 'static,
+    {
+        self.execute(query, params).await
+    }
+
+    /// Execute the given upsert query with the given parameters and returns the affected row, if any.
+    ///
+    /// The query *must* end in `RETURNING *` for the row to be converted to the specified type.
+    /// If the conflict clause is `DO NOTHING` and a conflict happens then `None` is returned.
+    pub async fn upsert_row<T, Q, P>(&self, query: Q, params: P) -> rusqlite::Result<Option<T>>
+    where
+        T: Table + Send + 'static,
+        P: rusqlite::Params + Send + 'static,
+        Q: Into<Cow<'static, str>> + Send,
+    {
+        self.get(query, params).await
+    }
+
     /// Execute the given query with a connection from the pool.
     pub async fn execute_batch<Q>(&self, query: Q) -> rusqlite::Result<()>
     where
@@ -247,7 +369,7 @@ impl Database {
         T::Id: rusqlite::ToSql + Send + 'static,
     {
         self.call(move |conn| -> rusqlite::Result<Option<T>> {
-            let query = format!("SELECT * FROM {} WHERE id=?", T::NAME);
+            let query = T::select_query("id = ?");
             let mut stmt = conn.prepare_cached(&query)?;
             match stmt.query_row(rusqlite::params![id], T::from_row) {
                 Ok(value) => Ok(Some(value)),
@@ -270,12 +392,55 @@ impl Database {
         let query = query.into();
         self.call(move |conn| -> rusqlite::Result<Vec<T>> {
             let mut stmt = conn.prepare_cached(query.as_ref())?;
-            let result = match stmt.query_map(params, T::from_row) {
-                Ok(value) => value.collect(),
-                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(Vec::new()),
-                Err(e) => Err(e),
+            let rows = match stmt.query_map(params, T::from_row) {
+                Ok(rows) => rows,
+                Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(Vec::new()),
+                Err(e) => return Err(e),
             };
-            result
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Execute the query with the given parameters and returns a page of results.
+    ///
+    /// This appends `LIMIT ? OFFSET ?` to the query along with the respective parameters.
+    /// Therefore the query must not have a `LIMIT` clause of its own.
+    pub async fn paginate<T, Q, P>(&self, query: Q, params: P, limit: usize, offset: usize) -> rusqlite::Result<Vec<T>>
+    where
+        T: Table + Send + 'static,
+        P: IntoIterator<Item = Box<dyn rusqlite::ToSql + Send>> + Send + 'static,
+        Q: Into<Cow<'static, str>> + Send,
+    {
+        let mut query = query.into().into_owned();
+        query.push_str(" LIMIT ? OFFSET ?");
+        self.call(move |conn| -> rusqlite::Result<Vec<T>> {
+            let mut stmt = conn.prepare_cached(&query)?;
+            let extra: [Box<dyn rusqlite::ToSql + Send>; 2] = [Box::new(limit as i64), Box::new(offset as i64)];
+            let params = rusqlite::params_from_iter(params.into_iter().chain(extra));
+            let rows = match stmt.query_map(params, T::from_row) {
+                Ok(rows) => rows,
+                Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Execute the given `SELECT COUNT(*)` query and returns the count.
+    ///
+    /// This is mainly used alongside [`Database::paginate`] for pagination metadata.
+    pub async fn count<Q, P>(&self, count_query: Q, params: P) -> rusqlite::Result<u64>
+    where
+        P: rusqlite::Params + Send + 'static,
+        Q: Into<Cow<'static, str>> + Send,
+    {
+        let query = count_query.into();
+        self.call(move |conn| -> rusqlite::Result<u64> {
+            let mut stmt = conn.prepare_cached(query.as_ref())?;
+            let count: i64 = stmt.query_row(params, |row| row.get(0))?;
+            Ok(count.max(0) as u64)
         })
         .await
     }
@@ -295,6 +460,23 @@ impl Database {
         .await
     }
 
+    /// Reclaims unused disk space by running `VACUUM` on the database.
+    ///
+    /// Since `VACUUM` cannot run while there are pending WAL frames, the WAL
+    /// is checkpointed and truncated beforehand. This is exempt from the query timeout.
+    pub async fn vacuum(&self) -> rusqlite::Result<()> {
+        self.call_unbounded(|conn| {
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", [], |_| Ok(()))?;
+            conn.execute_batch("VACUUM;")
+        })
+        .await
+    }
+
+    /// Returns the total number of pages in the database file.
+    pub async fn page_count(&self) -> rusqlite::Result<i64> {
+        self.get_row("PRAGMA page_count;", [], |row| row.get(0)).await
+    }
+
     /// Gets the value from the key-value store. Returns `None` if not found.
     ///
     /// Unlike other functions here, all errors are coerced into `None` for usability here.
@@ -317,15 +499,16 @@ impl Database {
         .flatten()
     }
 
-    /// Updates the value in the key-value store.
+    /// Updates the value in the key-value store, inserting it if it doesn't exist.
     pub async fn update_storage<T>(&self, key: &'static str, value: T) -> rusqlite::Result<()>
     where
         T: rusqlite::types::ToSql + Send + 'static,
     {
         self.call(move |conn| {
-            let query = "UPDATE storage SET value = ? WHERE name = ?";
+            let query =
+                "INSERT INTO storage(name, value) VALUES (?, ?) ON CONFLICT(name) DO UPDATE SET value = excluded.value";
             let mut stmt = conn.prepare_cached(query)?;
-            stmt.execute((value, key))?;
+            stmt.execute((key, value))?;
             Ok(())
         })
         .await
@@ -368,6 +551,7 @@ impl Worker {
         path: PathBuf,
         result_sender: mpsc::Sender<rusqlite::Result<()>>,
         init: Option<InitFn>,
+        query_timeout: Option<Duration>,
         receiver: Receiver<Message>,
     ) -> Self {
         let thread = thread::spawn(move || {
@@ -409,11 +593,25 @@ impl Worker {
 
             trace!("database connection worker {} has signaled completion", id);
 
+            let watchdog = query_timeout.map(|timeout| Watchdog::new(id, timeout, &connection));
             while let Ok(msg) = receiver.recv() {
                 match msg {
                     Message::Call(func) => {
                         trace!("database connection worker {} received request to process call", id);
-                        func(&mut connection)
+                        if let Some(watchdog) = &watchdog {
+                            watchdog.start();
+                        }
+                        func(&mut connection);
+                        if let Some(watchdog) = &watchdog {
+                            watchdog.finish();
+                        }
+                    }
+                    Message::UnboundedCall(func) => {
+                        trace!(
+                            "database connection worker {} received request to process unbounded call",
+                            id
+                        );
+                        func(&mut connection);
                     }
                     Message::Terminate => break,
                 }
@@ -445,6 +643,83 @@ impl Worker {
     }
 }
 
+/// The state of the call a [`Watchdog`] is watching.
+#[derive(Debug, Clone, Copy)]
+enum WatchdogState {
+    Idle,
+    Running(Instant),
+    Exited,
+}
+
+/// Interrupts a connection whose call has been running for longer than the timeout.
+///
+/// Each worker has its own watchdog thread. It exits once the worker drops it.
+struct Watchdog {
+    shared: Arc<(Mutex<WatchdogState>, Condvar)>,
+}
+
+impl Watchdog {
+    fn new(id: usize, timeout: Duration, connection: &rusqlite::Connection) -> Self {
+        let shared = Arc::new((Mutex::new(WatchdogState::Idle), Condvar::new()));
+        let handle = connection.get_interrupt_handle();
+        let inner = shared.clone();
+        thread::spawn(move || {
+            let (lock, condvar) = &*inner;
+            let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+            loop {
+                match *state {
+                    WatchdogState::Exited => break,
+                    WatchdogState::Idle => state = condvar.wait(state).unwrap_or_else(|e| e.into_inner()),
+                    WatchdogState::Running(start) => {
+                        let elapsed = start.elapsed();
+                        if elapsed >= timeout {
+                            // This is done while holding the lock so a call that has already
+                            // finished can never be interrupted, see Watchdog::finish
+                            warn!(
+                                worker = id,
+                                ?elapsed,
+                                "interrupting database call that exceeded the query timeout"
+                            );
+                            handle.interrupt();
+                            *state = WatchdogState::Idle;
+                        } else {
+                            state = condvar
+                                .wait_timeout(state, timeout - elapsed)
+                                .unwrap_or_else(|e| e.into_inner())
+                                .0;
+                        }
+                    }
+                }
+            }
+        });
+        Self { shared }
+    }
+
+    fn set(&self, new: WatchdogState) {
+        let (lock, condvar) = &*self.shared;
+        *lock.lock().unwrap_or_else(|e| e.into_inner()) = new;
+        condvar.notify_one();
+    }
+
+    fn start(&self) {
+        self.set(WatchdogState::Running(Instant::now()));
+    }
+
+    /// Disarms the watchdog.
+    ///
+    /// Once this returns the finished call can no longer be interrupted, so the
+    /// connection is safe to use for the next call.
+    fn finish(&self) {
+        self.set(WatchdogState::Idle);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.set(WatchdogState::Exited);
+    }
+}
+
 impl std::fmt::Debug for Worker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Worker")
@@ -535,6 +810,14 @@ pub fn is_unique_constraint_violation(e: &rusqlite::Error) -> bool {
     }
 }
 
+/// Checks whether an error is due to the query being interrupted, e.g. by the query timeout.
+pub fn is_interrupted(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(error, _) => error.code == rusqlite::ErrorCode::OperationInterrupted,
+        _ => false,
+    }
+}
+
 /// Returns (and creates) the directory for the main.db file
 pub fn directory() -> anyhow::Result<PathBuf> {
     use anyhow::Context;
@@ -575,6 +858,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_query_helpers() {
+        assert_eq!(
+            Foo::select_query("id = ?"),
+            "SELECT id, name, age FROM foo WHERE id = ?"
+        );
+        assert_eq!(Foo::select_query(""), "SELECT id, name, age FROM foo");
+        assert_eq!(
+            Foo::insert_query(["name", "age"]),
+            "INSERT INTO foo(name, age) VALUES (?, ?)"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_query_unknown_column() {
+        Foo::insert_query(["name", "height"]);
+    }
+
+    #[tokio::test]
+    async fn test_call_cancellable() {
+        let conn = Database::file(":memory:").connections(1).open().await.unwrap();
+        let token = CancellationToken::new();
+        let result = conn
+            .call_cancellable(|conn, _| conn.query_row("SELECT 1", [], |r| r.get::<_, i64>(0)), token)
+            .await;
+        assert_eq!(result.transpose().unwrap(), Some(1));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = conn.call_cancellable(|_, _| 1, token).await;
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let conn = Database::file(":memory:")
+            .connections(1)
+            .query_timeout(Duration::from_millis(50))
+            .open()
+            .await
+            .unwrap();
+        let result = conn
+            .call(|conn| {
+                let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c";
+                conn.query_row(sql, [], |r| r.get::<_, i64>(0))
+            })
+            .await;
+        assert!(result.is_err_and(|e| is_interrupted(&e)));
+
+        // The connection is still usable afterwards
+        let result = conn
+            .call(|conn| conn.query_row("SELECT 1", [], |r| r.get::<_, i64>(0)))
+            .await;
+        assert_eq!(result.unwrap(), 1);
+
+        let result = conn
+            .call_unbounded(|conn| {
+                let sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 5000000) \
+                           SELECT count(*) FROM c";
+                conn.query_row(sql, [], |r| r.get::<_, i64>(0))
+            })
+            .await;
+        assert_eq!(result.unwrap(), 5000000);
+    }
+
     #[tokio::test]
     async fn test_basic_connection() {
         let conn = Database::file(":memory:")
@@ -607,6 +956,117 @@ mod tests {
         );
     }
 
+    async fn paginated_database() -> Database {
+        Database::file(":memory:")
+            .with_init(|con| {
+                con.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS foo(id INTEGER PRIMARY KEY, name TEXT, age INTEGER);
+            INSERT INTO foo(name, age) VALUES ('bob', 20), ('tanya', 25), ('phil', 25), ('ann', 30), ('joe', 35);",
+                )
+            })
+            .open()
+            .await
+            .expect("could not connect DB")
+    }
+
+    #[tokio::test]
+    async fn test_paginate() {
+        let conn = paginated_database().await;
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo ORDER BY id", Vec::new(), 2, 0)
+            .await
+            .expect("paginate failed to run");
+        assert_eq!(page.iter().map(|f| f.id).collect::<Vec<_>>(), vec![1, 2]);
+
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo ORDER BY id", Vec::new(), 2, 4)
+            .await
+            .expect("paginate failed to run");
+        assert_eq!(page.iter().map(|f| f.id).collect::<Vec<_>>(), vec![5]);
+
+        let params: Vec<Box<dyn rusqlite::ToSql + Send>> = vec![Box::new(25)];
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo WHERE age = ? ORDER BY id", params, 10, 1)
+            .await
+            .expect("paginate failed to run");
+        assert_eq!(page.iter().map(|f| f.id).collect::<Vec<_>>(), vec![3]);
+
+        let total = conn
+            .count("SELECT COUNT(*) FROM foo WHERE age = ?", (25,))
+            .await
+            .expect("count failed to run");
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_paginate_boundaries() {
+        let conn = paginated_database().await;
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo ORDER BY id", Vec::new(), 10, 100)
+            .await
+            .expect("paginate failed to run");
+        assert!(page.is_empty());
+
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo WHERE age > 100", Vec::new(), 10, 0)
+            .await
+            .expect("paginate failed to run");
+        assert!(page.is_empty());
+
+        let page: Vec<Foo> = conn
+            .paginate("SELECT * FROM foo ORDER BY id", Vec::new(), 0, 0)
+            .await
+            .expect("paginate failed to run");
+        assert!(page.is_empty());
+
+        let total = conn
+            .count("SELECT COUNT(*) FROM foo WHERE age > 100", [])
+            .await
+            .expect("count failed to run");
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_row() {
+        let conn = Database::file(":memory:")
+            .connections(1)
+            .with_init(|con| {
+                con.execute_batch("CREATE TABLE foo(id INTEGER PRIMARY KEY, name TEXT UNIQUE, age INTEGER);")
+            })
+            .open()
+            .await
+            .expect("could not connect DB");
+
+        let query = "INSERT INTO foo(name, age) VALUES (?, ?) ON CONFLICT DO UPDATE SET age = EXCLUDED.age RETURNING *";
+        let foo: Option<Foo> = conn
+            .upsert_row(query, ("bob", 20))
+            .await
+            .expect("upsert_row failed to run");
+        assert_eq!(foo.map(|f| (f.id, f.age)), Some((1, 20)));
+
+        let foo: Option<Foo> = conn
+            .upsert_row(query, ("bob", 21))
+            .await
+            .expect("upsert_row failed to run");
+        assert_eq!(foo.map(|f| (f.id, f.age)), Some((1, 21)));
+
+        let query = "INSERT INTO foo(name, age) VALUES (?, ?) ON CONFLICT DO NOTHING RETURNING *";
+        let foo: Option<Foo> = conn
+            .upsert_row(query, ("bob", 22))
+            .await
+            .expect("upsert_row failed to run");
+        assert!(foo.is_none());
+
+        let changed = conn
+            .upsert(
+                "INSERT INTO foo(name, age) VALUES (?, ?) ON CONFLICT DO UPDATE SET age = EXCLUDED.age",
+                ("phil", 30),
+            )
+            .await
+            .expect("upsert failed to run");
+        assert_eq!(changed, 1);
+    }
+
     #[test]
     fn test_update_query_creation() {
         let query = Foo::update_query(["name", "age"]);
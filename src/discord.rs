@@ -1,10 +1,11 @@
 //! Support for Discord's webhook
 
-use std::{borrow::Cow, str::FromStr};
+use std::{borrow::Cow, str::FromStr, time::Duration};
 
 use regex::Regex;
 use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize};
 use std::sync::OnceLock;
+use tracing::warn;
 
 use crate::models::Account;
 
@@ -25,7 +26,7 @@ fn discord_webhook_url_regex() -> &'static Regex {
 }
 
 /// A Discord Webhook to send to.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Webhook {
     url: reqwest::Url,
 }
@@ -68,6 +69,11 @@ pub struct PreparedWebhookRequest<T> {
 }
 
 impl Webhook {
+    /// Returns the URL of the webhook.
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
     /// Prepare a request for sending with the given JSON value.
     pub fn prepare<T: Serialize>(&self, json: T) -> PreparedWebhookRequest<T> {
         PreparedWebhookRequest {
@@ -77,11 +83,18 @@ impl Webhook {
     }
 }
 
+/// The longest that a rate limited webhook request waits before retrying.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+}
+
 impl<T: Serialize> PreparedWebhookRequest<T> {
-    /// Sends the request to Discord
-    pub async fn send(self, client: &reqwest::Client) -> Option<reqwest::StatusCode> {
+    async fn post(&self, client: &reqwest::Client) -> reqwest::Result<reqwest::Response> {
         client
-            .post(self.url)
+            .post(self.url.clone())
             .json(&self.json)
             .header(
                 reqwest::header::USER_AGENT,
@@ -89,8 +102,39 @@ impl<T: Serialize> PreparedWebhookRequest<T> {
             )
             .send()
             .await
-            .map(|r| r.status())
-            .ok()
+    }
+
+    /// Sends the request to Discord
+    ///
+    /// If Discord rate limits the request then it waits for the requested
+    /// amount of time and retries once.
+    pub async fn send(self, client: &reqwest::Client) -> Option<reqwest::StatusCode> {
+        let response = match self.post(client).await {
+            Ok(r) if r.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = r
+                    .json::<RateLimited>()
+                    .await
+                    .ok()
+                    .and_then(|r| Duration::try_from_secs_f64(r.retry_after).ok())
+                    .unwrap_or(Duration::from_secs(1))
+                    .min(MAX_RETRY_AFTER);
+                tokio::time::sleep(retry_after).await;
+                self.post(client).await
+            }
+            response => response,
+        };
+
+        match response {
+            Ok(r) if r.status().is_success() => Some(r.status()),
+            Ok(r) => {
+                warn!(status = %r.status(), "could not send discord webhook");
+                Some(r.status())
+            }
+            Err(e) => {
+                warn!(error = %e, "could not send discord webhook");
+                None
+            }
+        }
     }
 }
 
@@ -107,6 +151,24 @@ struct AlertAuthor {
     url: String,
 }
 
+/// The maximum number of characters Discord allows in an embed description.
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+/// Truncates the description to fit within [`MAX_DESCRIPTION_LENGTH`].
+fn truncate_description(description: &str) -> Cow<'_, str> {
+    const SUFFIX: &str = "…(truncated)";
+    match description.char_indices().nth(MAX_DESCRIPTION_LENGTH) {
+        None => Cow::Borrowed(description),
+        Some(_) => {
+            let end = description
+                .char_indices()
+                .nth(MAX_DESCRIPTION_LENGTH - SUFFIX.chars().count())
+                .map_or(description.len(), |(idx, _)| idx);
+            Cow::Owned(format!("{}{SUFFIX}", &description[..end]))
+        }
+    }
+}
+
 /// An actual structured alert to send to Discord.
 ///
 /// This is basically just an embed builder.
@@ -115,6 +177,7 @@ pub struct Alert {
     url: Option<Cow<'static, str>>,
     author: Option<AlertAuthor>,
     fields: Vec<AlertField>,
+    max_fields: usize,
     description: Option<Cow<'static, str>>,
     color: u32,
     username: Cow<'static, str>,
@@ -127,6 +190,10 @@ impl Alert {
     pub const SUCCESS: u32 = 0x1c7951;
     /// The alert color for error.
     pub const ERROR: u32 = 0xa4392f;
+    /// The default maximum number of fields shown.
+    ///
+    /// Discord allows up to 25 fields, one is reserved for summarising the remaining fields.
+    pub const DEFAULT_MAX_FIELDS: usize = 24;
 
     const fn new_with(color: u32, title: Cow<'static, str>) -> Self {
         Self {
@@ -134,6 +201,7 @@ impl Alert {
             url: None,
             author: None,
             fields: Vec::new(),
+            max_fields: Self::DEFAULT_MAX_FIELDS,
             description: None,
             color,
             username: Cow::Borrowed("Jimaku"),
@@ -162,9 +230,11 @@ impl Alert {
         self
     }
 
+    /// Sets the URL to a path on the website.
+    ///
+    /// The path is made absolute using the current config when the alert is sent.
     pub fn url(mut self, url: impl Into<Cow<'static, str>>) -> Self {
-        let url = crate::CONFIG.get().unwrap().url_to(url);
-        self.url = Some(Cow::Owned(url));
+        self.url = Some(url.into());
         self
     }
 
@@ -173,6 +243,12 @@ impl Alert {
         self
     }
 
+    /// Sets the maximum number of fields shown before the rest are summarised.
+    pub fn max_fields(mut self, max_fields: usize) -> Self {
+        self.max_fields = max_fields.min(Self::DEFAULT_MAX_FIELDS);
+        self
+    }
+
     pub fn name(mut self, username: impl Into<Cow<'static, str>>) -> Self {
         self.username = username.into();
         self
@@ -206,15 +282,24 @@ impl Alert {
     }
 
     pub fn account(mut self, account: Account) -> Self {
-        let mut url = crate::CONFIG.get().unwrap().canonical_url();
-        url.push_str("/user/");
-        url.push_str(&account.name);
+        let url = format!("/user/{}", account.name);
         self.author = Some(AlertAuthor {
             name: account.name,
             url,
         });
         self
     }
+
+    /// Makes every URL that is a path on the website absolute using the given base URL.
+    pub fn resolve_urls(mut self, base: &str) -> Self {
+        if let Some(url) = self.url.as_mut().filter(|url| url.starts_with('/')) {
+            *url = Cow::Owned(format!("{base}{url}"));
+        }
+        if let Some(author) = self.author.as_mut().filter(|author| author.url.starts_with('/')) {
+            author.url.insert_str(0, base);
+        }
+        self
+    }
 }
 
 struct InnerEmbed<'a> {
@@ -232,13 +317,25 @@ impl<'a> Serialize for InnerEmbed<'a> {
             map.serialize_entry("url", url)?;
         }
         if let Some(description) = self.embed.description.as_deref() {
-            map.serialize_entry("description", description)?;
+            map.serialize_entry("description", &truncate_description(description))?;
         }
         if let Some(author) = &self.embed.author {
             map.serialize_entry("author", author)?;
         }
         map.serialize_entry("color", &self.embed.color)?;
-        map.serialize_entry("fields", &self.embed.fields)?;
+        let fields = &self.embed.fields;
+        let max_fields = self.embed.max_fields;
+        if fields.len() > max_fields {
+            let overflow = AlertField {
+                name: format!("({} more fields)", fields.len() - max_fields),
+                value: String::from("…"),
+                inline: false,
+            };
+            let fields: Vec<&AlertField> = fields[..max_fields].iter().chain(std::iter::once(&overflow)).collect();
+            map.serialize_entry("fields", &fields)?;
+        } else {
+            map.serialize_entry("fields", fields)?;
+        }
         map.end()
     }
 }
@@ -254,3 +351,31 @@ impl Serialize for Alert {
         map.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_description() {
+        assert!(matches!(truncate_description("short"), Cow::Borrowed("short")));
+        let exact = "あ".repeat(MAX_DESCRIPTION_LENGTH);
+        assert!(matches!(truncate_description(&exact), Cow::Borrowed(_)));
+
+        let long = "あ".repeat(MAX_DESCRIPTION_LENGTH + 1);
+        let truncated = truncate_description(&long);
+        assert_eq!(truncated.chars().count(), MAX_DESCRIPTION_LENGTH);
+        assert!(truncated.ends_with("あ…(truncated)"));
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let alert = Alert::info("test").url("/entry/1").resolve_urls("https://jimaku.cc");
+        assert_eq!(alert.url.as_deref(), Some("https://jimaku.cc/entry/1"));
+
+        let alert = Alert::info("test")
+            .external_url("https://anilist.co/anime/1")
+            .resolve_urls("https://jimaku.cc");
+        assert_eq!(alert.url.as_deref(), Some("https://anilist.co/anime/1"));
+    }
+}
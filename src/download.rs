@@ -1,9 +1,60 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use hmac::Mac;
+use serde::Deserialize;
 use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
-/// Ensures the path is valid and does not traverse outside of the base path.
-pub fn validate_path(base: &Path, requested: &str) -> Option<PathBuf> {
+use crate::key::SecretKey;
+
+/// How long a signed download link is valid for.
+pub const SIGNED_DOWNLOAD_DURATION: Duration = Duration::from_secs(3600);
+
+/// The query parameters of a signed download link.
+#[derive(Debug, Default, Deserialize)]
+pub struct SignedDownload {
+    /// The signature of the download, see [`sign_download`].
+    #[serde(default)]
+    pub token: Option<String>,
+    /// The UNIX timestamp, in seconds, when the link expires.
+    #[serde(default)]
+    pub expires: Option<i64>,
+}
+
+fn download_signature(key: &SecretKey, entry_id: i64, filename: &str, expires: i64) -> hmac::Hmac<sha2::Sha256> {
+    let mut mac = key.hmac();
+    mac.update(format!("{entry_id}:{}:{expires}", filename.trim_start_matches('/')).as_bytes());
+    mac
+}
+
+/// Signs a download of the given file that is valid until the `expires` UNIX timestamp.
+///
+/// The returned token is an HMAC-SHA256 of `entry_id:filename:expires` in URL-safe base64.
+pub fn sign_download(key: &SecretKey, entry_id: i64, filename: &str, expires: i64) -> String {
+    let signature = download_signature(key, entry_id, filename, expires)
+        .finalize()
+        .into_bytes();
+    BASE64_URL_SAFE_NO_PAD.encode(signature)
+}
+
+/// Verifies a token returned by [`sign_download`].
+///
+/// This does not check whether the link has expired.
+pub fn verify_download(key: &SecretKey, entry_id: i64, filename: &str, expires: i64, token: &str) -> bool {
+    let Ok(signature) = BASE64_URL_SAFE_NO_PAD.decode(token.as_bytes()) else {
+        return false;
+    };
+    download_signature(key, entry_id, filename, expires)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+/// Joins the requested path onto the base path, ensuring it does not traverse outside of it.
+///
+/// This only checks the path lexically, so it works for files that don't exist yet.
+/// Prefer [`validate_path`] for existing files since it also resolves symbolic links.
+pub fn join_path(base: &Path, requested: &str) -> Option<PathBuf> {
     let path = Path::new(requested.trim_start_matches('/'));
     let mut path_to_file = base.to_path_buf();
     for component in path.components() {
@@ -22,12 +73,28 @@ pub fn validate_path(base: &Path, requested: &str) -> Option<PathBuf> {
     Some(path_to_file)
 }
 
+/// Ensures the path exists and does not traverse outside of the base path.
+///
+/// Unlike [`join_path`] this resolves symbolic links, so a file that is a symlink
+/// pointing outside of the base path is rejected. The returned path is the resolved one,
+/// so it is the file that was actually checked.
+pub fn validate_path(base: &Path, requested: &str) -> Option<PathBuf> {
+    let path = join_path(base, requested)?;
+    let canonical_base = base.canonicalize().ok()?;
+    let canonical = path.canonicalize().ok()?;
+    canonical.starts_with(canonical_base).then_some(canonical)
+}
+
 /// A download response
 pub enum DownloadResponse {
     /// The file that is being downloaded
     File(Response),
     /// The file is not found
     NotFound,
+    /// The signed download link has an invalid token
+    InvalidToken,
+    /// The signed download link has expired
+    Expired,
 }
 
 impl IntoResponse for DownloadResponse {
@@ -35,6 +102,56 @@ impl IntoResponse for DownloadResponse {
         match self {
             DownloadResponse::File(r) => r,
             DownloadResponse::NotFound => StatusCode::NOT_FOUND.into_response(),
+            DownloadResponse::InvalidToken => StatusCode::FORBIDDEN.into_response(),
+            DownloadResponse::Expired => StatusCode::GONE.into_response(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_download() {
+        let key = SecretKey([7; 32]);
+        let token = sign_download(&key, 1, "foo.srt", 1000);
+        assert!(verify_download(&key, 1, "foo.srt", 1000, &token));
+        assert!(verify_download(&key, 1, "/foo.srt", 1000, &token));
+        assert!(!verify_download(&key, 2, "foo.srt", 1000, &token));
+        assert!(!verify_download(&key, 1, "bar.srt", 1000, &token));
+        assert!(!verify_download(&key, 1, "foo.srt", 2000, &token));
+        assert!(!verify_download(&SecretKey([8; 32]), 1, "foo.srt", 1000, &token));
+        assert!(!verify_download(&key, 1, "foo.srt", 1000, "not a token"));
+    }
+
+    #[test]
+    fn test_join_path() {
+        let base = Path::new("/subtitles/entry");
+        assert_eq!(join_path(base, "foo.srt"), Some(base.join("foo.srt")));
+        assert_eq!(join_path(base, "/foo.srt"), Some(base.join("foo.srt")));
+        assert_eq!(join_path(base, "../foo.srt"), None);
+        assert_eq!(join_path(base, "a/../../foo.srt"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_path_symlinks() -> std::io::Result<()> {
+        let root = std::env::temp_dir().join(format!("jimaku-validate-path-{}", std::process::id()));
+        let base = root.join("entry");
+        std::fs::create_dir_all(&base)?;
+        std::fs::write(base.join("foo.srt"), "")?;
+        std::fs::write(root.join("secret.txt"), "")?;
+        std::os::unix::fs::symlink(root.join("secret.txt"), base.join("bar.srt"))?;
+
+        let result = (
+            validate_path(&base, "foo.srt"),
+            validate_path(&base, "bar.srt"),
+            validate_path(&base, "missing.srt"),
+        );
+        let expected = base.canonicalize()?.join("foo.srt");
+        std::fs::remove_dir_all(&root)?;
+        assert_eq!(result, (Some(expected), None, None));
+        Ok(())
+    }
+}
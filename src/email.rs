@@ -0,0 +1,69 @@
+//! Email related functionality.
+//!
+//! Email addresses are never stored in plaintext. Instead they're stored
+//! as an HMAC of the normalised address so they can only be looked up
+//! by someone who already knows the address.
+
+use anyhow::Context;
+use hmac::Mac;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message,
+    Tokio1Executor,
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{
+    config::SmtpConfig,
+    key::{to_hex, SecretKey},
+};
+
+/// The amount of time, in minutes, that a verification code is valid for.
+pub const VERIFICATION_CODE_EXPIRY_MINUTES: i64 = 15;
+
+/// Checks if the email address is roughly valid.
+///
+/// Actual validation is done by sending a verification code to it.
+pub fn is_valid_email(email: &SecretString) -> bool {
+    let email = email.expose_secret();
+    email.len() <= 254
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'))
+}
+
+/// Hashes the email address for storage.
+///
+/// The email address is normalised to lowercase beforehand.
+pub fn hash_email(key: &SecretKey, email: &SecretString) -> String {
+    let mut mac = key.hmac();
+    mac.update(email.expose_secret().trim().to_lowercase().as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+/// Generates a random 6-digit verification code.
+pub fn generate_verification_code() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 4];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(format!("{:06}", u32::from_le_bytes(bytes) % 1_000_000))
+}
+
+/// Sends a plaintext email to the given address.
+pub async fn send_email(config: &SmtpConfig, to: &SecretString, subject: &str, body: String) -> anyhow::Result<()> {
+    let from: Mailbox = config.from.parse().context("invalid from mailbox in SMTP config")?;
+    let to: Mailbox = to.expose_secret().parse().context("invalid email address")?;
+    let message = Message::builder().from(from).to(to).subject(subject).body(body)?;
+
+    let builder = if config.port == 465 {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+    };
+
+    let transport = builder
+        .port(config.port)
+        .credentials(Credentials::new(config.username.clone(), config.password.clone()))
+        .build();
+
+    transport.send(message).await?;
+    Ok(())
+}
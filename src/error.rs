@@ -9,7 +9,10 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::{logging::BadRequestReason, models::Account};
+use crate::{
+    logging::BadRequestReason,
+    models::{Account, Ban},
+};
 
 #[derive(Template)]
 #[template(path = "error.html")]
@@ -81,6 +84,10 @@ pub enum ApiErrorCode {
     Unauthorized = 7,
     /// The client is being rate limited.
     RateLimited = 8,
+    /// The file already exists.
+    FileAlreadyExists = 9,
+    /// The account is temporarily banned.
+    AccountBanned = 10,
 }
 
 impl ApiErrorCode {
@@ -94,6 +101,8 @@ impl ApiErrorCode {
             6 => Some(Self::NotFound),
             7 => Some(Self::Unauthorized),
             8 => Some(Self::RateLimited),
+            9 => Some(Self::FileAlreadyExists),
+            10 => Some(Self::AccountBanned),
             _ => None,
         }
     }
@@ -181,10 +190,25 @@ impl ApiError {
         }
     }
 
+    pub fn banned(ban: &Ban) -> Self {
+        let until = ban
+            .expires_at
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default();
+        let error = match ban.reason.as_deref() {
+            Some(reason) => format!("account is banned until {until}: {reason}"),
+            None => format!("account is banned until {until}"),
+        };
+        Self {
+            error: error.into(),
+            code: ApiErrorCode::AccountBanned,
+        }
+    }
+
     fn status_code(&self) -> StatusCode {
         if self.code == ApiErrorCode::ServerError {
             StatusCode::INTERNAL_SERVER_ERROR
-        } else if self.code == ApiErrorCode::NoPermissions {
+        } else if self.code == ApiErrorCode::NoPermissions || self.code == ApiErrorCode::AccountBanned {
             StatusCode::FORBIDDEN
         } else if self.code == ApiErrorCode::NotFound {
             StatusCode::NOT_FOUND
@@ -192,6 +216,8 @@ impl ApiError {
             StatusCode::UNAUTHORIZED
         } else if self.code == ApiErrorCode::RateLimited {
             StatusCode::TOO_MANY_REQUESTS
+        } else if self.code == ApiErrorCode::FileAlreadyExists {
+            StatusCode::CONFLICT
         } else {
             StatusCode::BAD_REQUEST
         }
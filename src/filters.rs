@@ -35,12 +35,40 @@ pub fn isoformat(dt: &OffsetDateTime) -> askama::Result<String> {
     ))
 }
 
-/// Returns a canonical URL to the given path
-pub fn canonical_url(url: impl Display) -> askama::Result<String> {
-    let path = url.to_string();
-    let mut url = crate::CONFIG.get().unwrap().canonical_url();
-    url.push_str(&path);
-    Ok(url)
+fn plural(value: i64, unit: &str) -> String {
+    if value == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{value} {unit}s ago")
+    }
+}
+
+/// Formats the time between `dt` and `now` as e.g. "3 minutes ago".
+///
+/// Times in the future are treated as "just now".
+fn relative_time_since(dt: OffsetDateTime, now: OffsetDateTime) -> String {
+    let seconds = (now - dt).whole_seconds();
+    let days = seconds / 86400;
+    if seconds < 60 {
+        "just now".to_owned()
+    } else if seconds < 3600 {
+        plural(seconds / 60, "minute")
+    } else if seconds < 86400 {
+        plural(seconds / 3600, "hour")
+    } else if days == 1 {
+        "yesterday".to_owned()
+    } else if days < 30 {
+        plural(days, "day")
+    } else if days < 365 {
+        plural(days / 30, "month")
+    } else {
+        plural(days / 365, "year")
+    }
+}
+
+/// Formats the given time relative to now, e.g. "2 hours ago".
+pub fn relative_time(dt: &OffsetDateTime) -> askama::Result<String> {
+    Ok(relative_time_since(*dt, OffsetDateTime::now_utc()))
 }
 
 pub fn maybe_tmdb_url(opt: &Option<crate::tmdb::Id>) -> askama::Result<String> {
@@ -55,8 +83,31 @@ pub fn maybe_anilist_url(opt: &Option<u32>) -> askama::Result<String> {
 }
 
 /// HTML input pattern for TMDB URLs
-pub const TMDB_PATTERN: &str =
-    r#"https:\/\/(?:www\.)?themoviedb\.org\/(?:tv|movie)\/(\d+)(?:-[a-zA-Z0-9\-]+)?(\/.*)?(?:\?.*)?"#;
+pub const TMDB_PATTERN: &str = concat!(
+    r#"(?:https?:\/\/)?(?:(?:www\.)?themoviedb\.org\/(?:tv|movie|tv-show)|tmdb\.to\/3\/(?:tv|movie))"#,
+    r#"\/(\d+)(?:-[a-zA-Z0-9\-]+)?(\/.*)?(?:\?.*)?|(?:tv|movie):\d+"#
+);
 
 /// HTML input pattern for AniList URLs
 pub const ANILIST_PATTERN: &str = r#"https:\/\/anilist\.co\/anime\/(\d+)(?:\/.*)?"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn test_relative_time() {
+        let now = OffsetDateTime::now_utc();
+        let ago = |d: Duration| relative_time_since(now - d, now);
+        assert_eq!(ago(Duration::seconds(-30)), "just now");
+        assert_eq!(ago(Duration::seconds(59)), "just now");
+        assert_eq!(ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(Duration::minutes(3)), "3 minutes ago");
+        assert_eq!(ago(Duration::hours(2)), "2 hours ago");
+        assert_eq!(ago(Duration::hours(30)), "yesterday");
+        assert_eq!(ago(Duration::days(3)), "3 days ago");
+        assert_eq!(ago(Duration::days(65)), "2 months ago");
+        assert_eq!(ago(Duration::days(400)), "1 year ago");
+    }
+}
@@ -1,9 +1,17 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use crate::{anilist::MediaTitle, models::EntryFlags, tmdb, AppState};
+use crate::{
+    anilist::MediaTitle,
+    audit::{AuditLogEntry, ScrapeEntry, ScrapeSource},
+    models::{count_files, EntryFlags},
+    tmdb, AppState,
+};
 
 /// A fixture that represents a directory entry that is pending addition to the database.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,38 +27,275 @@ pub struct Fixture {
     pub title: MediaTitle,
     #[serde(default, with = "crate::models::expand_flags")]
     pub flags: EntryFlags,
+    /// The scraper that created this fixture.
+    #[serde(default)]
+    pub source: ScrapeSource,
+    /// The number of new files the scraper downloaded for this fixture.
+    #[serde(default)]
+    pub files_downloaded: usize,
+}
+
+/// Parses a fixture file.
+///
+/// Fixture files are either a single JSON array or newline delimited JSON, where each
+/// line is a fixture object. The latter is what `scrape --append` writes.
+pub fn parse_fixtures(s: &str) -> serde_json::Result<Vec<Fixture>> {
+    if s.trim_start().starts_with('[') {
+        serde_json::from_str(s)
+    } else {
+        serde_json::Deserializer::from_str(s).into_iter().collect()
+    }
+}
+
+/// Writes the fixtures as newline delimited JSON.
+pub fn write_ndjson<W: std::io::Write>(mut writer: W, fixtures: &[Fixture]) -> std::io::Result<()> {
+    for fixture in fixtures {
+        serde_json::to_writer(&mut writer, fixture)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// A directory whose files should be moved into another directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirectoryMerge {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// Merges fixtures from different sources that refer to the same TMDB entry.
+///
+/// Kitsunekko fixtures always take precedence over jpsubbers ones, otherwise the
+/// first fixture wins. The paths of the fixtures that lost need to have their
+/// files moved to the path of the one that won.
+///
+/// Fixtures whose path already belongs to an entry in `committed` are never merged
+/// away, since that would leave the entry without its files.
+fn deduplicate_fixtures(fixtures: Vec<Fixture>, committed: &HashSet<PathBuf>) -> (Vec<Fixture>, Vec<DirectoryMerge>) {
+    let mut result: Vec<Fixture> = Vec::with_capacity(fixtures.len());
+    let mut by_tmdb_id: HashMap<tmdb::Id, usize> = HashMap::new();
+    let mut merges = Vec::new();
+    for fixture in fixtures {
+        let Some(tmdb_id) = fixture.tmdb_id else {
+            result.push(fixture);
+            continue;
+        };
+
+        let Some(&index) = by_tmdb_id.get(&tmdb_id) else {
+            by_tmdb_id.insert(tmdb_id, result.len());
+            result.push(fixture);
+            continue;
+        };
+
+        let prefer_new = match (
+            committed.contains(&result[index].path),
+            committed.contains(&fixture.path),
+        ) {
+            (true, true) => {
+                result.push(fixture);
+                continue;
+            }
+            (true, false) => false,
+            (false, true) => true,
+            (false, false) => {
+                result[index].source == ScrapeSource::Jpsubbers && fixture.source != ScrapeSource::Jpsubbers
+            }
+        };
+        let existing = &mut result[index];
+        let (mut winner, loser) = if prefer_new {
+            (fixture, existing.clone())
+        } else {
+            (existing.clone(), fixture)
+        };
+        winner.last_updated_at = winner.last_updated_at.max(loser.last_updated_at);
+        if winner.path != loser.path {
+            merges.push(DirectoryMerge {
+                from: loser.path,
+                to: winner.path.clone(),
+            });
+        }
+        *existing = winner;
+    }
+    (result, merges)
 }
 
+/// Moves every file from one directory to another, removing the source directory afterwards.
+///
+/// Files that already exist in the destination are left in place.
+async fn merge_directory(merge: &DirectoryMerge) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(&merge.to).await?;
+    let mut entries = tokio::fs::read_dir(&merge.from).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_file() {
+            continue;
+        }
+        let destination = merge.to.join(entry.file_name());
+        if !destination.exists() {
+            tokio::fs::rename(entry.path(), destination).await?;
+        }
+    }
+    // This only succeeds if it's empty, which is fine
+    let _ = tokio::fs::remove_dir(&merge.from).await;
+    Ok(())
+}
+
+/// Inserts the fixtures into the database, merging any duplicates.
+///
+/// Every committed fixture gets a [`ScrapeEntry`] audit log entry for the entry it ended up in.
 pub async fn commit_fixtures(state: &AppState, fixtures: Vec<Fixture>) -> anyhow::Result<()> {
-    state
+    let existing: HashSet<PathBuf> = state.directory_entries().await.iter().map(|e| e.path.clone()).collect();
+    let (fixtures, merges) = deduplicate_fixtures(fixtures, &existing);
+    for merge in &merges {
+        tracing::info!(from = %merge.from.display(), to = %merge.to.display(), "merging duplicate fixture directories");
+        if let Err(e) = merge_directory(merge).await {
+            tracing::warn!(error = %e, from = %merge.from.display(), "could not merge fixture directories");
+        }
+    }
+
+    // The directories are recounted since merges can move files between them
+    let mut file_counts = Vec::with_capacity(fixtures.len());
+    for fixture in &fixtures {
+        let count = count_files(&fixture.path).await.unwrap_or(fixture.files_downloaded);
+        file_counts.push(count as u32);
+    }
+
+    let logs = state
         .database()
-        .call(move |conn| -> rusqlite::Result<()> {
+        .call(move |conn| -> rusqlite::Result<Vec<AuditLogEntry>> {
             let sql = r#"
-                INSERT INTO directory_entry(path, last_updated_at, flags, anilist_id, tmdb_id, english_name, japanese_name, name)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                INSERT INTO directory_entry(
+                    path, last_updated_at, flags, anilist_id, tmdb_id, english_name, japanese_name, name, file_count
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
                 ON CONFLICT DO UPDATE
-                SET last_updated_at = MAX(last_updated_at, EXCLUDED.last_updated_at)
+                SET last_updated_at = MAX(last_updated_at, EXCLUDED.last_updated_at),
+                    file_count = EXCLUDED.file_count
+                RETURNING id
             "#;
+            let mut logs = Vec::with_capacity(fixtures.len());
             let tx = conn.transaction()?;
             {
                 let mut stmt = tx.prepare(sql)?;
-                for fixture in fixtures {
-                    stmt.execute((
-                        fixture.path.to_string_lossy(),
-                        fixture.last_updated_at,
-                        fixture.flags,
-                        fixture.anilist_id,
-                        fixture.tmdb_id,
-                        fixture.title.english,
-                        fixture.title.native,
-                        fixture.title.romaji,
-                    ))?;
+                for (fixture, file_count) in fixtures.into_iter().zip(file_counts) {
+                    let data = ScrapeEntry {
+                        source: fixture.source,
+                        original_name: fixture.original_name,
+                        anilist_id: fixture.anilist_id,
+                        tmdb_id: fixture.tmdb_id,
+                        files_downloaded: fixture.files_downloaded,
+                    };
+                    let entry_id: i64 = stmt.query_row(
+                        (
+                            fixture.path.to_string_lossy(),
+                            fixture.last_updated_at,
+                            fixture.flags,
+                            fixture.anilist_id,
+                            fixture.tmdb_id,
+                            fixture.title.english,
+                            fixture.title.native,
+                            fixture.title.romaji,
+                            file_count,
+                        ),
+                        |row| row.get(0),
+                    )?;
+                    logs.push(AuditLogEntry::new(data).with_entry(entry_id));
                 }
             }
             tx.commit()?;
-            Ok(())
+            Ok(logs)
         })
         .await?;
-    state.cached_directories().invalidate().await;
+    state.cached_directories().invalidate();
+    state.audit_many(logs).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str, tmdb_id: Option<tmdb::Id>, source: ScrapeSource) -> Fixture {
+        Fixture {
+            path: PathBuf::from(path),
+            original_name: path.to_owned(),
+            last_updated_at: OffsetDateTime::UNIX_EPOCH,
+            anilist_id: None,
+            tmdb_id,
+            title: MediaTitle::new(path.to_owned()),
+            flags: EntryFlags::default(),
+            source,
+            files_downloaded: 0,
+        }
+    }
+
+    #[test]
+    fn test_deduplicate_fixtures() {
+        let id = Some(tmdb::Id::Tv { id: 1 });
+        let fixtures = vec![
+            fixture("jpsubbers_a", id, ScrapeSource::Jpsubbers),
+            fixture("b", None, ScrapeSource::Jpsubbers),
+            fixture("a", id, ScrapeSource::Kitsunekko),
+            fixture("c", None, ScrapeSource::Kitsunekko),
+        ];
+        let (result, merges) = deduplicate_fixtures(fixtures, &HashSet::new());
+        let paths: Vec<_> = result.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["a", "b", "c"]);
+        assert_eq!(
+            merges,
+            [DirectoryMerge {
+                from: PathBuf::from("jpsubbers_a"),
+                to: PathBuf::from("a"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_deduplicate_fixtures_keeps_existing_entries() {
+        let id = Some(tmdb::Id::Tv { id: 1 });
+        let fixtures = || {
+            vec![
+                fixture("jpsubbers_a", id, ScrapeSource::Jpsubbers),
+                fixture("a", id, ScrapeSource::Kitsunekko),
+            ]
+        };
+
+        // An existing entry wins even if its source would normally lose
+        let existing = HashSet::from([PathBuf::from("jpsubbers_a")]);
+        let (result, merges) = deduplicate_fixtures(fixtures(), &existing);
+        let paths: Vec<_> = result.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["jpsubbers_a"]);
+        assert_eq!(
+            merges,
+            [DirectoryMerge {
+                from: PathBuf::from("a"),
+                to: PathBuf::from("jpsubbers_a"),
+            }]
+        );
+
+        // Two existing entries are left alone
+        let existing = HashSet::from([PathBuf::from("jpsubbers_a"), PathBuf::from("a")]);
+        let (result, merges) = deduplicate_fixtures(fixtures(), &existing);
+        let paths: Vec<_> = result.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["jpsubbers_a", "a"]);
+        assert!(merges.is_empty());
+    }
+
+    #[test]
+    fn test_parse_fixtures() {
+        let fixtures = vec![
+            fixture("a", None, ScrapeSource::Kitsunekko),
+            fixture("b", None, ScrapeSource::Jpsubbers),
+        ];
+        let array = serde_json::to_string(&fixtures).unwrap();
+        let paths = |f: Vec<Fixture>| f.into_iter().map(|f| f.original_name).collect::<Vec<_>>();
+        assert_eq!(paths(parse_fixtures(&array).unwrap()), ["a", "b"]);
+
+        let mut ndjson = Vec::new();
+        write_ndjson(&mut ndjson, &fixtures[..1]).unwrap();
+        write_ndjson(&mut ndjson, &fixtures[1..]).unwrap();
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        assert_eq!(ndjson.lines().count(), 2);
+        assert_eq!(paths(parse_fixtures(&ndjson).unwrap()), ["a", "b"]);
+        assert!(parse_fixtures("").unwrap().is_empty());
+    }
+}
@@ -20,6 +20,12 @@ use crate::key::SecretKey;
 const REMOVE_FLASH_MESSAGES: HeaderValue =
     HeaderValue::from_static("flash_messages=; Path=/; HttpOnly; SameSite=Lax; Expires=Thu, 01 Jan 1970 00:00:00 GMT");
 
+/// The maximum serialized size, in bytes, of the detail lines kept in a flash message.
+///
+/// Flash messages are stored in a cookie, which browsers limit to 4096 bytes. The cookie
+/// value is base64 encoded so this ends up taking around 3KB of it.
+const MAX_FLASH_DETAILS_SIZE: usize = 2048;
+
 /// A container responsible for handling flash messages
 #[derive(Debug, Clone, Default)]
 pub struct Flasher {
@@ -30,6 +36,12 @@ pub struct Flasher {
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Flashes(Vec<FlashMessage>);
 
+impl From<Vec<FlashMessage>> for Flashes {
+    fn from(value: Vec<FlashMessage>) -> Self {
+        Self(value)
+    }
+}
+
 impl std::ops::Deref for Flashes {
     type Target = [FlashMessage];
 
@@ -47,6 +59,11 @@ pub struct FlashMessage {
     /// The level of the message
     #[serde(rename = "l")]
     pub level: FlashLevel,
+    /// Additional lines of detail, e.g. the files that failed to upload.
+    ///
+    /// These are shown collapsed under the message.
+    #[serde(rename = "d", default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<String>>,
 }
 
 impl FlashMessage {
@@ -55,6 +72,7 @@ impl FlashMessage {
         Self {
             content: content.into(),
             level,
+            details: None,
         }
     }
 
@@ -74,9 +92,30 @@ impl FlashMessage {
         Self::new(content, FlashLevel::Success)
     }
 
-    /// Creates a new flash message with the given content with Warning level.
-    pub fn warning(content: impl Into<String>) -> Self {
-        Self::new(content, FlashLevel::Warning)
+    /// Creates a new flash message with the given content and optional details with Warning level.
+    ///
+    /// Only the detail lines that fit in [`MAX_FLASH_DETAILS_SIZE`] are kept, the rest are
+    /// summarised in a final line.
+    pub fn warning(content: impl Into<String>, details: Option<Vec<String>>) -> Self {
+        let mut message = Self::new(content, FlashLevel::Warning);
+        message.details = details.filter(|d| !d.is_empty()).map(|mut details| {
+            let mut size = 0;
+            let kept = details
+                .iter()
+                .take_while(|line| {
+                    // The quotes, the separating comma, and any escapes
+                    size += serde_json::to_string(line).map_or(usize::MAX, |s| s.len()) + 1;
+                    size <= MAX_FLASH_DETAILS_SIZE
+                })
+                .count();
+            if kept < details.len() {
+                let remaining = details.len() - kept;
+                details.truncate(kept);
+                details.push(format!("...and {remaining} more"));
+            }
+            details
+        });
+        message
     }
 
     /// Creates a new flash message with the given content with Error level.
@@ -94,6 +133,7 @@ impl<'a> From<&'a str> for FlashMessage {
         Self {
             content: value.to_owned(),
             level: FlashLevel::Error,
+            details: None,
         }
     }
 }
@@ -103,6 +143,7 @@ impl From<String> for FlashMessage {
         Self {
             content,
             level: FlashLevel::Error,
+            details: None,
         }
     }
 }
@@ -114,11 +155,22 @@ struct FlashMessageHtml<'a> {
 impl<'a> std::fmt::Display for FlashMessageHtml<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, r#"<div class="alert {}" role="alert">"#, self.message.level)?;
+        if self.message.details.is_some() {
+            writeln!(f, r#"<div class="alert-body">"#)?;
+        }
         writeln!(
             f,
             r#"<p>{}</p>"#,
             askama::MarkupDisplay::new_unsafe(&self.message.content, askama::Html)
         )?;
+        if let Some(details) = &self.message.details {
+            writeln!(f, r"<details><summary>Details</summary><ul>")?;
+            for line in details {
+                writeln!(f, r"<li>{}</li>", askama::MarkupDisplay::new_unsafe(line, askama::Html))?;
+            }
+            writeln!(f, r"</ul></details>")?;
+            writeln!(f, r"</div>")?;
+        }
         writeln!(
             f,
             r#"<button type="button" aria-hidden=true class="close" onclick="closeAlert(event)"></button>"#
@@ -290,3 +342,41 @@ pub async fn process_flash_messages(mut request: Request, next: Next) -> Respons
     }
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warning_details() {
+        let message = FlashMessage::warning("Partial upload", Some(Vec::new()));
+        assert_eq!(message.details, None);
+
+        let lines = (0..30).map(|i| format!("file{i}.srt")).collect();
+        let message = FlashMessage::warning("Partial upload", Some(lines));
+        assert_eq!(message.details.as_deref().unwrap().len(), 30);
+
+        let lines = (0..300).map(|i| format!("file{i:03}.srt")).collect();
+        let message = FlashMessage::warning("Partial upload", Some(lines));
+        let details = message.details.as_deref().unwrap();
+        // Each line takes 14 bytes serialized, so 146 of them fit
+        assert_eq!(details.len(), 147);
+        assert_eq!(details.last().unwrap(), "...and 154 more");
+
+        let html = FlashMessage::warning("Partial upload", Some(vec!["<b>.srt".into()]))
+            .html()
+            .to_string();
+        assert!(html.contains("<li>&lt;b&gt;.srt</li>"));
+    }
+
+    #[test]
+    fn test_warning_cookie_size() {
+        let lines = (0..100)
+            .map(|i| format!("【字幕】とても長いアニメのタイトル　第{i}話.srt"))
+            .collect();
+        let flasher = Flasher::new();
+        flasher.add(FlashMessage::warning("Some files could not be uploaded", Some(lines)));
+        let cookie = flasher.to_cookie(&SecretKey::random().unwrap()).unwrap();
+        assert!(cookie.to_string().len() < 4096);
+    }
+}
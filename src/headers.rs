@@ -19,7 +19,7 @@ impl FromRequestParts<AppState> for Referrer {
     type Rejection = (StatusCode, &'static str);
 
     async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
-        if let Some(referrer) = get_safe_referrer(state.config(), parts.headers.get(REFERER)) {
+        if let Some(referrer) = get_safe_referrer(&state.config(), parts.headers.get(REFERER)) {
             Ok(Referrer(referrer.to_string()))
         } else {
             Err((StatusCode::BAD_REQUEST, "`Referer` header is missing or invalid"))
@@ -1,5 +1,7 @@
 use std::{borrow::Cow, ops::RangeInclusive};
 
+use unicode_normalization::{is_nfkc, UnicodeNormalization};
+
 use crate::borrowed::MaybeBorrowedString;
 
 const fn is_diacritic(c: char) -> bool {
@@ -38,6 +40,148 @@ pub fn normalize_diacritics(s: &str) -> Cow<'_, str> {
     }
 }
 
+/// Normalizes the string using Unicode NFKC after normalizing diacritics.
+///
+/// This turns compatibility variants such as full-width Latin characters and digits
+/// into their regular counterparts, e.g. `ＪＫと６法全書` becomes `JKと6法全書`.
+/// The ideographic space (U+3000) is also mapped to a regular space.
+pub fn normalize_unicode(s: &str) -> Cow<'_, str> {
+    let s = normalize_diacritics(s);
+    if s.is_ascii() || (is_nfkc(&s) && !s.contains('\u{3000}')) {
+        return s;
+    }
+
+    s.nfkc()
+        .map(|c| if c == '\u{3000}' { ' ' } else { c })
+        .collect::<String>()
+        .into()
+}
+
+/// Compound words that are conventionally written with kanji numerals, keyed by their
+/// spelling with Arabic digits.
+///
+/// This is not exhaustive. It's a list of common words that show up in show names.
+const KANJI_NUMBER_COMPOUNDS: [(&str, &str); 50] = [
+    ("6法", "六法"),
+    ("3国志", "三国志"),
+    ("3銃士", "三銃士"),
+    ("8犬伝", "八犬伝"),
+    ("10戒", "十戒"),
+    ("4天王", "四天王"),
+    ("5輪", "五輪"),
+    ("1期1会", "一期一会"),
+    ("1石2鳥", "一石二鳥"),
+    ("1騎当千", "一騎当千"),
+    ("1刀両断", "一刀両断"),
+    ("1心同体", "一心同体"),
+    ("1生懸命", "一生懸命"),
+    ("1目惚れ", "一目惚れ"),
+    ("1匹狼", "一匹狼"),
+    ("1寸法師", "一寸法師"),
+    ("1番星", "一番星"),
+    ("1休", "一休"),
+    ("2刀流", "二刀流"),
+    ("2枚目", "二枚目"),
+    ("2重人格", "二重人格"),
+    ("2人3脚", "二人三脚"),
+    ("3日月", "三日月"),
+    ("3角関係", "三角関係"),
+    ("3毛猫", "三毛猫"),
+    ("3枚目", "三枚目"),
+    ("3途の川", "三途の川"),
+    ("4季", "四季"),
+    ("4畳半", "四畳半"),
+    ("4面楚歌", "四面楚歌"),
+    ("5月雨", "五月雨"),
+    ("5里霧中", "五里霧中"),
+    ("6本木", "六本木"),
+    ("7夕", "七夕"),
+    ("7福神", "七福神"),
+    ("7不思議", "七不思議"),
+    ("7転8倒", "七転八倒"),
+    ("8百屋", "八百屋"),
+    ("8王子", "八王子"),
+    ("8重桜", "八重桜"),
+    ("8方美人", "八方美人"),
+    ("9尾", "九尾"),
+    ("9州", "九州"),
+    ("10字架", "十字架"),
+    ("10手", "十手"),
+    ("16夜", "十六夜"),
+    ("100人1首", "百人一首"),
+    ("100鬼夜行", "百鬼夜行"),
+    ("100貨店", "百貨店"),
+    ("1000里眼", "千里眼"),
+];
+
+/// Returns the value of an ASCII or full-width digit.
+fn digit_value(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        '０'..='９' => Some(c as u32 - '０' as u32),
+        _ => None,
+    }
+}
+
+/// Returns the number of characters matched if the haystack starts with the compound.
+///
+/// Digits in the compound match both their ASCII and full-width variants.
+fn match_number_compound(haystack: &[char], compound: &str) -> Option<usize> {
+    let mut len = 0;
+    for expected in compound.chars() {
+        let c = *haystack.get(len)?;
+        let matches = match expected.to_digit(10) {
+            Some(digit) => digit_value(c) == Some(digit),
+            None => c == expected,
+        };
+        if !matches {
+            return None;
+        }
+        len += 1;
+    }
+    Some(len)
+}
+
+/// Replaces common compound words written with Arabic digits with their kanji spelling,
+/// e.g. `JKと６法全書` becomes `JKと六法全書`.
+///
+/// This is not a general purpose number converter, see [`KANJI_NUMBER_COMPOUNDS`]
+/// for the words that are replaced. Digits that are part of a larger number are
+/// never replaced, so `16法` is left alone.
+pub fn normalize_japanese_numbers(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(|c| digit_value(c).is_some()) {
+        return Cow::Borrowed(s);
+    }
+
+    let chars: Vec<char> = s.chars().collect();
+    let mut output = String::with_capacity(s.len());
+    let mut changed = false;
+    let mut index = 0;
+    while index < chars.len() {
+        let preceded_by_digit = index > 0 && digit_value(chars[index - 1]).is_some();
+        if !preceded_by_digit && digit_value(chars[index]).is_some() {
+            let longest = KANJI_NUMBER_COMPOUNDS
+                .iter()
+                .filter_map(|(digits, kanji)| Some((match_number_compound(&chars[index..], digits)?, kanji)))
+                .max_by_key(|(len, _)| *len);
+            if let Some((len, kanji)) = longest {
+                output.push_str(kanji);
+                index += len;
+                changed = true;
+                continue;
+            }
+        }
+        output.push(chars[index]);
+        index += 1;
+    }
+
+    if changed {
+        Cow::Owned(output)
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
 /// Deserializes the string by replacing diacritics with the ASCII counterpart
 pub fn normalized_ascii_representation<'de, D>(de: D) -> Result<String, D::Error>
 where
@@ -68,12 +212,237 @@ pub fn is_japanese_char(ch: char) -> bool {
     CJK_MAPPING.iter().any(|c| c.contains(&ch))
 }
 
+/// Returns `true` if more than half of the non-whitespace characters are Japanese.
+///
+/// See [`is_japanese_char`] for what counts as a Japanese character.
+pub fn is_mostly_japanese(s: &str) -> bool {
+    let total = s.chars().filter(|c| !c.is_whitespace()).count();
+    let japanese = s.chars().filter(|c| is_japanese_char(*c)).count();
+    japanese * 2 > total
+}
+
+#[inline]
+const fn is_convertible_hiragana(ch: char) -> bool {
+    matches!(ch, '\u{3041}'..='\u{3096}')
+}
+
+#[inline]
+const fn is_convertible_katakana(ch: char) -> bool {
+    matches!(ch, '\u{30a1}'..='\u{30f6}')
+}
+
+/// The distance between a hiragana character and its katakana counterpart.
+const KANA_OFFSET: u32 = 0x60;
+
+/// Converts all hiragana characters in the string to katakana.
+///
+/// If there's nothing to convert then no allocation takes place.
+pub fn hiragana_to_katakana(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(is_convertible_hiragana) {
+        return Cow::Borrowed(s);
+    }
+
+    s.chars()
+        .map(|c| {
+            if is_convertible_hiragana(c) {
+                char::from_u32(c as u32 + KANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .into()
+}
+
+/// Converts all katakana characters in the string to hiragana.
+///
+/// If there's nothing to convert then no allocation takes place.
+pub fn katakana_to_hiragana(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(is_convertible_katakana) {
+        return Cow::Borrowed(s);
+    }
+
+    s.chars()
+        .map(|c| {
+            if is_convertible_katakana(c) {
+                char::from_u32(c as u32 - KANA_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .into()
+}
+
+/// Returns the Hepburn romanization of a single hiragana character.
+///
+/// Small kana that modify the previous character (e.g. ゃ or っ) are not handled here.
+const fn hiragana_to_romaji_char(ch: char) -> Option<&'static str> {
+    let romaji = match ch {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' => "o",
+        'か' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' => "ji",
+        'ず' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'ぢ' => "ji",
+        'づ' => "zu",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' => "ya",
+        'ゆ' => "yu",
+        'よ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' => "wa",
+        'ゐ' => "wi",
+        'ゑ' => "we",
+        'を' => "wo",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+/// Converts hiragana and katakana text to romaji, in a lossy manner.
+///
+/// This is the inverse of [`romaji_to_hiragana`] and uses Hepburn romanization.
+/// Characters that aren't kana are kept as-is.
+pub fn kana_to_romaji(s: &str) -> String {
+    let s = katakana_to_hiragana(s);
+    let mut output = String::with_capacity(s.len());
+    let mut sokuon = false;
+    let mut after_n = false;
+    for ch in s.chars() {
+        match ch {
+            'っ' => {
+                sokuon = true;
+                continue;
+            }
+            'ゃ' | 'ゅ' | 'ょ' => {
+                let vowel = match ch {
+                    'ゃ' => 'a',
+                    'ゅ' => 'u',
+                    _ => 'o',
+                };
+                // きゃ => kya but しゃ => sha
+                if output.ends_with('i') {
+                    output.pop();
+                    if !(output.ends_with("sh") || output.ends_with("ch") || output.ends_with('j')) {
+                        output.push('y');
+                    }
+                } else {
+                    output.push('y');
+                }
+                output.push(vowel);
+            }
+            'ー' => {
+                if let Some(last) = output.chars().next_back().filter(is_vowel) {
+                    output.push(last);
+                }
+            }
+            _ => match hiragana_to_romaji_char(ch) {
+                Some(romaji) => {
+                    // ん needs to be separated from whatever would make it read differently:
+                    // んあ => n'a rather than な, んや => n'ya rather than にゃ
+                    // and んん => n'n rather than nn, which is read as a single ん
+                    if after_n && (ch == 'ん' || romaji.starts_with(|c| is_vowel(&c) || c == 'y')) {
+                        output.push('\'');
+                    }
+                    if sokuon {
+                        if romaji.starts_with("ch") {
+                            output.push('t');
+                        } else if let Some(consonant) = romaji.chars().next().filter(|c| !is_vowel(c)) {
+                            output.push(consonant);
+                        }
+                    }
+                    output.push_str(romaji);
+                }
+                None => output.push(ch),
+            },
+        }
+        sokuon = false;
+        after_n = ch == 'ん';
+    }
+    output
+}
+
+/// Returns a key suitable for sorting titles in a Japanese aware manner.
+///
+/// The key is romanized, lowercase, and has leading English articles stripped.
+pub fn japanese_sort_key(s: &str) -> String {
+    let s = normalize_diacritics(s);
+    let mut key = kana_to_romaji(&s).to_lowercase();
+    for article in ["the ", "an ", "a "] {
+        if key.starts_with(article) {
+            key.drain(..article.len());
+            break;
+        }
+    }
+    key
+}
+
 /// Converts the romaji text to hiragana, in a lossy manner.
 ///
 /// If the character doesn't map to a hiragana character then it's kept mostly as-is.
 ///
-/// Note that this is loose-ly based off of Hepburn romanization. This doesn't
-/// support most things from Nihon/Kunrei shiki.
+/// Note that this is loose-ly based off of Hepburn romanization. The common Nihon-shiki
+/// and Wapuro spellings (e.g. `si`, `ti`, `tu`) are also accepted, but Hepburn spellings
+/// take priority.
 pub fn romaji_to_hiragana(s: &str) -> String {
     let mut output = String::with_capacity(s.len());
     let mut parser = s.chars().map(|c| c.to_ascii_lowercase()).peekable();
@@ -140,6 +509,11 @@ pub fn romaji_to_hiragana(s: &str) -> String {
                             output.push('さ');
                             parser.next();
                         }
+                        // Nihon-shiki / Wapuro
+                        'i' => {
+                            output.push('し');
+                            parser.next();
+                        }
                         'u' => {
                             output.push('す');
                             parser.next();
@@ -203,6 +577,15 @@ pub fn romaji_to_hiragana(s: &str) -> String {
                             output.push('た');
                             parser.next();
                         }
+                        // Nihon-shiki / Wapuro
+                        'i' => {
+                            output.push('ち');
+                            parser.next();
+                        }
+                        'u' => {
+                            output.push('つ');
+                            parser.next();
+                        }
                         'e' => {
                             output.push('て');
                             parser.next();
@@ -220,7 +603,8 @@ pub fn romaji_to_hiragana(s: &str) -> String {
                                 output.push('s');
                             }
                         }
-                        't' => {
+                        // tchi => っち
+                        't' | 'c' => {
                             output.push('っ');
                         }
                         _ => {
@@ -278,6 +662,22 @@ pub fn romaji_to_hiragana(s: &str) -> String {
                         // kin'emon => ki n' e mo n
                         // Essentially before a consonant it becomes an N
                         // but before a vowel it requires a '
+                        //
+                        // A double n is ambiguous, it's either a ん followed by a syllable
+                        // starting with n or the wapuro way of writing a single ん:
+                        // nonnonbiyori => no n no n bi yo ri
+                        // konnyaku => ko n nya ku
+                        // konnbanwa => ko nn ba n wa
+                        // shinn => shi nn
+                        // So the second n is only kept if a vowel or y follows it
+                        'n' => {
+                            output.push('ん');
+                            let mut lookahead = parser.clone();
+                            lookahead.next();
+                            if !lookahead.peek().is_some_and(|c| is_vowel(c) || *c == 'y') {
+                                parser.next();
+                            }
+                        }
                         '\'' => {
                             output.push('ん');
                             parser.next();
@@ -606,6 +1006,10 @@ pub fn romaji_to_hiragana(s: &str) -> String {
                             output.push('だ');
                             parser.next();
                         }
+                        'i' => {
+                            output.push('ぢ');
+                            parser.next();
+                        }
                         'o' => {
                             output.push('ど');
                             parser.next();
@@ -762,5 +1166,181 @@ mod tests {
         assert_eq!(romaji_to_hiragana("kin'emon"), "きんえもん");
         assert_eq!(romaji_to_hiragana("sekkyokuteki"), "せっきょくてき");
         assert_eq!(romaji_to_hiragana("jiyuu"), "じゆう");
+        assert_eq!(romaji_to_hiragana("kanai"), "かない");
+        assert_eq!(romaji_to_hiragana("konnbanwa"), "こんばんわ");
+        assert_eq!(romaji_to_hiragana("nonnonbiyori"), "のんのんびより");
+        assert_eq!(romaji_to_hiragana("onnanoko"), "おんなのこ");
+        assert_eq!(romaji_to_hiragana("kinnikuman"), "きんにくまん");
+        assert_eq!(romaji_to_hiragana("kannagi"), "かんなぎ");
+        assert_eq!(romaji_to_hiragana("konnyaku"), "こんにゃく");
+        assert_eq!(romaji_to_hiragana("shinsekai"), "しんせかい");
+        assert_eq!(romaji_to_hiragana("shinnsekai"), "しんせかい");
+        assert_eq!(romaji_to_hiragana("gintama"), "ぎんたま");
+        assert_eq!(romaji_to_hiragana("ranma"), "らんま");
+        assert_eq!(romaji_to_hiragana("tonikaku"), "とにかく");
+        assert_eq!(romaji_to_hiragana("monogatari"), "ものがたり");
+    }
+
+    #[test]
+    fn test_kana_to_romaji() {
+        assert_eq!(kana_to_romaji("かっこいい"), "kakkoii");
+        assert_eq!(kana_to_romaji("しゃしん"), "shashin");
+        assert_eq!(kana_to_romaji("きょう"), "kyou");
+        assert_eq!(kana_to_romaji("こっち"), "kotchi");
+        assert_eq!(kana_to_romaji("フリーレン"), "furiiren");
+        assert_eq!(kana_to_romaji("じゅうじゅつ"), "juujutsu");
+        assert_eq!(kana_to_romaji("葬送のフリーレン"), "葬送nofuriiren");
+        assert_eq!(kana_to_romaji("かんい"), "kan'i");
+        assert_eq!(kana_to_romaji("こんや"), "kon'ya");
+        assert_eq!(kana_to_romaji("んん"), "n'n");
+        assert_eq!(kana_to_romaji("こんな"), "konna");
+    }
+
+    #[test]
+    fn test_japanese_sort_key() {
+        assert_eq!(japanese_sort_key("The Apothecary Diaries"), "apothecary diaries");
+        assert_eq!(japanese_sort_key("A Sign of Affection"), "sign of affection");
+        assert_eq!(japanese_sort_key("Tendō"), "tendou");
+        assert_eq!(japanese_sort_key("ぼっち・ざ・ろっく！"), "botchi・za・rokku！");
+        assert_eq!(japanese_sort_key("Another"), "another");
+    }
+
+    #[test]
+    fn test_normalize_unicode() {
+        assert_eq!(normalize_unicode("ＪＫと６法全書"), "JKと6法全書");
+        assert_eq!(
+            normalize_unicode("ガールズバンドクライ　２期"),
+            "ガールズバンドクライ 2期"
+        );
+        assert_eq!(normalize_unicode("ｶﾀｶﾅ"), "カタカナ");
+        assert_eq!(normalize_unicode("Tendō"), "Tendou");
+        assert!(matches!(normalize_unicode("JKと6法全書"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_japanese_numbers() {
+        assert_eq!(normalize_japanese_numbers("JKと６法全書"), "JKと六法全書");
+        assert_eq!(normalize_japanese_numbers("JKと6法全書"), "JKと六法全書");
+        assert_eq!(normalize_japanese_numbers("７転８倒の１６夜"), "七転八倒の十六夜");
+        assert_eq!(normalize_japanese_numbers("２人３脚"), "二人三脚");
+        assert_eq!(normalize_japanese_numbers("16法"), "16法");
+        assert_eq!(
+            normalize_japanese_numbers("ガールズバンドクライ ２期"),
+            "ガールズバンドクライ ２期"
+        );
+        assert!(matches!(normalize_japanese_numbers("９ボーダー"), Cow::Borrowed(_)));
+        assert!(matches!(normalize_japanese_numbers("六法全書"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_kana_conversion() {
+        assert_eq!(hiragana_to_katakana("ひらがな"), "ヒラガナ");
+        assert_eq!(hiragana_to_katakana("ぼっち・ざ・ろっく！"), "ボッチ・ザ・ロック！");
+        assert_eq!(katakana_to_hiragana("カタカナ"), "かたかな");
+        assert_eq!(katakana_to_hiragana("ヴァイオレット"), "ゔぁいおれっと");
+        assert_eq!(katakana_to_hiragana("ラーメン"), "らーめん");
+        assert!(matches!(hiragana_to_katakana("カタカナ"), Cow::Borrowed(_)));
+        assert!(matches!(katakana_to_hiragana("漢字 and ascii"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_is_mostly_japanese() {
+        assert!(is_mostly_japanese("光る君へ"));
+        assert!(is_mostly_japanese("ガールズバンドクライ 2期"));
+        assert!(is_mostly_japanese("JKと6法全書"));
+        assert!(!is_mostly_japanese("Love Live! スーパースター!!"));
+        assert!(!is_mostly_japanese("Sousou no Frieren"));
+        assert!(!is_mostly_japanese(""));
+    }
+}
+
+#[cfg(test)]
+mod wapuro_tests {
+    use super::*;
+
+    #[test]
+    fn test_wapuro_spellings() {
+        assert_eq!(romaji_to_hiragana("sinbun"), "しんぶん");
+        assert_eq!(romaji_to_hiragana("tikara"), "ちから");
+        assert_eq!(romaji_to_hiragana("tukue"), "つくえ");
+        assert_eq!(romaji_to_hiragana("huku"), "ふく");
+        assert_eq!(romaji_to_hiragana("hanadi"), "はなぢ");
+        assert_eq!(romaji_to_hiragana("tuduku"), "つづく");
+        assert_eq!(romaji_to_hiragana("kotchi"), "こっち");
+        assert_eq!(romaji_to_hiragana("mittu"), "みっつ");
+    }
+
+    #[test]
+    fn test_hepburn_takes_priority() {
+        assert_eq!(romaji_to_hiragana("shinbun"), "しんぶん");
+        assert_eq!(romaji_to_hiragana("chikara"), "ちから");
+        assert_eq!(romaji_to_hiragana("tsukue"), "つくえ");
+        assert_eq!(romaji_to_hiragana("fuku"), "ふく");
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Romaji syllables that map to a single hiragana sequence.
+    ///
+    /// Ambiguous spellings such as `di` or `du` are left out since they don't round trip.
+    const SYLLABLES: &[&str] = &[
+        "a", "i", "u", "e", "o", "ka", "ki", "ku", "ke", "ko", "kya", "kyu", "kyo", "sa", "shi", "si", "su", "se",
+        "so", "sha", "shu", "sho", "ta", "chi", "ti", "tsu", "tu", "te", "to", "cha", "chu", "cho", "na", "ni", "nu",
+        "ne", "no", "nya", "nyu", "nyo", "ha", "hi", "fu", "hu", "he", "ho", "hya", "hyu", "hyo", "ma", "mi", "mu",
+        "me", "mo", "mya", "myu", "myo", "ya", "yu", "yo", "ra", "ri", "ru", "re", "ro", "rya", "ryu", "ryo", "wa",
+        "wo", "ga", "gi", "gu", "ge", "go", "gya", "gyu", "gyo", "ja", "ji", "ju", "jo", "za", "zu", "ze", "zo", "da",
+        "de", "do", "ba", "bi", "bu", "be", "bo", "bya", "byu", "byo", "pa", "pi", "pu", "pe", "po", "pya", "pyu",
+        "pyo", "n'",
+    ];
+
+    fn romaji() -> impl Strategy<Value = String> {
+        proptest::collection::vec(proptest::sample::select(SYLLABLES), 0..32).prop_map(|v| v.concat())
+    }
+
+    fn is_hiragana(ch: char) -> bool {
+        ('\u{3041}'..='\u{309f}').contains(&ch)
+    }
+
+    /// Inputs that the round trip property failed on before.
+    #[test]
+    fn round_trip_regressions() {
+        for hiragana in ["んん", "んんか", "んな", "かんい", "こんや", "んにゃ"] {
+            assert_eq!(romaji_to_hiragana(&kana_to_romaji(hiragana)), hiragana);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn output_is_never_longer(s in romaji()) {
+            prop_assert!(romaji_to_hiragana(&s).chars().count() <= s.chars().count());
+        }
+
+        #[test]
+        fn output_is_hiragana_or_passthrough(s in romaji()) {
+            let output = romaji_to_hiragana(&s);
+            prop_assert!(output.chars().all(|c| is_hiragana(c) || s.contains(c)), "{s} => {output}");
+        }
+
+        #[test]
+        fn conversion_is_pure(s in romaji()) {
+            prop_assert_eq!(romaji_to_hiragana(&s), romaji_to_hiragana(&s));
+        }
+
+        #[test]
+        fn round_trips_through_romaji(s in romaji()) {
+            let hiragana = romaji_to_hiragana(&s);
+            prop_assert_eq!(romaji_to_hiragana(&kana_to_romaji(&hiragana)), hiragana);
+        }
+
+        #[test]
+        fn katakana_romanizes_like_hiragana(s in romaji()) {
+            let hiragana = romaji_to_hiragana(&s);
+            let katakana = hiragana_to_katakana(&hiragana);
+            prop_assert_eq!(kana_to_romaji(&katakana), kana_to_romaji(&hiragana));
+        }
     }
 }
@@ -19,12 +19,21 @@ Basically the same idea.
 
 There are a few caveats:
 
-1) If the name starts with @ then it's an inner non-airing group so it should be skipped.
+1) If the name starts with @ then it's an inner non-airing group. These contain more directories
+   so they're recursed into, see `get_directories`.
 2) If the name is `..` then it's bringing you up a directory, so should be ignored.
 3) There is no last_modified information so each scrape loop will force a full crawl either way.
+   To cut down on work, the file names seen in each directory are stored after every scrape and
+   directories whose file listing hasn't changed since then are skipped after fetching the listing.
 */
 
-use std::{collections::HashMap, io::Write, path::PathBuf, sync::OnceLock};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+    path::PathBuf,
+    sync::OnceLock,
+    time::Duration,
+};
 
 use anyhow::{bail, Context};
 use regex::Regex;
@@ -36,12 +45,17 @@ use crate::{
     anilist::MediaTitle,
     audit::{AuditLogEntry, ScrapeDirectory, ScrapeResult, ScrapeSource},
     fixture::{commit_fixtures, Fixture},
+    japanese::{normalize_japanese_numbers, normalize_unicode},
     kitsunekko::USER_AGENT,
     models::EntryFlags,
-    tmdb, AppState,
+    tmdb, AppState, Config,
 };
 
 const BASE_URL: &str = "https://jpsubbers.com";
+const ROOT_URL: &str = "https://jpsubbers.com/Japanese-Subtitles/";
+
+/// The maximum number of nested `@` groups that are recursed into.
+const MAX_GROUP_DEPTH: usize = 2;
 
 fn regex() -> &'static Regex {
     static HTML_REGEX: OnceLock<Regex> = OnceLock::new();
@@ -111,17 +125,16 @@ impl File {
         Ok(true)
     }
 
-    fn is_supported(&self) -> bool {
-        let extensions = [".zip", ".ass", ".srt", ".7z", ".rar", ".sup"];
-        extensions.iter().any(|s| self.name.ends_with(s))
+    fn is_supported(&self, config: &Config) -> bool {
+        config.is_scraper_file(&self.name)
     }
 }
 
 impl Directory {
     /// Updates the `files` attribute with the file entries found for this entry.
-    pub async fn find_files(&mut self, client: &reqwest::Client) -> anyhow::Result<()> {
+    pub async fn find_files(&mut self, client: &reqwest::Client, config: &Config) -> anyhow::Result<()> {
         self.files = get_entries(client, &self.url).await?;
-        self.files.retain(|f| f.is_supported());
+        self.files.retain(|f| f.is_supported(config));
         for file in &mut self.files {
             cleanup_filename(&mut file.name);
         }
@@ -146,23 +159,107 @@ impl Directory {
     }
 }
 
+/// Returns whether the name is an `@` group, e.g. `@OtherSPs`.
+fn is_group(name: &str) -> bool {
+    name.starts_with('@')
+}
+
+/// Strips the `@group/` prefix from names of directories inside groups.
+fn strip_group_prefix(name: &str) -> &str {
+    match name.split_once('/') {
+        Some((group, rest)) if is_group(group) => rest,
+        _ => name,
+    }
+}
+
 /// Returns a list of file entries from the URL.
 ///
 /// This does not actually download anything. It merely fetches the information.
 /// This method works with both the main directory listing and the subdirectory listings.
 /// However, the return type is always [`File`]. Consider using `into()` to convert it
 /// into a [`Directory`].
+///
+/// `@` groups are not included in the result. See [`get_directories`] for those.
 pub async fn get_entries(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<File>> {
-    let body = client
-        .get(url)
-        .header(
-            reqwest::header::USER_AGENT,
-            reqwest::header::HeaderValue::from_static(USER_AGENT),
-        )
-        .send()
-        .await?
-        .text()
-        .await?;
+    let mut entries = get_listing(client, url).await?;
+    entries.retain(|f| !is_group(&f.name));
+    Ok(entries)
+}
+
+/// Returns every directory in the main directory listing, including the ones nested in `@` groups.
+///
+/// Groups are recursed into up to [`MAX_GROUP_DEPTH`] levels deep, waiting for the given
+/// delay in between each group listing request.
+pub async fn get_directories(client: &reqwest::Client, delay: Duration) -> anyhow::Result<Vec<Directory>> {
+    let mut result = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![(ROOT_URL.to_owned(), 0)];
+    while let Some((url, depth)) = pending.pop() {
+        if !visited.insert(url.clone()) {
+            continue;
+        }
+
+        if depth > 0 {
+            tokio::time::sleep(delay).await;
+        }
+
+        let entries = match get_listing(client, &url).await {
+            Ok(entries) => entries,
+            // Groups are not important enough to fail the entire scrape
+            Err(e) if depth > 0 => {
+                warn!(error = %e, url, "Could not fetch group listing");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            if is_group(&entry.name) {
+                if depth < MAX_GROUP_DEPTH {
+                    pending.push((entry.url, depth + 1));
+                }
+            } else {
+                result.push(Directory::from(entry));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the duration given by the `Retry-After` header, if any.
+///
+/// Only the delay in seconds form is supported.
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let seconds = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+async fn get_listing(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<File>> {
+    let send = || {
+        client
+            .get(url)
+            .header(
+                reqwest::header::USER_AGENT,
+                reqwest::header::HeaderValue::from_static(USER_AGENT),
+            )
+            .send()
+    };
+
+    let mut resp = send().await?;
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let delay = retry_after(&resp).unwrap_or(Duration::from_secs(30));
+        warn!(url, ?delay, "rate limited by jpsubbers, retrying");
+        tokio::time::sleep(delay).await;
+        resp = send().await?;
+    }
+    let body = resp.error_for_status()?.text().await?;
 
     let re = regex();
     re.captures_iter(&body)
@@ -174,9 +271,15 @@ pub async fn get_entries(client: &reqwest::Client, url: &str) -> anyhow::Result<
                 url.push_str("/Japanese-Subtitles/");
             }
             url.push_str(captured_url);
-            let name = &cap["name"];
-            if name.starts_with('@') || name == ".." {
+            let name = strip_group_prefix(&cap["name"]);
+            if name == ".." {
                 None
+            } else if is_group(name) {
+                // Sanitising would mangle the name so it's kept as-is, since it's never stored
+                Some(anyhow::Ok(File {
+                    url,
+                    name: name.to_owned(),
+                }))
             } else {
                 Some(anyhow::Ok(File {
                     url,
@@ -191,12 +294,14 @@ pub async fn get_entries(client: &reqwest::Client, url: &str) -> anyhow::Result<
 ///
 /// Right now these are, e.g. [3rd], [4th], etc. and ・ in between kana.
 ///
-/// There is another known pattern with numbers, e.g. JKと６法全書 vs JKと六法全書 but this one is not
-/// as simple to fix, since e.g. ９ボーダー and ９５ are both fine.
+/// Common compound words written with digits, e.g. JKと６法全書 vs JKと六法全書, are also
+/// converted to kanji. This is not done for every number since e.g. ９ボーダー and ９５ are both fine.
 fn prepare_query(haystack: &str) -> String {
     static KNOWN_PATTERNS: OnceLock<Regex> = OnceLock::new();
     let re = KNOWN_PATTERNS.get_or_init(|| Regex::new(r"(?:・|(?:\d|[０-９])+(?:st|nd|rd|th))").unwrap());
-    re.replace_all(haystack, "").into_owned()
+    let haystack = normalize_japanese_numbers(haystack);
+    let haystack = normalize_unicode(&haystack);
+    re.replace_all(&haystack, "").into_owned()
 }
 
 async fn get_redirects(state: &AppState) -> Option<HashMap<String, i64>> {
@@ -207,20 +312,96 @@ async fn get_redirects(state: &AppState) -> Option<HashMap<String, i64>> {
     serde_json::from_str(&from_storage).ok()
 }
 
-pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
+/// A mapping of directory name to the file names that were in it during the last scrape.
+type SeenFiles = HashMap<String, BTreeSet<String>>;
+
+async fn get_seen_files(state: &AppState) -> Option<SeenFiles> {
+    let from_storage = state
+        .database()
+        .get_from_storage::<String>("jpsubbers_seen_files")
+        .await?;
+    serde_json::from_str(&from_storage).ok()
+}
+
+/// The outcome of a JPSubbers scrape.
+pub struct ScrapeOutcome {
+    /// The fixtures of the directories that had new files.
+    pub fixtures: Vec<Fixture>,
+    /// The names of the directories that failed to be scraped.
+    pub failed: Vec<String>,
+}
+
+/// Finds the directory of an existing entry related to the given TMDB entry, e.g. a prequel.
+async fn get_related_tmdb_directory_path(state: &AppState, api_key: &str, info: &tmdb::Info) -> Option<PathBuf> {
+    let mut related = state.anime_relations().await.by_tmdb_id(info.id);
+    if related.is_empty() {
+        let collection_id = info.collection_id()?;
+        match tmdb::get_collection_parts(&state.client, api_key, collection_id).await {
+            Ok(parts) => {
+                state.add_tmdb_relations(&parts).await;
+                related = state.anime_relations().await.by_tmdb_id(info.id);
+            }
+            Err(e) => {
+                warn!(error = ?e, "could not fetch TMDB collection {}", collection_id);
+                return None;
+            }
+        }
+    }
+
+    for id in related {
+        if let Some(path) = state.get_tmdb_directory_entry_path(id).await {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Scrapes JPSubbers for new files.
+///
+/// This only fails if the directory listing itself can't be fetched. Directories that
+/// fail to be scraped are logged and returned in [`ScrapeOutcome::failed`] instead.
+pub async fn scrape(state: &AppState) -> anyhow::Result<ScrapeOutcome> {
     let mut result = Vec::new();
-    let directories = get_entries(&state.client, "https://jpsubbers.com/Japanese-Subtitles/")
-        .await?
-        .into_iter()
-        .map(Directory::from)
-        .collect::<Vec<_>>();
-
-    let api_key = &state.config().tmdb_api_key;
-    let subtitle_path = state.config().subtitle_path.as_path();
+    let mut failed = Vec::new();
+    let config = state.config();
+    let delay = Duration::from_millis(config.jpsubbers_request_delay_ms);
+    let directories = get_directories(&state.client, delay).await?;
+
+    let api_key = &config.tmdb_api_key;
+    let subtitle_path = config.subtitle_path.as_path();
     let total = directories.len();
     let redirects = get_redirects(state).await.unwrap_or_default();
+    let previously_seen = get_seen_files(state).await.unwrap_or_default();
+    let mut seen = SeenFiles::with_capacity(total);
+    let mut unchanged = 0;
+    if let Some(date) = state
+        .database()
+        .get_from_storage::<OffsetDateTime>("jpsubbers_scrape_date")
+        .await
+    {
+        info!("checking jpsubbers directories for changes since {}", date);
+    }
     for (index, mut entry) in directories.into_iter().enumerate() {
-        entry.find_files(&state.client).await?;
+        if config.is_scraper_blocked(&entry.name) {
+            info!(
+                "[{}/{}] skipping {:?} due to being blocked",
+                index + 1,
+                total,
+                &entry.name
+            );
+            continue;
+        }
+        // Only listing requests are spaced out, the file downloads are done concurrently
+        tokio::time::sleep(delay).await;
+        if let Err(e) = entry
+            .find_files(&state.client, &config)
+            .await
+            .with_context(|| format!("Could not list files in {:?}", &entry.name))
+        {
+            warn!(error = ?e, "[{}/{}] failed to scrape {:?}", index + 1, total, &entry.name);
+            failed.push(entry.name);
+            continue;
+        }
         if entry.files.is_empty() {
             info!(
                 "[{}/{}] skipping {:?} due to having no files",
@@ -231,6 +412,19 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
             continue;
         }
 
+        let files: BTreeSet<String> = entry.files.iter().map(|f| f.name.clone()).collect();
+        if previously_seen.get(&entry.name) == Some(&files) {
+            info!(
+                "[{}/{}] skipping {:?} due to having no changes since the last scrape",
+                index + 1,
+                total,
+                &entry.name
+            );
+            unchanged += 1;
+            seen.insert(entry.name, files);
+            continue;
+        }
+
         let mut directory = subtitle_path.join(format!("jpsubbers_{}", &entry.name));
         let query = prepare_query(&entry.name);
         let mut flags = EntryFlags::default();
@@ -259,6 +453,8 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
                         native: original.japanese_name,
                     },
                     flags: original.flags,
+                    source: ScrapeSource::Jpsubbers,
+                    files_downloaded: 0,
                 }
             } else {
                 Fixture {
@@ -269,12 +465,24 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
                     tmdb_id: None,
                     title: MediaTitle::new(entry.name.clone()),
                     flags,
+                    source: ScrapeSource::Jpsubbers,
+                    files_downloaded: 0,
                 }
             }
-        } else if let Ok(Some(info)) = tmdb::find_match(&state.client, api_key, &query).await {
-            // Check if it this AniList ID exists in the database already
+        } else if let Ok(Some(info)) = tmdb::find_match(&state.client, api_key, &query, Some(state.tmdb_cache())).await
+        {
+            // Check if it this TMDB ID or a related one exists in the database already
             if let Some(path) = state.get_tmdb_directory_entry_path(info.id).await {
                 directory = path;
+            } else if let Some(path) = get_related_tmdb_directory_path(state, api_key, &info).await {
+                info!(
+                    "[{}/{}] using related entry at {} for {:?}",
+                    index + 1,
+                    total,
+                    path.display(),
+                    &entry.name
+                );
+                directory = path;
             }
             flags.set_adult(info.is_adult());
             flags.set_movie(info.id.is_movie());
@@ -286,6 +494,8 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
                 tmdb_id: Some(info.id),
                 title: info.titles(),
                 flags,
+                source: ScrapeSource::Jpsubbers,
+                files_downloaded: 0,
             }
         } else {
             Fixture {
@@ -296,19 +506,39 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
                 tmdb_id: None,
                 title: MediaTitle::new(entry.name.clone()),
                 flags,
+                source: ScrapeSource::Jpsubbers,
+                files_downloaded: 0,
             }
         };
 
         if !directory.exists() {
             if let Err(e) = std::fs::create_dir_all(&directory) {
                 if e.kind() != std::io::ErrorKind::AlreadyExists {
-                    return Err(e).with_context(|| format!("Could not create directory {}", directory.display()));
+                    let e = anyhow::Error::from(e).context(format!("Could not create {}", directory.display()));
+                    warn!(error = ?e, "[{}/{}] failed to scrape {:?}", index + 1, total, &entry.name);
+                    failed.push(entry.name);
+                    continue;
                 }
             }
         }
 
         let name = entry.name.clone();
-        let download_count = entry.download_files(&state.client, directory).await?;
+        let download_count = match entry
+            .download_files(&state.client, directory.clone())
+            .await
+            .with_context(|| format!("Could not download files in {name:?}"))
+        {
+            Ok(count) => count,
+            Err(e) => {
+                warn!(error = ?e, "[{}/{}] failed to scrape {:?}", index + 1, total, name);
+                failed.push(name);
+                continue;
+            }
+        };
+        // Files that failed to download should be retried next time
+        if files.iter().all(|f| directory.join(f).exists()) {
+            seen.insert(name.clone(), files);
+        }
         if download_count == 0 {
             info!(
                 "[{}/{}] skipping {:?} due to having no new files",
@@ -319,17 +549,33 @@ pub async fn scrape(state: &AppState) -> anyhow::Result<Vec<Fixture>> {
             continue;
         } else {
             info!("[{}/{}] finished downloading {:?}", index + 1, total, name);
-            result.push(fixture);
+            result.push(Fixture {
+                files_downloaded: download_count,
+                ..fixture
+            });
         }
     }
 
     info!(
-        "finished downloading {} entries ({} total, {} skipped)",
+        "finished downloading {} entries ({} total, {} skipped, {} unchanged since the last scrape, {} failed)",
         result.len(),
         total,
-        total - result.len()
+        total - result.len() - failed.len(),
+        unchanged,
+        failed.len(),
     );
-    Ok(result)
+    state
+        .database()
+        .update_storage("jpsubbers_seen_files", serde_json::to_string(&seen)?)
+        .await?;
+    state
+        .database()
+        .update_storage("jpsubbers_scrape_date", OffsetDateTime::now_utc())
+        .await?;
+    Ok(ScrapeOutcome {
+        fixtures: result,
+        failed,
+    })
 }
 
 pub async fn auto_scrape_loop(state: AppState) {
@@ -350,45 +596,73 @@ pub async fn auto_scrape_loop(state: AppState) {
             break;
         }
 
-        let result = scrape(&state).await;
-        match result {
-            Ok(fixtures) => {
-                let mut scrape = ScrapeResult {
-                    directories: fixtures
-                        .iter()
-                        .map(|f| ScrapeDirectory {
-                            original_name: f.original_name.clone(),
-                            name: f.title.romaji.clone(),
-                            tmdb_id: f.tmdb_id,
-                            anilist_id: None,
-                        })
-                        .collect(),
-                    error: false,
-                    date: None,
-                    source: ScrapeSource::Jpsubbers,
-                };
-                if let Err(e) = commit_fixtures(&state, fixtures).await {
-                    tracing::error!(error = %e, "Error occurred while committing fixtures");
-                    scrape.error = true;
-                } else if !scrape.directories.is_empty() {
-                    let preview = crate::utils::join_iter(
-                        "\n",
-                        scrape.directories.iter().map(|x| format!("- {}", x.name)).take(25),
-                    );
-                    state.send_alert(
-                        crate::discord::Alert::success("Scraped from JPSubbers")
+        if !state.start_scrape(ScrapeSource::Jpsubbers) {
+            info!("skipping JPSubbers scrape since one is already running");
+        } else {
+            let result = scrape(&state).await;
+            match result {
+                Ok(ScrapeOutcome { fixtures, failed }) => {
+                    let failed_count = failed.len();
+                    let mut scrape = ScrapeResult {
+                        directories: fixtures
+                            .iter()
+                            .map(|f| ScrapeDirectory {
+                                original_name: f.original_name.clone(),
+                                name: f.title.romaji.clone(),
+                                tmdb_id: f.tmdb_id,
+                                anilist_id: None,
+                                failed: false,
+                            })
+                            .chain(failed.into_iter().map(|name| ScrapeDirectory {
+                                original_name: name.clone(),
+                                name,
+                                tmdb_id: None,
+                                anilist_id: None,
+                                failed: true,
+                            }))
+                            .collect(),
+                        error: false,
+                        date: None,
+                        source: ScrapeSource::Jpsubbers,
+                    };
+                    if let Err(e) = commit_fixtures(&state, fixtures).await {
+                        tracing::error!(error = %e, "Error occurred while committing fixtures");
+                        scrape.error = true;
+                    } else if !scrape.directories.is_empty() {
+                        let succeeded = scrape.directories.len() - failed_count;
+                        let preview = crate::utils::join_iter(
+                            "\n",
+                            scrape
+                                .directories
+                                .iter()
+                                .filter(|x| !x.failed)
+                                .map(|x| format!("- {}", x.name))
+                                .take(25),
+                        );
+                        let mut alert = crate::discord::Alert::success("Scraped from JPSubbers")
                             .url("/logs")
                             .description(preview)
-                            .field("Total", scrape.directories.len()),
-                    );
+                            .field("Total", succeeded);
+                        if failed_count > 0 {
+                            alert = alert.field("Failed", failed_count);
+                        }
+                        state.send_alert(alert);
+                    }
+                    let outcome = if scrape.error {
+                        Err("could not commit fixtures".to_owned())
+                    } else {
+                        Ok(scrape.directories.len() - failed_count)
+                    };
+                    state.audit(AuditLogEntry::new(scrape)).await;
+                    state.finish_scrape(ScrapeSource::Jpsubbers, outcome);
+                }
+                Err(e) => {
+                    state
+                        .audit(AuditLogEntry::new(ScrapeResult::errored(ScrapeSource::Jpsubbers)))
+                        .await;
+                    tracing::error!(error = %e, "Error occurred while scraping JPSubbers");
+                    state.finish_scrape(ScrapeSource::Jpsubbers, Err(e.to_string()));
                 }
-                state.audit(AuditLogEntry::new(scrape)).await;
-            }
-            Err(e) => {
-                state
-                    .audit(AuditLogEntry::new(ScrapeResult::errored(ScrapeSource::Jpsubbers)))
-                    .await;
-                tracing::error!(error = %e, "Error occurred while scraping JPSubbers");
             }
         }
 
@@ -410,6 +684,7 @@ mod tests {
     #[tokio::test]
     async fn test_jpsubbers_parse() -> anyhow::Result<()> {
         let client = reqwest::Client::new();
+        let config = Config::new()?;
         let url = "https://jpsubbers.com/Japanese-Subtitles/";
         let mut captures = get_entries(&client, url)
             .await?
@@ -417,10 +692,18 @@ mod tests {
             .map(Directory::from)
             .collect::<Vec<_>>();
         for dir in captures.iter_mut() {
-            dir.find_files(&client).await?;
+            dir.find_files(&client, &config).await?;
         }
         println!("got {} entries", captures.len());
         println!("{:?}", &captures[0..5]);
         Ok(())
     }
+
+    #[test]
+    fn test_strip_group_prefix() {
+        assert_eq!(strip_group_prefix("@OtherSPs/相棒"), "相棒");
+        assert_eq!(strip_group_prefix("@OtherSPs"), "@OtherSPs");
+        assert_eq!(strip_group_prefix("光る君へ"), "光る君へ");
+        assert_eq!(strip_group_prefix("a/b"), "a/b");
+    }
 }
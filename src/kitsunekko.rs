@@ -34,8 +34,10 @@ use crate::{
     anilist::{Media, MediaTitle},
     audit::{AuditLogEntry, ScrapeDirectory, ScrapeResult, ScrapeSource},
     fixture::{commit_fixtures, Fixture},
+    japanese::{is_mostly_japanese, katakana_to_hiragana, normalize_unicode},
     models::EntryFlags,
-    AppState,
+    utils::http_get_with_retry,
+    AppState, Config,
 };
 
 fn regex() -> &'static Regex {
@@ -51,6 +53,9 @@ fn remove_parentheses(haystack: &str) -> String {
     re.replace_all(haystack, "").into_owned()
 }
 
+/// The number of times a failed directory listing request is retried.
+const MAX_RETRIES: usize = 3;
+
 pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:122.0) Gecko/20100101 Firefox/122.0";
 const DATE_FORMAT: &[FormatItem<'static>] =
     format_description!("[month repr:short] [day] [year] [hour repr:12]:[minute]:[second] [period case:upper]");
@@ -83,10 +88,10 @@ impl From<File> for Directory {
 }
 
 impl File {
-    async fn download(self, client: reqwest::Client, directory: PathBuf) -> anyhow::Result<()> {
+    async fn download(self, client: reqwest::Client, directory: PathBuf) -> anyhow::Result<bool> {
         let path = directory.join(&self.name);
         if path.exists() {
-            return Ok(());
+            return Ok(false);
         }
 
         let resp = client.get(&self.url).send().await?;
@@ -104,35 +109,42 @@ impl File {
         let mut file =
             std::fs::File::create(&path).with_context(|| format!("Could not create file at {}", path.display()))?;
         file.write_all(&bytes)?;
-        Ok(())
+        Ok(true)
     }
 
-    fn is_supported(&self) -> bool {
-        let extensions = [".zip", ".ass", ".srt", ".7z", ".rar", ".sup"];
-        extensions.iter().any(|s| self.name.ends_with(s))
+    fn is_supported(&self, config: &Config) -> bool {
+        config.is_scraper_file(&self.name)
     }
 }
 
 impl Directory {
     /// Updates the `files` attribute with the file entries found for this entry.
-    pub async fn find_files(&mut self, client: &reqwest::Client, date: &OffsetDateTime) -> anyhow::Result<()> {
+    pub async fn find_files(
+        &mut self,
+        client: &reqwest::Client,
+        config: &Config,
+        date: &OffsetDateTime,
+    ) -> anyhow::Result<()> {
         self.files = get_entries(client, &self.url).await?;
-        self.files.retain(|f| f.is_supported() && &f.date > date);
+        self.files.retain(|f| f.is_supported(config) && &f.date > date);
         Ok(())
     }
 
-    /// Concurrently downloads every file in this directory
-    pub async fn download_files(self, client: &reqwest::Client, directory: PathBuf) -> anyhow::Result<()> {
+    /// Concurrently downloads every file in this directory, returning the number of new files.
+    pub async fn download_files(self, client: &reqwest::Client, directory: PathBuf) -> anyhow::Result<usize> {
         let mut set = JoinSet::new();
         for file in self.files {
             set.spawn(file.download(client.clone(), directory.clone()));
         }
+        let mut downloaded = 0;
         while let Some(result) = set.join_next().await {
-            if let Ok(Err(e)) = result {
-                warn!(error = %e, "Could not download file");
+            match result {
+                Ok(Err(e)) => warn!(error = %e, "Could not download file"),
+                Ok(Ok(true)) => downloaded += 1,
+                _ => {}
             }
         }
-        Ok(())
+        Ok(downloaded)
     }
 }
 
@@ -143,16 +155,7 @@ impl Directory {
 /// However, the return type is always [`File`]. Consider using `into()` to convert it
 /// into a [`Directory`].
 pub async fn get_entries(client: &reqwest::Client, url: &str) -> anyhow::Result<Vec<File>> {
-    let body = client
-        .get(url)
-        .header(
-            reqwest::header::USER_AGENT,
-            reqwest::header::HeaderValue::from_static(USER_AGENT),
-        )
-        .send()
-        .await?
-        .text()
-        .await?;
+    let body = http_get_with_retry(client, url, MAX_RETRIES).await?;
 
     let re = regex();
     re.captures_iter(&body)
@@ -185,6 +188,14 @@ fn levenshtein_distance(title: &MediaTitle, query: &str) -> usize {
     }
 }
 
+#[inline]
+fn native_levenshtein_distance(title: &MediaTitle, query: &str) -> usize {
+    match &title.native {
+        Some(native) => strsim::levenshtein(&katakana_to_hiragana(query), &katakana_to_hiragana(native)),
+        None => levenshtein_distance(title, query),
+    }
+}
+
 #[inline]
 fn case_insensitive_search(title: &MediaTitle, query: &str) -> bool {
     let base = title.romaji.eq_ignore_ascii_case(query);
@@ -205,7 +216,22 @@ fn case_insensitive_search(title: &MediaTitle, query: &str) -> bool {
     base
 }
 
+/// Checks if the native title matches the query after converting both to hiragana.
+#[inline]
+fn native_search(title: &MediaTitle, query: &str) -> bool {
+    title
+        .native
+        .as_deref()
+        .is_some_and(|native| katakana_to_hiragana(native) == katakana_to_hiragana(query))
+}
+
 async fn get_anilist_info(client: &reqwest::Client, query: &str) -> anyhow::Result<Option<Media>> {
+    let query = normalize_unicode(query);
+    let query = query.as_ref();
+    if is_mostly_japanese(query) {
+        return get_anilist_info_native(client, query).await;
+    }
+
     // The order of this is weird because I wanna rely on the response sort order before doing any
     // postprocessing, but doing it this way avoids the needless clone
     let has_parens = query.contains('(');
@@ -245,6 +271,19 @@ async fn get_anilist_info(client: &reqwest::Client, query: &str) -> anyhow::Resu
     }
 }
 
+/// Like [`get_anilist_info`] except it matches against the native title.
+///
+/// The query must already be normalised.
+async fn get_anilist_info_native(client: &reqwest::Client, query: &str) -> anyhow::Result<Option<Media>> {
+    let mut result = crate::anilist::search_native(client, query).await?;
+    if let Some(idx) = result.iter().position(|m| native_search(&m.title, query)) {
+        return Ok(Some(result.swap_remove(idx)));
+    }
+    Ok(result
+        .into_iter()
+        .min_by_key(|m| native_levenshtein_distance(&m.title, query)))
+}
+
 async fn get_redirects(state: &AppState) -> Option<HashMap<String, i64>> {
     let from_storage = state
         .database()
@@ -268,11 +307,21 @@ pub async fn scrape(state: &AppState, date: OffsetDateTime) -> anyhow::Result<Ve
     .collect::<Vec<_>>();
 
     directories.sort_by_key(|s| s.date);
-    let subtitle_path = state.config().subtitle_path.as_path();
+    let config = state.config();
+    let subtitle_path = config.subtitle_path.as_path();
     let total = directories.len();
     let redirects = get_redirects(state).await.unwrap_or_default();
     for (index, mut entry) in directories.into_iter().enumerate() {
-        entry.find_files(&state.client, &date).await?;
+        if config.is_scraper_blocked(&entry.name) {
+            info!(
+                "[{}/{}] skipping {:?} due to being blocked",
+                index + 1,
+                total,
+                &entry.name
+            );
+            continue;
+        }
+        entry.find_files(&state.client, &config, &date).await?;
         if entry.files.is_empty() {
             info!(
                 "[{}/{}] skipping {:?} due to having no files",
@@ -309,6 +358,8 @@ pub async fn scrape(state: &AppState, date: OffsetDateTime) -> anyhow::Result<Ve
                         native: original.japanese_name,
                     },
                     flags: original.flags,
+                    source: ScrapeSource::Kitsunekko,
+                    files_downloaded: 0,
                 };
                 if let Some(anilist_id) = original.anilist_id {
                     potential_dupes.insert(anilist_id, as_fixture);
@@ -343,6 +394,8 @@ pub async fn scrape(state: &AppState, date: OffsetDateTime) -> anyhow::Result<Ve
                         tmdb_id: None,
                         title: media.title,
                         flags,
+                        source: ScrapeSource::Kitsunekko,
+                        files_downloaded: 0,
                     },
                 );
             }
@@ -355,6 +408,8 @@ pub async fn scrape(state: &AppState, date: OffsetDateTime) -> anyhow::Result<Ve
                 tmdb_id: None,
                 title: MediaTitle::new(entry.name.clone()),
                 flags,
+                source: ScrapeSource::Kitsunekko,
+                files_downloaded: 0,
             });
         }
 
@@ -367,7 +422,15 @@ pub async fn scrape(state: &AppState, date: OffsetDateTime) -> anyhow::Result<Ve
         }
 
         let name = entry.name.clone();
-        entry.download_files(&state.client, directory).await?;
+        let downloaded = entry.download_files(&state.client, directory.clone()).await?;
+        // Duplicates share a fixture, so the count has to be found by the directory it was downloaded to
+        if let Some(fixture) = result
+            .iter_mut()
+            .chain(potential_dupes.values_mut())
+            .find(|f| f.path == directory)
+        {
+            fixture.files_downloaded += downloaded;
+        }
         info!("[{}/{}] finished downloading {:?}", index + 1, total, name);
     }
 
@@ -399,56 +462,69 @@ pub async fn auto_scrape_loop(state: AppState) {
             break;
         }
 
-        let mut date = state
-            .database()
-            .get_from_storage::<OffsetDateTime>("kitsunekko_scrape_date")
-            .await
-            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
-        let result = scrape(&state, date).await;
-        match result {
-            Ok(fixtures) => {
-                let new_date = fixtures.iter().map(|x| x.last_updated_at).max();
-                let mut scrape = ScrapeResult {
-                    directories: fixtures
-                        .iter()
-                        .map(|f| ScrapeDirectory {
-                            original_name: f.original_name.clone(),
-                            name: f.title.romaji.clone(),
-                            anilist_id: f.anilist_id,
-                            tmdb_id: None,
-                        })
-                        .collect(),
-                    error: false,
-                    date: new_date,
-                    source: ScrapeSource::Kitsunekko,
-                };
-                if let Err(e) = commit_fixtures(&state, fixtures).await {
-                    tracing::error!(error = %e, "Error occurred while committing fixtures");
-                    scrape.error = true;
-                } else if let Some(dt) = new_date {
-                    date = dt;
-                    let preview = crate::utils::join_iter(
-                        "\n",
-                        scrape.directories.iter().map(|x| format!("- {}", x.name)).take(25),
-                    );
-                    state.send_alert(
-                        crate::discord::Alert::success("Scraped from Kitsunekko")
-                            .url("/logs")
-                            .description(preview)
-                            .field("Total", scrape.directories.len()),
-                    );
+        if !state.start_scrape(ScrapeSource::Kitsunekko) {
+            info!("skipping Kitsunekko scrape since one is already running");
+        } else {
+            let mut date = state
+                .database()
+                .get_from_storage::<OffsetDateTime>("kitsunekko_scrape_date")
+                .await
+                .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+            let result = scrape(&state, date).await;
+            match result {
+                Ok(fixtures) => {
+                    let new_date = fixtures.iter().map(|x| x.last_updated_at).max();
+                    let mut scrape = ScrapeResult {
+                        directories: fixtures
+                            .iter()
+                            .map(|f| ScrapeDirectory {
+                                original_name: f.original_name.clone(),
+                                name: f.title.romaji.clone(),
+                                anilist_id: f.anilist_id,
+                                tmdb_id: None,
+                                failed: false,
+                            })
+                            .collect(),
+                        error: false,
+                        date: new_date,
+                        source: ScrapeSource::Kitsunekko,
+                    };
+                    if let Err(e) = commit_fixtures(&state, fixtures).await {
+                        tracing::error!(error = %e, "Error occurred while committing fixtures");
+                        scrape.error = true;
+                    } else if let Some(dt) = new_date {
+                        date = dt;
+                        let preview = crate::utils::join_iter(
+                            "\n",
+                            scrape.directories.iter().map(|x| format!("- {}", x.name)).take(25),
+                        );
+                        state.send_alert(
+                            crate::discord::Alert::success("Scraped from Kitsunekko")
+                                .url("/logs")
+                                .description(preview)
+                                .field("Total", scrape.directories.len()),
+                        );
+                    }
+                    let outcome = if scrape.error {
+                        Err("could not commit fixtures".to_owned())
+                    } else {
+                        Ok(scrape.directories.len())
+                    };
+                    state.audit(AuditLogEntry::new(scrape)).await;
+                    state.finish_scrape(ScrapeSource::Kitsunekko, outcome);
+                }
+                Err(e) => {
+                    state
+                        .audit(AuditLogEntry::new(ScrapeResult::errored(ScrapeSource::Kitsunekko)))
+                        .await;
+                    tracing::error!(error = %e, "Error occurred while scraping Kitsunekko");
+                    state.finish_scrape(ScrapeSource::Kitsunekko, Err(e.to_string()));
                 }
-                state.audit(AuditLogEntry::new(scrape)).await;
-            }
-            Err(e) => {
-                state
-                    .audit(AuditLogEntry::new(ScrapeResult::errored(ScrapeSource::Kitsunekko)))
-                    .await;
-                tracing::error!(error = %e, "Error occurred while scraping Kitsunekko");
             }
+
+            let _ = state.database().update_storage("kitsunekko_scrape_date", date).await;
         }
 
-        let _ = state.database().update_storage("kitsunekko_scrape_date", date).await;
         tokio::select! {
             _ = tokio::time::sleep(std::time::Duration::from_secs(3600)) => {
 
@@ -8,6 +8,7 @@ mod config;
 pub mod database;
 pub mod discord;
 pub mod download;
+pub mod email;
 pub mod error;
 pub mod filters;
 pub mod fixture;
@@ -19,17 +20,19 @@ pub mod key;
 pub mod kitsunekko;
 pub mod logging;
 pub mod models;
+pub mod notification;
 pub mod ratelimit;
 pub mod relations;
 pub mod routes;
 mod state;
+pub mod subtitle;
 pub mod tmdb;
 pub mod token;
 pub mod trash;
 pub mod utils;
 
 pub use cli::{Command, PROGRAM_NAME};
-pub use config::{Config, CONFIG};
+pub use config::Config;
 pub use database::Database;
 pub use routes::{copy_api_token, ApiToken};
 pub use state::AppState;
@@ -3,13 +3,16 @@
 use std::{
     future::Future,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::{Duration, Instant, SystemTime},
 };
 
-use axum::{extract::Request, response::Response};
+use axum::{body::HttpBody, extract::Request, response::Response};
 use crossbeam_channel::Sender;
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
 use tower::{Layer, Service};
 use tracing::{event, Level};
@@ -28,7 +31,10 @@ CREATE TABLE IF NOT EXISTS request (
     user_id INTEGER,
     user_agent TEXT,
     referrer TEXT,
-    latency REAL
+    latency REAL,
+    slow INTEGER NOT NULL DEFAULT 0,
+    response_bytes INTEGER,
+    client_ip TEXT
 );
 
 CREATE INDEX IF NOT EXISTS request_status_code_idx ON request(status_code);
@@ -60,6 +66,20 @@ pub struct RequestLogEntry {
     pub referrer: Option<String>,
     /// The latency (in seconds) of the request
     pub latency: f64,
+    /// Whether the request took longer than the configured slow request threshold.
+    #[serde(default)]
+    pub slow: bool,
+    /// The size of the response body in bytes.
+    ///
+    /// This is only known for responses with a `Content-Length` header.
+    #[serde(default)]
+    pub response_bytes: Option<u64>,
+    /// The IP of the client that made the request.
+    ///
+    /// Behind a trusted reverse proxy this is taken from the `X-Forwarded-For` header
+    /// rather than the connection.
+    #[serde(default)]
+    pub client_ip: Option<String>,
 }
 
 impl RequestLogEntry {
@@ -74,6 +94,121 @@ impl RequestLogEntry {
             user_agent: row.get("user_agent")?,
             referrer: row.get("referrer")?,
             latency: row.get("latency")?,
+            slow: row.get("slow")?,
+            response_bytes: row.get("response_bytes")?,
+            client_ip: row.get("client_ip")?,
+        })
+    }
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote, or newline.
+fn csv_escape(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+impl RequestLogEntry {
+    /// The header row of [`Self::to_csv_row`].
+    pub const CSV_HEADER: &'static str =
+        "id,ts,status_code,path,route,user_id,user_agent,referrer,latency,slow,response_bytes\n";
+
+    /// Returns the entry as a CSV row, including the trailing newline.
+    ///
+    /// Missing values are written as empty fields.
+    pub fn to_csv_row(&self) -> String {
+        fn opt<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            self.id,
+            self.ts,
+            self.status_code,
+            csv_escape(&self.path),
+            csv_escape(&opt(&self.route)),
+            opt(&self.user_id),
+            csv_escape(&opt(&self.user_agent)),
+            csv_escape(&opt(&self.referrer)),
+            self.latency,
+            self.slow as u8,
+            opt(&self.response_bytes),
+        )
+    }
+}
+
+/// Aggregated latency statistics for a single route.
+///
+/// See [`RequestLogger::aggregated_stats`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteStats {
+    /// The "route" of the requests.
+    pub route: String,
+    /// The number of requests made to the route.
+    pub request_count: u64,
+    pub avg_latency_ms: f64,
+    pub min_latency_ms: f64,
+    pub max_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    /// The ratio of requests that resulted in a server error (5xx), from 0.0 to 1.0.
+    pub error_rate: f64,
+    /// The total size of the response bodies with a known size, in bytes.
+    pub total_bandwidth_bytes: u64,
+}
+
+/// Returns the nearest-rank percentile of the already sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl RouteStats {
+    /// Creates the statistics from the latencies, in seconds, sorted in ascending order.
+    fn from_sorted(route: String, latencies: &[f64], errors: u64, bandwidth: u64) -> Self {
+        let count = latencies.len();
+        let total: f64 = latencies.iter().sum();
+        Self {
+            route,
+            request_count: count as u64,
+            avg_latency_ms: if count == 0 { 0.0 } else { total / count as f64 * 1000.0 },
+            min_latency_ms: latencies.first().copied().unwrap_or_default() * 1000.0,
+            max_latency_ms: latencies.last().copied().unwrap_or_default() * 1000.0,
+            p50_latency_ms: percentile(latencies, 50.0) * 1000.0,
+            p95_latency_ms: percentile(latencies, 95.0) * 1000.0,
+            p99_latency_ms: percentile(latencies, 99.0) * 1000.0,
+            error_rate: if count == 0 { 0.0 } else { errors as f64 / count as f64 },
+            total_bandwidth_bytes: bandwidth,
+        }
+    }
+}
+
+/// The number of hits a route or path received.
+///
+/// See [`RequestLogger::top_routes`] and [`RequestLogger::top_paths`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteHit {
+    /// The route, or path for [`RequestLogger::top_paths`], that was requested.
+    pub route: Option<String>,
+    /// The number of requests made.
+    pub hits: u64,
+    /// The number of requests that resulted in a server error (5xx).
+    pub error_count: u64,
+}
+
+impl RouteHit {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            route: row.get("route")?,
+            hits: row.get("hits")?,
+            error_count: row.get("errors")?,
         })
     }
 }
@@ -89,6 +224,8 @@ enum RequestMessage {
 #[derive(Debug, Clone)]
 pub struct RequestLogger {
     sender: Sender<RequestMessage>,
+    /// The path to the request database, used to open separate read-only connections.
+    path: Arc<PathBuf>,
 }
 
 fn bulk_insert_request_logs<It>(connection: &mut rusqlite::Connection, logs: It) -> rusqlite::Result<()>
@@ -96,7 +233,12 @@ where
     It: Iterator<Item = RequestLogEntry>,
 {
     let tx = connection.transaction()?;
-    let query = r#"INSERT INTO request(ts, status_code, path, route, user_id, user_agent, referrer, latency) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#;
+    let query = r#"
+        INSERT INTO request(
+            ts, status_code, path, route, user_id, user_agent, referrer, latency, slow, response_bytes, client_ip
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    "#;
 
     {
         let mut stmt = tx.prepare_cached(query)?;
@@ -110,6 +252,9 @@ where
                 log.user_agent,
                 log.referrer,
                 log.latency,
+                log.slow,
+                log.response_bytes,
+                log.client_ip,
             ])?;
         }
     }
@@ -136,14 +281,30 @@ fn clean_request_logs(connection: &mut rusqlite::Connection) -> rusqlite::Result
     Ok(())
 }
 
+/// Adds a column to request databases that were created before it existed.
+fn add_missing_column(connection: &rusqlite::Connection, name: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('request') WHERE name = ?)",
+        [name],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute_batch(&format!("ALTER TABLE request ADD COLUMN {name} {definition};"))?;
+    }
+    Ok(())
+}
+
 impl RequestLogger {
     pub fn new() -> anyhow::Result<Self> {
         let (sender, receiver) = crossbeam_channel::unbounded();
         let mut path = crate::database::directory()?;
         path.set_file_name("requests.db");
 
-        let mut connection = rusqlite::Connection::open(path)?;
+        let mut connection = rusqlite::Connection::open(&path)?;
         connection.execute_batch(REQUEST_LOGGING_QUERY)?;
+        add_missing_column(&connection, "slow", "INTEGER NOT NULL DEFAULT 0")?;
+        add_missing_column(&connection, "response_bytes", "INTEGER")?;
+        add_missing_column(&connection, "client_ip", "TEXT")?;
 
         std::thread::spawn(move || {
             // This set up is so it can be bulk-inserted somewhat efficiently
@@ -176,7 +337,10 @@ impl RequestLogger {
             }
         });
 
-        Ok(Self { sender })
+        Ok(Self {
+            sender,
+            path: Arc::new(path),
+        })
     }
 
     /// Requests to terminate the worker thread
@@ -214,6 +378,106 @@ impl RequestLogger {
             .expect("unexpected channel termination: should be unreachable")
     }
 
+    /// Reclaims unused disk space by running `VACUUM` on the request database.
+    ///
+    /// See [`crate::Database::vacuum`] for more details.
+    pub async fn vacuum(&self) -> rusqlite::Result<()> {
+        self.call(|conn| {
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", [], |_| Ok(()))?;
+            conn.execute_batch("VACUUM;")
+        })
+        .await
+    }
+
+    /// Returns the total number of pages in the request database file.
+    pub async fn page_count(&self) -> rusqlite::Result<i64> {
+        self.call(|conn| conn.query_row("PRAGMA page_count;", [], |row| row.get(0)))
+            .await
+    }
+
+    /// Returns latency statistics grouped by route for requests made after the given
+    /// UNIX timestamp in milliseconds.
+    ///
+    /// SQLite has no percentile functions so the latencies are sorted by the query and
+    /// the percentiles are computed here instead.
+    pub async fn aggregated_stats(&self, after: i64) -> rusqlite::Result<Vec<RouteStats>> {
+        self.call(move |conn| -> rusqlite::Result<Vec<RouteStats>> {
+            let mut stmt = conn.prepare_cached(
+                r#"
+                SELECT route, status_code, latency, response_bytes FROM request
+                WHERE ts >= ? AND route IS NOT NULL AND latency IS NOT NULL
+                ORDER BY route, latency
+                "#,
+            )?;
+            let mut rows = stmt.query([after])?;
+            let mut result = Vec::new();
+            let mut current: Option<String> = None;
+            let mut latencies = Vec::new();
+            let mut errors = 0;
+            let mut bandwidth = 0;
+            while let Some(row) = rows.next()? {
+                let route: String = row.get(0)?;
+                let status_code: u16 = row.get(1)?;
+                let latency: f64 = row.get(2)?;
+                let bytes: Option<u64> = row.get(3)?;
+                if current.as_ref() != Some(&route) {
+                    if let Some(previous) = current.replace(route) {
+                        result.push(RouteStats::from_sorted(previous, &latencies, errors, bandwidth));
+                    }
+                    latencies.clear();
+                    errors = 0;
+                    bandwidth = 0;
+                }
+                latencies.push(latency);
+                bandwidth += bytes.unwrap_or_default();
+                if status_code >= 500 {
+                    errors += 1;
+                }
+            }
+            if let Some(route) = current {
+                result.push(RouteStats::from_sorted(route, &latencies, errors, bandwidth));
+            }
+            Ok(result)
+        })
+        .await
+    }
+
+    async fn route_hits(&self, query: &'static str, limit: usize, after: i64) -> rusqlite::Result<Vec<RouteHit>> {
+        self.call(move |conn| -> rusqlite::Result<Vec<RouteHit>> {
+            let mut stmt = conn.prepare_cached(query)?;
+            let rows = stmt.query_map((after, limit), RouteHit::from_row)?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Returns the most requested routes made after the given UNIX timestamp in milliseconds.
+    pub async fn top_routes(&self, limit: usize, after: i64) -> rusqlite::Result<Vec<RouteHit>> {
+        let query = r#"
+            SELECT route, COUNT(*) AS hits, SUM(CASE WHEN status_code >= 500 THEN 1 ELSE 0 END) AS errors
+            FROM request
+            WHERE ts > ?
+            GROUP BY route
+            ORDER BY hits DESC
+            LIMIT ?
+        "#;
+        self.route_hits(query, limit, after).await
+    }
+
+    /// Returns the most requested paths that did not match any route, e.g. 404s,
+    /// made after the given UNIX timestamp in milliseconds.
+    pub async fn top_paths(&self, limit: usize, after: i64) -> rusqlite::Result<Vec<RouteHit>> {
+        let query = r#"
+            SELECT path AS route, COUNT(*) AS hits, SUM(CASE WHEN status_code >= 500 THEN 1 ELSE 0 END) AS errors
+            FROM request
+            WHERE ts > ? AND route IS NULL
+            GROUP BY path
+            ORDER BY hits DESC
+            LIMIT ?
+        "#;
+        self.route_hits(query, limit, after).await
+    }
+
     /// Requests logs given the following query and parameters.
     pub async fn query<Q, P>(&self, query: Q, params: P) -> rusqlite::Result<Vec<RequestLogEntry>>
     where
@@ -232,17 +496,88 @@ impl RequestLogger {
         })
         .await
     }
+
+    /// Like [`Self::query`] except the rows are sent one at a time as they are read.
+    ///
+    /// This is meant for large exports that should not be buffered in memory. The query
+    /// runs on its own read-only connection in a blocking task so the logger thread can
+    /// keep inserting new log entries in the meantime.
+    pub fn stream_query<Q, P>(&self, query: Q, params: P) -> impl Stream<Item = rusqlite::Result<RequestLogEntry>>
+    where
+        Q: Into<std::borrow::Cow<'static, str>> + Send,
+        P: rusqlite::Params + Send + 'static,
+    {
+        let query = query.into();
+        let path = self.path.clone();
+        let (sender, receiver) = tokio::sync::mpsc::channel(256);
+        tokio::task::spawn_blocking(move || {
+            let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+            let conn = match rusqlite::Connection::open_with_flags(path.as_path(), flags) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(e));
+                    return;
+                }
+            };
+            let mut stmt = match conn.prepare(query.as_ref()) {
+                Ok(stmt) => stmt,
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(e));
+                    return;
+                }
+            };
+            let rows = match stmt.query_map(params, RequestLogEntry::from_row) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(e));
+                    return;
+                }
+            };
+            for row in rows {
+                // The receiver is gone if e.g. the client disconnected
+                if sender.blocking_send(row).is_err() {
+                    break;
+                }
+            }
+        });
+
+        futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|row| (row, receiver))
+        })
+    }
 }
 
 /// Layer for [HttpTraceService]
 #[derive(Clone)]
 pub struct HttpTrace {
     logger: RequestLogger,
+    slow_threshold: Duration,
+    trust_x_forwarded_for: bool,
 }
 
 impl HttpTrace {
     pub fn new(logger: RequestLogger) -> Self {
-        Self { logger }
+        Self {
+            logger,
+            slow_threshold: Duration::from_millis(crate::config::DEFAULT_SLOW_REQUEST_THRESHOLD_MS),
+            trust_x_forwarded_for: false,
+        }
+    }
+
+    /// Sets the latency, in milliseconds, after which a request is considered slow.
+    ///
+    /// Slow requests emit a warning and are marked as such in the request log.
+    pub fn slow_request_threshold(mut self, ms: u64) -> Self {
+        self.slow_threshold = Duration::from_millis(ms);
+        self
+    }
+
+    /// Sets whether the client IP is taken from the `X-Forwarded-For` header.
+    ///
+    /// The connection IP is used if the header is missing or invalid.
+    pub fn trust_x_forwarded_for(mut self, trust: bool) -> Self {
+        self.trust_x_forwarded_for = trust;
+        self
     }
 }
 
@@ -253,6 +588,8 @@ impl<S> Layer<S> for HttpTrace {
         HttpTraceService {
             inner,
             logger: self.logger.clone(),
+            slow_threshold: self.slow_threshold,
+            trust_x_forwarded_for: self.trust_x_forwarded_for,
         }
     }
 }
@@ -261,6 +598,17 @@ impl<S> Layer<S> for HttpTrace {
 pub struct HttpTraceService<S> {
     inner: S,
     logger: RequestLogger,
+    slow_threshold: Duration,
+    trust_x_forwarded_for: bool,
+}
+
+/// Returns the client IP from the `X-Forwarded-For` header.
+///
+/// Only the rightmost value is used since that is the one appended by the reverse proxy in front
+/// of the server. Everything to the left of it is sent by the client and can be spoofed.
+fn forwarded_client_ip(headers: &axum::http::HeaderMap) -> Option<IpAddr> {
+    let value = headers.get("x-forwarded-for")?.to_str().ok()?;
+    value.rsplit(',').next()?.trim().parse().ok()
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -327,6 +675,17 @@ where
             .get::<axum::extract::MatchedPath>()
             .map(|p| p.as_str().to_owned());
 
+        let mut ip = req
+            .extensions()
+            .get::<axum::extract::ConnectInfo<SocketAddr>>()
+            .map(|addr| addr.ip());
+
+        if self.trust_x_forwarded_for {
+            if let Some(forwarded) = forwarded_client_ip(req.headers()) {
+                ip = Some(forwarded);
+            }
+        }
+
         let log = RequestLogEntry {
             ts: unix_now_ms(),
             path,
@@ -334,20 +693,17 @@ where
             user_id: get_token_from_request(req.extensions()).map(|t| t.id),
             user_agent,
             referrer,
+            client_ip: ip.map(|ip| ip.to_string()),
             ..Default::default()
         };
 
-        let ip = req
-            .extensions()
-            .get::<axum::extract::ConnectInfo<SocketAddr>>()
-            .map(|addr| addr.ip());
-
         PostFuture {
             inner: self.inner.call(req),
             logger: self.logger.clone(),
             log,
             ip,
             start,
+            slow_threshold: self.slow_threshold,
         }
     }
 }
@@ -364,6 +720,7 @@ pin_project_lite::pin_project! {
         log: RequestLogEntry,
         ip: Option<IpAddr>,
         start: Instant,
+        slow_threshold: Duration,
     }
 }
 
@@ -381,12 +738,25 @@ where
         };
         let latency = this.start.elapsed();
         this.log.latency = latency.as_secs_f64();
+        this.log.slow = latency > *this.slow_threshold;
         if let Ok(res) = &res {
             let status_code = res.status().as_u16();
             this.log.status_code = status_code;
+            // Content-Length is only filled in by hyper after this point, so ask the body instead.
+            // Streaming responses don't know their size up front and are left unknown.
+            this.log.response_bytes = HttpBody::size_hint(res.body()).exact();
             if let Some(token) = res.extensions().get::<crate::ApiToken>() {
                 this.log.user_id = Some(token.id);
             }
+            if this.log.slow {
+                tracing::warn!(
+                    path = this.log.path,
+                    route = this.log.route.as_deref(),
+                    status_code,
+                    latency_ms = latency.as_millis() as u64,
+                    "slow request"
+                );
+            }
             if (400..=499).contains(&status_code) {
                 let reason = BadRequestReason::from_response(res).as_str();
                 if let Some(ip) = this.ip {
@@ -399,3 +769,59 @@ where
         res.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_stats() {
+        let latencies: Vec<f64> = (1..=100).map(|x| x as f64 / 1000.0).collect();
+        let stats = RouteStats::from_sorted("/".into(), &latencies, 5, 1024);
+        assert_eq!(stats.request_count, 100);
+        assert_eq!(stats.p50_latency_ms, 50.0);
+        assert_eq!(stats.p95_latency_ms, 95.0);
+        assert_eq!(stats.p99_latency_ms, 99.0);
+        assert_eq!(stats.min_latency_ms, 1.0);
+        assert_eq!(stats.max_latency_ms, 100.0);
+        assert_eq!(stats.error_rate, 0.05);
+        assert_eq!(stats.total_bandwidth_bytes, 1024);
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let entry = RequestLogEntry {
+            id: 1,
+            ts: 1000,
+            status_code: 200,
+            path: "/search".into(),
+            route: Some("/search".into()),
+            user_agent: Some("Mozilla/5.0 (X11, \"Linux\")".into()),
+            latency: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            entry.to_csv_row(),
+            "1,1000,200,/search,/search,,\"Mozilla/5.0 (X11, \"\"Linux\"\")\",,0.5,0,\n"
+        );
+    }
+
+    #[test]
+    fn test_forwarded_client_ip() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(forwarded_client_ip(&headers), None);
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        assert_eq!(forwarded_client_ip(&headers), Some("203.0.113.7".parse().unwrap()));
+        headers.insert("x-forwarded-for", "10.0.0.1, 203.0.113.7".parse().unwrap());
+        assert_eq!(forwarded_client_ip(&headers), Some("203.0.113.7".parse().unwrap()));
+        headers.insert("x-forwarded-for", "unknown".parse().unwrap());
+        assert_eq!(forwarded_client_ip(&headers), None);
+    }
+
+    #[test]
+    fn test_percentile_edge_cases() {
+        assert_eq!(percentile(&[], 50.0), 0.0);
+        assert_eq!(percentile(&[3.0], 99.0), 3.0);
+        assert_eq!(percentile(&[1.0, 2.0], 0.0), 1.0);
+    }
+}
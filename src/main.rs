@@ -98,15 +98,42 @@ async fn shutdown_signal() {
     }
 }
 
+#[cfg(unix)]
+async fn reload_config_on_sighup(state: jimaku::AppState) {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => signal,
+        Err(e) => {
+            error!(error=%e, "could not install SIGHUP handler");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        if let Err(e) = state.reload_config() {
+            error!(error=%e, "could not reload config");
+        }
+    }
+}
+
 async fn run_server(state: jimaku::AppState) -> anyhow::Result<()> {
-    let config = state.config().clone();
-    let _ = jimaku::CONFIG.set(config.clone());
+    let config = state.config().as_ref().clone();
     let addr = config.server.address();
     let secret_key = config.secret_key;
 
     let request_logger = state.requests.clone();
+    let maintenance_state = state.clone();
+    #[cfg(unix)]
+    tokio::spawn(reload_config_on_sighup(state.clone()));
     tokio::spawn(jimaku::kitsunekko::auto_scrape_loop(state.clone()));
     tokio::spawn(jimaku::jpsubbers::auto_scrape_loop(state.clone()));
+    let sync_state = state.clone();
+    tokio::spawn(async move {
+        match sync_state.sync_file_counts().await {
+            Ok(0) => {}
+            Ok(updated) => info!(updated, "synced directory entry file counts"),
+            Err(e) => error!(error=%e, "could not sync directory entry file counts"),
+        }
+    });
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
         loop {
@@ -114,6 +141,19 @@ async fn run_server(state: jimaku::AppState) -> anyhow::Result<()> {
             if !request_logger.cleanup() {
                 break;
             }
+
+            maintenance_state.ratelimits().prune(Duration::from_secs(3600));
+            maintenance_state.prune_registrations();
+
+            if let Some(days) = maintenance_state.config().audit_log_retention_days {
+                let cutoff = time::OffsetDateTime::now_utc() - time::Duration::days(days as i64);
+                let before_ms = (cutoff.unix_timestamp_nanos() / 1_000_000) as i64;
+                match maintenance_state.prune_audit_logs(before_ms).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!(deleted, "pruned old audit log entries"),
+                    Err(e) => error!(error=%e, "could not prune audit log entries"),
+                }
+            }
         }
     });
 
@@ -125,11 +165,17 @@ async fn run_server(state: jimaku::AppState) -> anyhow::Result<()> {
         .nest_service("/robots.txt", ServeFile::new("static/robots.txt"))
         .nest_service("/static", ServeDir::new("static"))
         .layer(middleware::from_fn_with_state(state.clone(), jimaku::copy_api_token))
-        .layer(jimaku::logging::HttpTrace::new(state.requests.clone()))
+        .layer(
+            jimaku::logging::HttpTrace::new(state.requests.clone())
+                .slow_request_threshold(state.config().slow_request_threshold_ms)
+                .trust_x_forwarded_for(state.config().trust_x_forwarded_for),
+        )
         .layer(middleware::from_fn(jimaku::flash::process_flash_messages))
         .layer(middleware::from_fn(jimaku::parse_cookies))
         .layer(Extension(secret_key))
-        .layer(Extension(jimaku::cached::BodyCache::new(Duration::from_secs(120))))
+        .layer(Extension(state.body_cache().clone()))
+        .layer(Extension(state.ratelimits().clone()))
+        .layer(Extension(state.clone()))
         .layer(DefaultBodyLimit::max(jimaku::MAX_BODY_SIZE))
         .layer(tower_http::limit::RequestBodyLimitLayer::new(jimaku::MAX_BODY_SIZE))
         .layer(CompressionLayer::new())
@@ -254,7 +300,32 @@ async fn run_server(state: jimaku::AppState) -> anyhow::Result<()> {
     Ok(())
 }
 
-const MIGRATIONS: [&str; 2] = [include_str!("../sql/0.sql"), include_str!("../sql/1.sql")];
+const MIGRATIONS: [&str; 11] = [
+    include_str!("../sql/0.sql"),
+    include_str!("../sql/1.sql"),
+    include_str!("../sql/2.sql"),
+    include_str!("../sql/3.sql"),
+    include_str!("../sql/4.sql"),
+    include_str!("../sql/5.sql"),
+    include_str!("../sql/6.sql"),
+    include_str!("../sql/7.sql"),
+    include_str!("../sql/8.sql"),
+    include_str!("../sql/9.sql"),
+    include_str!("../sql/10.sql"),
+];
+const ROLLBACK_MIGRATIONS: [&str; 11] = [
+    include_str!("../sql/0_down.sql"),
+    include_str!("../sql/1_down.sql"),
+    include_str!("../sql/2_down.sql"),
+    include_str!("../sql/3_down.sql"),
+    include_str!("../sql/4_down.sql"),
+    include_str!("../sql/5_down.sql"),
+    include_str!("../sql/6_down.sql"),
+    include_str!("../sql/7_down.sql"),
+    include_str!("../sql/8_down.sql"),
+    include_str!("../sql/9_down.sql"),
+    include_str!("../sql/10_down.sql"),
+];
 
 fn init_db(connection: &mut rusqlite::Connection) -> rusqlite::Result<()> {
     let tx = connection.transaction()?;
@@ -269,18 +340,52 @@ fn init_db(connection: &mut rusqlite::Connection) -> rusqlite::Result<()> {
     tx.commit()
 }
 
+/// Rolls back the given number of migrations.
+///
+/// Returns the number of migrations that were rolled back and the new schema version.
+fn rollback_db(connection: &mut rusqlite::Connection, steps: usize) -> rusqlite::Result<(usize, usize)> {
+    let tx = connection.transaction()?;
+    let version: usize = {
+        let mut stmt = tx.prepare_cached("PRAGMA user_version;")?;
+        stmt.query_row([], |r| r.get(0))?
+    };
+    let version = version.min(ROLLBACK_MIGRATIONS.len());
+    let target = version.saturating_sub(steps);
+    for migration in ROLLBACK_MIGRATIONS[target..version].iter().rev() {
+        tx.execute_batch(migration)?;
+    }
+    tx.commit()?;
+    Ok((version - target, target))
+}
+
 async fn run(command: jimaku::Command) -> anyhow::Result<()> {
     let config = jimaku::Config::load()?;
+    let errors = config.validate();
+    for error in &errors {
+        eprintln!("config {error}");
+    }
+    let fatal = errors.iter().filter(|e| e.is_error()).count();
+    if fatal > 0 {
+        anyhow::bail!("config has {fatal} error(s), see above");
+    }
+
     let database = jimaku::Database::file(&jimaku::database::directory()?)
         .with_init(init_db)
+        .query_timeout(Duration::from_secs(config.db_query_timeout_secs))
         .open()
         .await?;
 
     let state = jimaku::AppState::new(config, database).await;
+    let result = run_command(command, state.clone()).await;
+    state.flush_audit_logs().await;
+    result
+}
+
+async fn run_command(command: jimaku::Command, state: jimaku::AppState) -> anyhow::Result<()> {
     match command {
         jimaku::Command::Run => run_server(state).await,
         jimaku::Command::Admin => {
-            let credentials = jimaku::cli::prompt_admin_account()?;
+            let credentials = jimaku::cli::prompt_admin_account(&state.config().argon2_params)?;
             let mut flags = jimaku::models::AccountFlags::default();
             flags.set_admin(true);
             state
@@ -293,7 +398,7 @@ async fn run(command: jimaku::Command) -> anyhow::Result<()> {
             info!("successfully created account {}", credentials.username);
             Ok(())
         }
-        jimaku::Command::Scrape { path } => {
+        jimaku::Command::Scrape { path, append } => {
             let date = state
                 .database()
                 .get_from_storage::<time::OffsetDateTime>("kitsunekko_scrape_date")
@@ -303,8 +408,13 @@ async fn run(command: jimaku::Command) -> anyhow::Result<()> {
             info!("scraping kitsunekko entries newer than {}", &date);
             let fixtures = jimaku::kitsunekko::scrape(&state, date).await?;
             let path = path.unwrap_or("fixtures.json".into());
-            let fp = std::fs::File::create(path)?;
-            serde_json::to_writer(fp, &fixtures)?;
+            if append {
+                let fp = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+                jimaku::fixture::write_ndjson(std::io::BufWriter::new(fp), &fixtures)?;
+            } else {
+                let fp = std::fs::File::create(path)?;
+                serde_json::to_writer(fp, &fixtures)?;
+            }
             if let Some(date) = fixtures.iter().map(|x| x.last_updated_at).max() {
                 state.database().update_storage("kitsunekko_scrape_date", date).await?;
             }
@@ -312,7 +422,7 @@ async fn run(command: jimaku::Command) -> anyhow::Result<()> {
         }
         jimaku::Command::Fixtures { path } => {
             let buffer = std::fs::read_to_string(path)?;
-            let fixtures: Vec<jimaku::fixture::Fixture> = serde_json::from_str(&buffer)?;
+            let fixtures = jimaku::fixture::parse_fixtures(&buffer)?;
             let total = fixtures.len();
             jimaku::fixture::commit_fixtures(&state, fixtures).await?;
             info!("committed {} fixtures to the database", total);
@@ -356,6 +466,26 @@ async fn run(command: jimaku::Command) -> anyhow::Result<()> {
             );
             Ok(())
         }
+        jimaku::Command::Rollback { steps } => {
+            let (rolled_back, version) = state.database().call(move |conn| rollback_db(conn, steps)).await?;
+            info!(
+                "rolled back {} migration(s), schema is now at version {}",
+                rolled_back, version
+            );
+            Ok(())
+        }
+        jimaku::Command::Vacuum => {
+            let before = state.database().page_count().await?;
+            state.database().vacuum().await?;
+            let after = state.database().page_count().await?;
+            println!("main.db: {before} pages -> {after} pages");
+
+            let before = state.requests.page_count().await?;
+            state.requests.vacuum().await?;
+            let after = state.requests.page_count().await?;
+            println!("requests.db: {before} pages -> {after} pages");
+            Ok(())
+        }
     }
 }
 
@@ -379,3 +509,40 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(connection: &rusqlite::Connection) -> usize {
+        connection
+            .query_row("PRAGMA user_version;", [], |r| r.get(0))
+            .expect("could not get user_version")
+    }
+
+    #[test]
+    fn test_migrations_round_trip() {
+        let mut connection = rusqlite::Connection::open_in_memory().expect("could not open DB");
+        init_db(&mut connection).expect("forward migrations failed");
+        assert_eq!(user_version(&connection), MIGRATIONS.len());
+
+        let (rolled_back, version) = rollback_db(&mut connection, 1).expect("rollback failed");
+        assert_eq!(rolled_back, 1);
+        assert_eq!(version, MIGRATIONS.len() - 1);
+        assert_eq!(user_version(&connection), version);
+
+        let (rolled_back, version) = rollback_db(&mut connection, usize::MAX).expect("rollback failed");
+        assert_eq!(rolled_back, MIGRATIONS.len() - 1);
+        assert_eq!(version, 0);
+        assert_eq!(user_version(&connection), 0);
+        let tables: i64 = connection
+            .query_row("SELECT COUNT(*) FROM sqlite_schema WHERE type = 'table'", [], |r| {
+                r.get(0)
+            })
+            .expect("could not count tables");
+        assert_eq!(tables, 0);
+
+        init_db(&mut connection).expect("forward migrations failed after rollback");
+        assert_eq!(user_version(&connection), MIGRATIONS.len());
+    }
+}
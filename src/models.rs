@@ -40,6 +40,8 @@ impl EntryFlags {
     const EXTERNAL: u32 = 1 << 2;
     const MOVIE: u32 = 1 << 3;
     const ADULT: u32 = 1 << 4;
+    pub(crate) const HIDDEN: u32 = 1 << 5;
+    const FEATURED: u32 = 1 << 6;
 
     pub const fn new() -> Self {
         Self(Self::ANIME)
@@ -98,6 +100,22 @@ impl EntryFlags {
     pub fn set_adult(&mut self, toggle: bool) {
         self.toggle_flag(Self::ADULT, toggle)
     }
+
+    pub fn is_hidden(&self) -> bool {
+        self.has_flag(Self::HIDDEN)
+    }
+
+    pub fn set_hidden(&mut self, toggle: bool) {
+        self.toggle_flag(Self::HIDDEN, toggle)
+    }
+
+    pub fn is_featured(&self) -> bool {
+        self.has_flag(Self::FEATURED)
+    }
+
+    pub fn set_featured(&mut self, toggle: bool) {
+        self.toggle_flag(Self::FEATURED, toggle)
+    }
 }
 
 impl Default for EntryFlags {
@@ -129,6 +147,16 @@ pub struct ExpandedEntryFlags {
     #[schema(example = false)]
     #[serde(default)]
     adult: bool,
+    /// The entry is hidden from public listings.
+    ///
+    /// This is only visible to editors.
+    #[schema(example = false)]
+    #[serde(default, skip_serializing_if = "crate::utils::is_false")]
+    hidden: bool,
+    /// The entry is featured at the top of the listing.
+    #[schema(example = false)]
+    #[serde(default)]
+    featured: bool,
 }
 
 impl From<EntryFlags> for ExpandedEntryFlags {
@@ -139,6 +167,8 @@ impl From<EntryFlags> for ExpandedEntryFlags {
             external: value.is_external(),
             movie: value.is_movie(),
             adult: value.is_adult(),
+            hidden: value.is_hidden(),
+            featured: value.is_featured(),
         }
     }
 }
@@ -151,6 +181,8 @@ impl From<ExpandedEntryFlags> for EntryFlags {
         flags.set_external(value.external);
         flags.set_movie(value.movie);
         flags.set_adult(value.adult);
+        flags.set_hidden(value.hidden);
+        flags.set_featured(value.featured);
         flags
     }
 }
@@ -164,6 +196,8 @@ impl std::fmt::Debug for EntryFlags {
             .field("external", &self.is_external())
             .field("movie", &self.is_movie())
             .field("adult", &self.is_adult())
+            .field("hidden", &self.is_hidden())
+            .field("featured", &self.is_featured())
             .finish()
     }
 }
@@ -251,6 +285,21 @@ pub struct DirectoryEntry {
     #[schema(example = "葬送のフリーレン")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub japanese_name: Option<String>,
+    /// The number of files in the entry.
+    ///
+    /// This is kept up to date by the upload and delete handlers.
+    #[schema(example = 12)]
+    pub file_count: u32,
+    /// The key used for sorting entries by name.
+    ///
+    /// This is only filled in for entries retrieved from [`crate::AppState::directory_entries`].
+    #[serde(skip)]
+    pub sort_key: String,
+    /// The Japanese name transliterated with [`crate::utils::transliterate_filename`].
+    ///
+    /// This is only filled in for entries retrieved from [`crate::AppState::directory_entries`].
+    #[serde(skip)]
+    pub transliterated_japanese_name: Option<String>,
 }
 
 impl Table for DirectoryEntry {
@@ -268,6 +317,7 @@ impl Table for DirectoryEntry {
         "english_name",
         "japanese_name",
         "name",
+        "file_count",
     ];
 
     type Id = i64;
@@ -286,6 +336,9 @@ impl Table for DirectoryEntry {
             notes: row.get("notes")?,
             english_name: row.get("english_name")?,
             japanese_name: row.get("japanese_name")?,
+            file_count: row.get("file_count")?,
+            sort_key: String::new(),
+            transliterated_japanese_name: None,
         })
     }
 }
@@ -309,6 +362,21 @@ pub struct DirectoryEntryData<'a> {
     pub english_name: &'a Option<String>,
     /// The Japanese name of the entry, i.e. with kanji and kana.
     pub japanese_name: &'a Option<String>,
+    /// The key used for sorting entries by name.
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub sort_key: &'a str,
+}
+
+/// Counts the number of files in the given directory.
+pub async fn count_files(path: &std::path::Path) -> std::io::Result<usize> {
+    let mut count = 0;
+    let mut dir = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            count += 1;
+        }
+    }
+    Ok(count)
 }
 
 impl DirectoryEntry {
@@ -329,9 +397,19 @@ impl DirectoryEntry {
             notes: Default::default(),
             english_name: Default::default(),
             japanese_name: Default::default(),
+            file_count: 0,
+            sort_key: Default::default(),
+            transliterated_japanese_name: None,
         }
     }
 
+    /// Counts the number of files in the entry's directory on disk.
+    ///
+    /// Unlike [`DirectoryEntry::file_count`] this does not rely on the stored count.
+    pub async fn file_count(&self) -> std::io::Result<usize> {
+        count_files(&self.path).await
+    }
+
     /// Returns data safe for embedding into the frontend
     pub fn data(&self) -> DirectoryEntryData<'_> {
         DirectoryEntryData {
@@ -342,6 +420,7 @@ impl DirectoryEntry {
             tmdb_id: self.tmdb_id,
             english_name: &self.english_name,
             japanese_name: &self.japanese_name,
+            sort_key: &self.sort_key,
         }
     }
 
@@ -395,6 +474,8 @@ impl ToSql for AccountFlags {
 impl AccountFlags {
     const ADMIN: u32 = 1 << 0;
     const EDITOR: u32 = 1 << 1;
+    const RESTRICTED: u32 = 1 << 2;
+    const TRUSTED: u32 = 1 << 3;
 
     pub const fn new() -> Self {
         Self(0)
@@ -429,6 +510,24 @@ impl AccountFlags {
     pub fn set_editor(&mut self, toggle: bool) {
         self.toggle_flag(Self::EDITOR, toggle)
     }
+
+    /// Restricted accounts can only browse, they cannot upload, create entries, or report.
+    pub fn is_restricted(&self) -> bool {
+        self.has_flag(Self::RESTRICTED)
+    }
+
+    pub fn set_restricted(&mut self, toggle: bool) {
+        self.toggle_flag(Self::RESTRICTED, toggle)
+    }
+
+    /// Trusted accounts can upload to existing entries without them being marked as unverified.
+    pub fn is_trusted(&self) -> bool {
+        self.has_flag(Self::TRUSTED)
+    }
+
+    pub fn set_trusted(&mut self, toggle: bool) {
+        self.toggle_flag(Self::TRUSTED, toggle)
+    }
 }
 
 impl std::fmt::Debug for AccountFlags {
@@ -437,6 +536,8 @@ impl std::fmt::Debug for AccountFlags {
             .field("value", &self.0)
             .field("editor", &self.is_editor())
             .field("admin", &self.is_admin())
+            .field("restricted", &self.is_restricted())
+            .field("trusted", &self.is_trusted())
             .finish()
     }
 }
@@ -444,7 +545,8 @@ impl std::fmt::Debug for AccountFlags {
 /// A registered account.
 ///
 /// This server implements a rather simple authentication scheme.
-/// Passwords are hashed using Argon2. No emails are stored.
+/// Passwords are hashed using Argon2. Emails are optional and only
+/// stored as an HMAC for account recovery purposes.
 ///
 /// Authentication is also done using [`crate::token::Token`] instead of
 /// maintaining a session database.
@@ -462,12 +564,24 @@ pub struct Account {
     pub flags: AccountFlags,
     /// The AniList username associated with this account
     pub anilist_username: Option<String>,
+    /// The HMAC of the email associated with this account, if any.
+    pub email: Option<String>,
+    /// Whether the email associated with this account has been verified.
+    pub email_verified: bool,
 }
 
 impl Table for Account {
     const NAME: &'static str = "account";
 
-    const COLUMNS: &'static [&'static str] = &["id", "name", "password", "flags", "anilist_username"];
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "name",
+        "password",
+        "flags",
+        "anilist_username",
+        "email",
+        "email_verified",
+    ];
 
     type Id = i64;
 
@@ -478,6 +592,8 @@ impl Table for Account {
             password: row.get("password")?,
             flags: row.get("flags")?,
             anilist_username: row.get("anilist_username")?,
+            email: row.get("email")?,
+            email_verified: row.get("email_verified")?,
         })
     }
 }
@@ -546,10 +662,237 @@ impl Table for Session {
 impl Session {
     /// A human readable label used for the user.
     pub fn label(&self) -> &str {
-        self.description.as_deref().unwrap_or("No description")
+        match self.description.as_deref() {
+            // API keys made before they could be described were all stored with this label
+            Some("API Key") | None if self.api_key => "Legacy API Key",
+            Some(description) => description,
+            None => "No description",
+        }
     }
 
     pub fn signed(&self, key: &SecretKey) -> Option<String> {
         Token::from_base64(&self.id).map(|t| t.signed(key))
     }
 }
+
+/// The review status of a report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    /// The report has not been reviewed yet.
+    #[default]
+    Pending,
+    /// The report was reviewed and acted upon.
+    Resolved,
+    /// The report was reviewed and deemed invalid.
+    Rejected,
+}
+
+impl ReportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Resolved => "resolved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromSql for ReportStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "pending" => Ok(Self::Pending),
+            "resolved" => Ok(Self::Resolved),
+            "rejected" => Ok(Self::Rejected),
+            _ => Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for ReportStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+fn timestamp_from_row(row: &rusqlite::Row<'_>, name: &str) -> rusqlite::Result<OffsetDateTime> {
+    let ts: i64 = row.get(name)?;
+    OffsetDateTime::from_unix_timestamp(ts)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Integer, Box::new(e)))
+}
+
+/// A time limited ban on an account.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Ban {
+    /// The account that is banned.
+    pub account_id: i64,
+    /// The reason given for the ban.
+    pub reason: Option<String>,
+    /// The admin that issued the ban. This is only `None` if the account was deleted.
+    pub banned_by: Option<i64>,
+    /// When the ban was issued.
+    #[serde(with = "time::serde::timestamp")]
+    pub banned_at: OffsetDateTime,
+    /// When the ban is lifted.
+    #[serde(with = "time::serde::timestamp")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl Table for Ban {
+    const NAME: &'static str = "ban";
+
+    const COLUMNS: &'static [&'static str] = &["account_id", "reason", "banned_by", "banned_at", "expires_at"];
+
+    type Id = i64;
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            account_id: row.get("account_id")?,
+            reason: row.get("reason")?,
+            banned_by: row.get("banned_by")?,
+            banned_at: timestamp_from_row(row, "banned_at")?,
+            expires_at: timestamp_from_row(row, "expires_at")?,
+        })
+    }
+}
+
+/// A user submitted report about an entry or some of its files.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Report {
+    /// The report ID.
+    pub id: i64,
+    /// The entry that was reported. This is only `None` if the entry was deleted.
+    pub entry_id: Option<i64>,
+    /// The account that made the report. This is only `None` if the account was deleted.
+    pub user_id: Option<i64>,
+    /// The reason given for the report.
+    pub reason: String,
+    /// The files that were reported. If empty then the whole entry was reported.
+    pub files: Vec<String>,
+    /// The review status of the report.
+    pub status: ReportStatus,
+    /// When the report was made.
+    #[serde(with = "time::serde::timestamp")]
+    pub created_at: OffsetDateTime,
+    /// The editor that reviewed the report.
+    pub resolved_by: Option<i64>,
+    /// When the report was reviewed.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub resolved_at: Option<OffsetDateTime>,
+    /// The response given by the editor that reviewed the report.
+    pub response: Option<String>,
+}
+
+impl Table for Report {
+    const NAME: &'static str = "report";
+
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "entry_id",
+        "user_id",
+        "reason",
+        "files",
+        "status",
+        "created_at",
+        "resolved_by",
+        "resolved_at",
+        "response",
+    ];
+
+    type Id = i64;
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        let files: String = row.get("files")?;
+        let resolved_at: Option<i64> = row.get("resolved_at")?;
+        Ok(Self {
+            id: row.get("id")?,
+            entry_id: row.get("entry_id")?,
+            user_id: row.get("user_id")?,
+            reason: row.get("reason")?,
+            files: serde_json::from_str(&files).unwrap_or_default(),
+            status: row.get("status")?,
+            created_at: timestamp_from_row(row, "created_at")?,
+            resolved_by: row.get("resolved_by")?,
+            resolved_at: resolved_at.and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok()),
+            response: row.get("response")?,
+        })
+    }
+}
+
+/// The review status of an entry submission.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmissionStatus {
+    /// The submission has not been reviewed yet.
+    #[default]
+    Pending,
+    /// The submission was approved and the entry was created.
+    Approved,
+    /// The submission was rejected.
+    Rejected,
+}
+
+impl SubmissionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromSql for SubmissionStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            "rejected" => Ok(Self::Rejected),
+            _ => Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for SubmissionStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+/// A directory entry submitted by a non-editor that is awaiting review.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct EntrySubmission {
+    /// The submission ID.
+    pub id: i64,
+    /// The account that submitted the entry. This is only `None` if the account was deleted.
+    pub submitted_by: Option<i64>,
+    /// The submitted payload, in the same shape as the entry creation form.
+    pub data: serde_json::Value,
+    /// When the entry was submitted.
+    #[serde(with = "time::serde::timestamp")]
+    pub submitted_at: OffsetDateTime,
+    /// The editor that reviewed the submission.
+    pub reviewed_by: Option<i64>,
+    /// The review status of the submission.
+    pub status: SubmissionStatus,
+}
+
+impl Table for EntrySubmission {
+    const NAME: &'static str = "pending_entry";
+
+    const COLUMNS: &'static [&'static str] = &["id", "submitted_by", "data", "submitted_at", "reviewed_by", "status"];
+
+    type Id = i64;
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        let data: String = row.get("data")?;
+        Ok(Self {
+            id: row.get("id")?,
+            submitted_by: row.get("submitted_by")?,
+            data: serde_json::from_str(&data).unwrap_or_default(),
+            submitted_at: timestamp_from_row(row, "submitted_at")?,
+            reviewed_by: row.get("reviewed_by")?,
+            status: row.get("status")?,
+        })
+    }
+}
@@ -0,0 +1,358 @@
+//! Implements user facing notifications.
+//!
+//! Notifications are stored in the database per user and are meant to be
+//! shown on the website.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use rusqlite::{types::FromSql, ToSql};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+use crate::{
+    database::Table,
+    models::{AccountFlags, EntrySubmission, Report, ReportStatus},
+    Database,
+};
+
+/*
+    Like the audit log, the data in here should be backwards compatible.
+*/
+
+/// The inner data of a notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationData {
+    /// A report the user made was reviewed by an editor.
+    ReportAnswered {
+        report_id: i64,
+        #[serde(default)]
+        entry_id: Option<i64>,
+        status: ReportStatus,
+        #[serde(default)]
+        response: Option<String>,
+    },
+    /// A new report was made that needs to be reviewed.
+    NewReport { report_id: i64, entry_id: i64 },
+    /// An entry the user submitted was approved and created.
+    SubmissionApproved { submission_id: i64, entry_id: i64 },
+    /// An entry the user submitted was rejected.
+    SubmissionRejected {
+        submission_id: i64,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+/// Every notification event type that can be toggled by the user.
+///
+/// These are the same as the `type` of the serialized [`NotificationData`].
+pub const NOTIFICATION_EVENT_TYPES: [&str; 4] = [
+    "report_answered",
+    "new_report",
+    "submission_approved",
+    "submission_rejected",
+];
+
+impl NotificationData {
+    /// Returns the event type of this notification, used for notification preferences.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::ReportAnswered { .. } => "report_answered",
+            Self::NewReport { .. } => "new_report",
+            Self::SubmissionApproved { .. } => "submission_approved",
+            Self::SubmissionRejected { .. } => "submission_rejected",
+        }
+    }
+}
+
+impl FromSql for NotificationData {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?).map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for NotificationData {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let as_str = serde_json::to_string(self).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        Ok(rusqlite::types::ToSqlOutput::Owned(as_str.into()))
+    }
+}
+
+/// A notification sent to a user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    /// The notification ID.
+    pub id: i64,
+    /// The user this notification is for.
+    pub user_id: i64,
+    /// When the notification was created.
+    #[serde(with = "time::serde::timestamp")]
+    pub ts: OffsetDateTime,
+    /// When the notification was marked as read, if ever.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub read_at: Option<OffsetDateTime>,
+    /// Whether the notification was marked as read.
+    pub read: bool,
+    /// The actual data for this notification.
+    pub data: NotificationData,
+}
+
+impl Table for Notification {
+    const NAME: &'static str = "notification";
+    const COLUMNS: &'static [&'static str] = &["id", "user_id", "ts", "read_at", "data"];
+    type Id = i64;
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        let ts: i64 = row.get("ts")?;
+        let read_at: Option<i64> = row.get("read_at")?;
+        let read_at = read_at.and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok());
+        Ok(Self {
+            id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            ts: OffsetDateTime::from_unix_timestamp(ts).unwrap_or(OffsetDateTime::UNIX_EPOCH),
+            read: read_at.is_some(),
+            read_at,
+            data: row.get("data")?,
+        })
+    }
+}
+
+/// A subscription to changes of a user's unread notification count.
+///
+/// This is retrieved from [`crate::AppState::subscribe_notifications`]. The user's
+/// channel is removed once their last subscription is dropped.
+pub struct NotificationSubscription {
+    // Fields are dropped in declaration order, so the receiver is gone by the time the guard runs
+    pub receiver: broadcast::Receiver<u64>,
+    _guard: SubscriptionGuard,
+}
+
+impl NotificationSubscription {
+    pub(crate) fn new(channels: &Arc<DashMap<i64, broadcast::Sender<u64>>>, user_id: i64) -> Self {
+        let receiver = channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(16).0)
+            .subscribe();
+        Self {
+            receiver,
+            _guard: SubscriptionGuard {
+                channels: channels.clone(),
+                user_id,
+            },
+        }
+    }
+}
+
+struct SubscriptionGuard {
+    channels: Arc<DashMap<i64, broadcast::Sender<u64>>>,
+    user_id: i64,
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.channels
+            .remove_if(&self.user_id, |_, sender| sender.receiver_count() == 0);
+    }
+}
+
+/// Sends notifications to users.
+///
+/// This is retrieved from [`crate::AppState::notifications`].
+pub struct NotificationService<'a> {
+    database: &'a Database,
+    channels: &'a DashMap<i64, broadcast::Sender<u64>>,
+}
+
+impl<'a> NotificationService<'a> {
+    pub fn new(database: &'a Database, channels: &'a DashMap<i64, broadcast::Sender<u64>>) -> Self {
+        Self { database, channels }
+    }
+
+    /// Returns the number of unread notifications the given user has.
+    pub async fn unread_count(&self, user_id: i64) -> rusqlite::Result<u64> {
+        self.database
+            .get_row(
+                "SELECT COUNT(*) FROM notification WHERE user_id = ? AND read_at IS NULL",
+                [user_id],
+                |row| row.get(0),
+            )
+            .await
+    }
+
+    /// Sends the user's current unread notification count to their subscribers, if any.
+    ///
+    /// See [`crate::AppState::subscribe_notifications`].
+    pub async fn publish_unread_count(&self, user_id: i64) {
+        let has_subscribers = match self.channels.get(&user_id) {
+            Some(sender) => sender.receiver_count() > 0,
+            None => return,
+        };
+
+        if !has_subscribers {
+            self.channels
+                .remove_if(&user_id, |_, sender| sender.receiver_count() == 0);
+            return;
+        }
+
+        match self.unread_count(user_id).await {
+            Ok(count) => {
+                if let Some(sender) = self.channels.get(&user_id) {
+                    let _ = sender.send(count);
+                }
+            }
+            Err(e) => tracing::error!(error=%e, user_id, "Could not fetch unread notification count"),
+        }
+    }
+
+    /// Sends a notification to the given user.
+    ///
+    /// If the user disabled notifications of this type then nothing is sent.
+    /// Errors are silently dropped, since they can't be handled anyway.
+    pub async fn notify(&self, user_id: i64, data: NotificationData) {
+        let query = r#"
+            INSERT INTO notification(user_id, data)
+            SELECT ?1, ?2
+            WHERE NOT EXISTS (
+                SELECT 1 FROM notification_preferences WHERE user_id = ?1 AND event_type = ?3 AND enabled = 0
+            )
+        "#;
+        let event_type = data.event_type();
+        let result = self.database.execute(query, (user_id, data, event_type)).await;
+
+        match result {
+            Ok(0) => {}
+            Ok(_) => self.publish_unread_count(user_id).await,
+            Err(e) => tracing::error!(error=%e, user_id, "Could not insert notification"),
+        }
+    }
+
+    /// Notifies every editor, other than the reporter, that a new report was made.
+    pub async fn notify_new_report(&self, report: &Report) {
+        let Some(entry_id) = report.entry_id else {
+            return;
+        };
+
+        let data = NotificationData::NewReport {
+            report_id: report.id,
+            entry_id,
+        };
+        let reporter = report.user_id;
+        let editors: rusqlite::Result<Vec<i64>> = self
+            .database
+            .call(move |conn| {
+                let mut stmt = conn.prepare_cached("SELECT id, flags FROM account")?;
+                let rows = stmt.query_map([], |row| Ok((row.get("id")?, row.get::<_, AccountFlags>("flags")?)))?;
+                let mut result = Vec::new();
+                for row in rows {
+                    let (id, flags) = row?;
+                    if flags.is_editor() && Some(id) != reporter {
+                        result.push(id);
+                    }
+                }
+                Ok(result)
+            })
+            .await;
+
+        match editors {
+            Ok(editors) => {
+                for editor in editors {
+                    self.notify(editor, data.clone()).await;
+                }
+            }
+            Err(e) => tracing::error!(error=%e, report_id = report.id, "Could not fetch editors to notify"),
+        }
+    }
+
+    /// Notifies the reporter that their report has been reviewed.
+    pub async fn notify_answered_report(&self, report: &Report) {
+        let Some(user_id) = report.user_id else {
+            return;
+        };
+
+        let data = NotificationData::ReportAnswered {
+            report_id: report.id,
+            entry_id: report.entry_id,
+            status: report.status,
+            response: report.response.clone(),
+        };
+        self.notify(user_id, data).await;
+    }
+
+    /// Notifies the submitter that their entry submission was approved.
+    pub async fn notify_approved_submission(&self, submission: &EntrySubmission, entry_id: i64) {
+        let Some(user_id) = submission.submitted_by else {
+            return;
+        };
+
+        let data = NotificationData::SubmissionApproved {
+            submission_id: submission.id,
+            entry_id,
+        };
+        self.notify(user_id, data).await;
+    }
+
+    /// Notifies the submitter that their entry submission was rejected.
+    pub async fn notify_rejected_submission(&self, submission: &EntrySubmission, reason: Option<String>) {
+        let Some(user_id) = submission.submitted_by else {
+            return;
+        };
+
+        let data = NotificationData::SubmissionRejected {
+            submission_id: submission.id,
+            reason,
+        };
+        self.notify(user_id, data).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_type_matches_serialized_type() {
+        let notifications = [
+            NotificationData::ReportAnswered {
+                report_id: 1,
+                entry_id: None,
+                status: ReportStatus::Pending,
+                response: None,
+            },
+            NotificationData::NewReport {
+                report_id: 1,
+                entry_id: 1,
+            },
+            NotificationData::SubmissionApproved {
+                submission_id: 1,
+                entry_id: 1,
+            },
+            NotificationData::SubmissionRejected {
+                submission_id: 1,
+                reason: None,
+            },
+        ];
+
+        for (data, expected) in notifications.iter().zip(NOTIFICATION_EVENT_TYPES) {
+            let value = serde_json::to_value(data).unwrap();
+            assert_eq!(value["type"], data.event_type());
+            assert_eq!(data.event_type(), expected);
+        }
+    }
+
+    #[test]
+    fn test_subscription_prunes_channel() {
+        let channels = Arc::new(DashMap::new());
+        let first = NotificationSubscription::new(&channels, 1);
+        let second = NotificationSubscription::new(&channels, 1);
+        assert_eq!(channels.len(), 1);
+
+        drop(first);
+        assert!(channels.contains_key(&1));
+        drop(second);
+        assert!(channels.is_empty());
+    }
+}
@@ -1,24 +1,27 @@
 #![allow(clippy::declare_interior_mutable_const)]
 
 use axum::{
-    extract::Request,
-    http::{HeaderName, HeaderValue},
+    extract::{MatchedPath, Request},
+    http::{header::AUTHORIZATION, HeaderName, HeaderValue},
     response::{IntoResponse, Response},
 };
+use dashmap::DashMap;
 use futures_util::future::Either;
 use quick_cache::sync::Cache;
+use serde::Serialize;
 
 use std::{
+    collections::VecDeque,
     future::{ready, Future, Ready},
     hash::Hash,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tower::{Layer, Service};
 
-use crate::error::ApiError;
+use crate::{error::ApiError, token::Token};
 
 const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
 const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
@@ -31,12 +34,30 @@ fn diff_seconds(a: SystemTime, b: SystemTime) -> f32 {
     (a - b) as f32
 }
 
-/// Implements rate limiting using the GCRA algorithm.
+/// The algorithm used to determine whether a request is rate limited.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    /// A token bucket implemented using the GCRA algorithm.
+    ///
+    /// This allows a client to burst up to the full quota at once.
+    #[default]
+    TokenBucket,
+    /// A sliding window that counts the requests made in the last `per` seconds.
+    ///
+    /// This is stricter than [`Self::TokenBucket`] since it prevents bursting at
+    /// window boundaries, at the cost of storing up to `rate` timestamps per key.
+    SlidingWindow,
+}
+
+type Window = Arc<Mutex<VecDeque<Instant>>>;
+
+/// Implements rate limiting using either the GCRA algorithm or a sliding window.
 ///
 /// Note that axum clones this *every* request.
 #[derive(Clone)]
 pub struct RateLimitLayer<T: KeyExtractor> {
     lookup: Arc<Cache<T::Key, SystemTime>>,
+    windows: Option<Arc<Cache<T::Key, Window>>>,
     rate: u16,
     per: f32,
     extractor: T,
@@ -51,6 +72,124 @@ pub trait KeyExtractor: Clone {
     ///
     /// If no key is found for this request then `None` should be returned.
     fn extract(&self, req: &Request) -> Option<Self::Key>;
+
+    /// Returns a human readable representation of the key.
+    ///
+    /// This is what is shown in [`RateLimitRegistry`], so it should not leak secrets.
+    fn describe(key: &Self::Key) -> String;
+}
+
+/// A snapshot of a rate limit bucket as of the last request that hit it.
+#[derive(Debug, Copy, Clone, Serialize)]
+pub struct BucketSnapshot {
+    pub limit: u16,
+    pub remaining: u16,
+    /// When the next request is allowed through, as a UNIX timestamp in seconds.
+    #[serde(serialize_with = "serialize_system_time")]
+    pub reset_at: SystemTime,
+    #[serde(serialize_with = "serialize_system_time")]
+    pub updated_at: SystemTime,
+}
+
+fn serialize_system_time<S: serde::Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64())
+}
+
+impl BucketSnapshot {
+    /// Whether the bucket is currently out of requests.
+    pub fn is_throttled(&self) -> bool {
+        self.remaining == 0 && self.reset_at > SystemTime::now()
+    }
+}
+
+/// The state of every rate limited route, keyed by the route pattern and the described key.
+///
+/// The rate limit layers are built before the application state exists, so they find this
+/// through a request extension and publish a [`BucketSnapshot`] after every request.
+///
+/// At most [`MAX_REGISTRY_BUCKETS`] snapshots are kept. Once that is reached, snapshots whose
+/// bucket has already reset are dropped, and new keys are not recorded if that frees up nothing.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitRegistry {
+    buckets: Arc<DashMap<(String, String), BucketSnapshot>>,
+}
+
+/// The maximum number of bucket snapshots kept by a [`RateLimitRegistry`].
+pub const MAX_REGISTRY_BUCKETS: usize = 10_000;
+
+impl RateLimitRegistry {
+    fn record(&self, route: &str, key: String, info: &RateLimitInfo) {
+        let key = (route.to_owned(), key);
+        if self.buckets.len() >= MAX_REGISTRY_BUCKETS && !self.buckets.contains_key(&key) {
+            let now = SystemTime::now();
+            self.buckets.retain(|_, v| v.reset_at > now);
+            if self.buckets.len() >= MAX_REGISTRY_BUCKETS {
+                return;
+            }
+        }
+
+        let snapshot = BucketSnapshot {
+            limit: info.limit,
+            remaining: info.remaining,
+            reset_at: info.reset_time,
+            updated_at: SystemTime::now(),
+        };
+        self.buckets.insert(key, snapshot);
+    }
+
+    /// Returns every bucket that matches the predicate.
+    pub fn filter<F>(&self, mut predicate: F) -> Vec<(String, String, BucketSnapshot)>
+    where
+        F: FnMut(&str, &str, &BucketSnapshot) -> bool,
+    {
+        self.buckets
+            .iter()
+            .filter(|e| predicate(&e.key().0, &e.key().1, e.value()))
+            .map(|e| (e.key().0.clone(), e.key().1.clone(), *e.value()))
+            .collect()
+    }
+
+    /// Removes buckets that have not been hit since the given duration.
+    pub fn prune(&self, older_than: Duration) {
+        let cutoff = SystemTime::now() - older_than;
+        self.buckets.retain(|_, v| v.updated_at >= cutoff);
+    }
+}
+
+/// A request extension that marks the request as exempt from rate limits.
+///
+/// This is inserted when the request is made with a trusted bearer token.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RateLimitExempt;
+
+/// Checks whether the request is exempt from rate limiting.
+///
+/// If it is, then the request is marked with the [`RateLimitExempt`] extension
+/// so subsequent layers do not have to check again.
+fn is_exempt(req: &mut Request) -> bool {
+    if req.extensions().get::<RateLimitExempt>().is_some() {
+        return true;
+    }
+
+    let Some(token) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    let trusted = req
+        .extensions()
+        .get::<crate::AppState>()
+        .is_some_and(|state| state.config().is_trusted_token(token.trim()));
+
+    if trusted {
+        tracing::debug!(path = %req.uri().path(), "request made with a trusted token");
+        req.extensions_mut().insert(RateLimitExempt);
+    }
+    trusted
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -68,13 +207,66 @@ impl<T: KeyExtractor> RateLimitLayer<T> {
     }
 
     fn process(&self, request: &Request) -> RateLimitInfo {
+        let Some(key) = self.extractor.extract(request) else {
+            return RateLimitInfo::banned();
+        };
+
+        let registry = request.extensions().get::<RateLimitRegistry>();
+        let described = registry.map(|_| T::describe(&key));
+        let info = match &self.windows {
+            Some(windows) => self.process_sliding_window(windows, key),
+            None => self.process_token_bucket(key),
+        };
+
+        let route = request.extensions().get::<MatchedPath>().map(|p| p.as_str());
+        if let (Some(registry), Some(route), Some(key)) = (registry, route, described) {
+            registry.record(route, key, &info);
+        }
+        info
+    }
+
+    fn process_sliding_window(&self, windows: &Cache<T::Key, Window>, key: T::Key) -> RateLimitInfo {
+        let limit = self.rate;
+        let per = Duration::from_secs_f32(self.per);
+        let window = match windows.get(&key) {
+            Some(window) => window,
+            None => {
+                let window = Arc::new(Mutex::new(VecDeque::with_capacity(limit as usize)));
+                windows.insert(key, window.clone());
+                window
+            }
+        };
+
+        let now = Instant::now();
+        let mut timestamps = window.lock().unwrap_or_else(|e| e.into_inner());
+        while timestamps.front().is_some_and(|ts| now.duration_since(*ts) >= per) {
+            timestamps.pop_front();
+        }
+
+        let ratelimited = timestamps.len() >= limit as usize;
+        if !ratelimited {
+            timestamps.push_back(now);
+        }
+
+        let remaining = limit.saturating_sub(timestamps.len() as u16);
+        let retry_after = match timestamps.front() {
+            Some(oldest) if remaining == 0 => per.saturating_sub(now.duration_since(*oldest)).as_secs_f32(),
+            _ => 0.0,
+        };
+        RateLimitInfo {
+            limit,
+            remaining,
+            ratelimited,
+            reset_time: SystemTime::now() + Duration::from_secs_f32(retry_after),
+            retry_after,
+        }
+    }
+
+    fn process_token_bucket(&self, key: T::Key) -> RateLimitInfo {
         let emission_interval = self.emission_interval();
         let limit = self.rate;
         let delay_variation_tolerance = self.per;
         let now = SystemTime::now();
-        let Some(key) = self.extractor.extract(request) else {
-            return RateLimitInfo::banned();
-        };
 
         let tat = self.lookup.get(&key).unwrap_or(now);
         let new_tat = tat.max(now) + Duration::from_secs_f32(emission_interval);
@@ -176,7 +368,7 @@ pin_project_lite::pin_project! {
     {
         #[pin]
         inner: F,
-        info: RateLimitInfo,
+        info: Option<RateLimitInfo>,
     }
 }
 
@@ -192,8 +384,8 @@ where
             Poll::Ready(t) => t,
             Poll::Pending => return Poll::Pending,
         };
-        if let Ok(resp) = &mut res {
-            this.info.modify_headers(resp);
+        if let (Ok(resp), Some(info)) = (&mut res, this.info) {
+            info.modify_headers(resp);
         }
         res.into()
     }
@@ -213,14 +405,21 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, req: Request) -> Self::Future {
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        if is_exempt(&mut req) {
+            return Either::Left(ModifyHeaders {
+                inner: self.inner.call(req),
+                info: None,
+            });
+        }
+
         let info = self.layer.process(&req);
         if info.is_ratelimited() {
             Either::Right(ready(Ok(info.into_response())))
         } else {
             Either::Left(ModifyHeaders {
                 inner: self.inner.call(req),
-                info,
+                info: Some(info),
             })
         }
     }
@@ -236,6 +435,10 @@ impl KeyExtractor for GlobalKeyExtractor {
     fn extract(&self, _req: &Request) -> Option<Self::Key> {
         Some(())
     }
+
+    fn describe(_key: &Self::Key) -> String {
+        "global".to_owned()
+    }
 }
 
 /// A key extractor based on IPs
@@ -250,6 +453,105 @@ impl KeyExtractor for IpKeyExtractor {
             .get::<axum::extract::ConnectInfo<SocketAddr>>()
             .map(|addr| addr.ip())
     }
+
+    fn describe(key: &Self::Key) -> String {
+        key.to_string()
+    }
+}
+
+/// A key extractor based on the `Authorization` header, i.e. the API key.
+///
+/// Requests without an `Authorization` header fall back to being keyed by IP.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ApiKeyExtractor;
+
+impl KeyExtractor for ApiKeyExtractor {
+    type Key = String;
+
+    fn extract(&self, req: &Request) -> Option<Self::Key> {
+        match req.headers().get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+            Some(key) => Some(key.to_owned()),
+            None => IpKeyExtractor.extract(req).map(|ip| ip.to_string()),
+        }
+    }
+
+    fn describe(key: &Self::Key) -> String {
+        // IPs are stored as-is, everything else is an API key
+        match key.parse::<IpAddr>() {
+            Ok(_) => key.clone(),
+            Err(_) => format!("api_key:{}", mask_api_key(key)),
+        }
+    }
+}
+
+/// Only keeps the first few characters of an API key so it can be told apart without leaking it.
+fn mask_api_key(key: &str) -> String {
+    let prefix: String = key.trim_start_matches("Bearer ").chars().take(6).collect();
+    format!("{prefix}…")
+}
+
+/// The part of a request that is used as the rate limit key.
+///
+/// This is meant to be passed to [`RateLimit::key`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The source IP of the request.
+    #[default]
+    Ip,
+    /// The account ID of the [`crate::ApiToken`] used in the request, or of the
+    /// signed session cookie if there is no API token.
+    ///
+    /// Anonymous requests fall back to being keyed by IP.
+    AccountId,
+    /// The API key in the `Authorization` header.
+    ///
+    /// Requests without an API key fall back to being keyed by IP.
+    ApiKey,
+}
+
+/// The key extracted by [`RateLimitKey`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RequestKey {
+    Ip(IpAddr),
+    AccountId(i64),
+    ApiKey(String),
+}
+
+impl KeyExtractor for RateLimitKey {
+    type Key = RequestKey;
+
+    fn extract(&self, req: &Request) -> Option<Self::Key> {
+        match self {
+            Self::Ip => {}
+            Self::AccountId => {
+                if let Some(token) = req.extensions().get::<crate::ApiToken>() {
+                    return Some(RequestKey::AccountId(token.id));
+                }
+                let cookie = req
+                    .extensions()
+                    .get::<Vec<cookie::Cookie>>()
+                    .and_then(|cookies| cookies.iter().find(|c| c.name() == "token"));
+                let key = req.extensions().get::<crate::key::SecretKey>();
+                if let Some(token) = cookie.zip(key).and_then(|(c, key)| Token::from_signed(c.value(), key)) {
+                    return Some(RequestKey::AccountId(token.id));
+                }
+            }
+            Self::ApiKey => {
+                if let Some(key) = req.headers().get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+                    return Some(RequestKey::ApiKey(key.to_owned()));
+                }
+            }
+        }
+        IpKeyExtractor.extract(req).map(RequestKey::Ip)
+    }
+
+    fn describe(key: &Self::Key) -> String {
+        match key {
+            RequestKey::Ip(ip) => ip.to_string(),
+            RequestKey::AccountId(id) => format!("account:{id}"),
+            RequestKey::ApiKey(key) => format!("api_key:{}", mask_api_key(key)),
+        }
+    }
 }
 
 /// A builder for creating [`RateLimitLayer`].
@@ -257,6 +559,7 @@ pub struct RateLimit<T: KeyExtractor> {
     max_capacity: usize,
     rate: u16,
     per: f32,
+    algorithm: RateLimitAlgorithm,
     extractor: T,
 }
 
@@ -268,6 +571,7 @@ impl Default for RateLimit<IpKeyExtractor> {
             max_capacity: 10_000,
             rate: 5,
             per: 5.0,
+            algorithm: RateLimitAlgorithm::TokenBucket,
             extractor: IpKeyExtractor,
         }
     }
@@ -279,10 +583,16 @@ impl<T: KeyExtractor> RateLimit<T> {
             max_capacity: self.max_capacity,
             rate: self.rate,
             per: self.per,
+            algorithm: self.algorithm,
             extractor: key,
         }
     }
 
+    /// Sets which part of the request is used as the rate limit key.
+    pub fn key(self, key: RateLimitKey) -> RateLimit<RateLimitKey> {
+        self.extractor(key)
+    }
+
     pub fn max_capacity(mut self, capacity: usize) -> Self {
         self.max_capacity = capacity;
         self
@@ -294,9 +604,22 @@ impl<T: KeyExtractor> RateLimit<T> {
         self
     }
 
+    /// Sets the algorithm used for rate limiting.
+    ///
+    /// Defaults to [`RateLimitAlgorithm::TokenBucket`].
+    pub fn algorithm(mut self, algorithm: RateLimitAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
     pub fn build(self) -> RateLimitLayer<T> {
+        let windows = match self.algorithm {
+            RateLimitAlgorithm::TokenBucket => None,
+            RateLimitAlgorithm::SlidingWindow => Some(Arc::new(Cache::new(self.max_capacity))),
+        };
         RateLimitLayer {
             lookup: Arc::new(Cache::new(self.max_capacity)),
+            windows,
             rate: self.rate,
             per: self.per,
             extractor: self.extractor,
@@ -30,6 +30,8 @@ use std::{collections::HashMap, str::FromStr};
 use serde::Serialize;
 use time::format_description::well_known::Iso8601;
 
+use crate::tmdb;
+
 pub const RELATIONS_URL: &str = "https://raw.githubusercontent.com/erengy/anime-relations/master/anime-relations.txt";
 
 /// The relation ID that you can look up relations by.
@@ -179,6 +181,11 @@ pub struct Relations {
     pub created_at: time::OffsetDateTime,
     #[serde(rename = "relations")]
     data: HashMap<u32, Relation>,
+    /// TMDB IDs that belong to the same series, e.g. movies in the same collection.
+    ///
+    /// The anime-relations file has no TMDB data so this is filled in as TMDB entries are looked up.
+    #[serde(skip)]
+    tmdb: HashMap<tmdb::Id, Vec<tmdb::Id>>,
 }
 
 impl Relations {
@@ -231,6 +238,7 @@ impl Relations {
             last_modified,
             data,
             created_at: time::OffsetDateTime::now_utc(),
+            tmdb: HashMap::new(),
         })
     }
 
@@ -248,6 +256,53 @@ impl Relations {
         let rules = self.data.get(&anilist_id)?;
         find_destination(rules, episode)
     }
+
+    /// Returns the AniList IDs that are related to the given ID in either direction.
+    ///
+    /// The returned IDs are sorted, deduplicated, and never include the given ID.
+    pub fn related(&self, anilist_id: u32) -> Vec<u32> {
+        let mut result = self
+            .data
+            .get(&anilist_id)
+            .into_iter()
+            .flatten()
+            .map(|rule| rule.id)
+            .chain(
+                self.data
+                    .iter()
+                    .filter(|(_, rules)| rules.iter().any(|r| r.id == anilist_id))
+                    .map(|(id, _)| *id),
+            )
+            .filter(|id| *id != anilist_id)
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// Records that the given TMDB IDs all belong to the same series.
+    pub fn add_tmdb_group(&mut self, ids: &[tmdb::Id]) {
+        for id in ids {
+            let related = self.tmdb.entry(*id).or_default();
+            related.extend(ids.iter().filter(|x| *x != id));
+            related.sort_unstable();
+            related.dedup();
+        }
+    }
+
+    /// Moves the TMDB relations from another instance into this one.
+    ///
+    /// This is used to keep them when the anime relations are reloaded.
+    pub fn take_tmdb_relations(&mut self, other: &mut Relations) {
+        self.tmdb = std::mem::take(&mut other.tmdb);
+    }
+
+    /// Returns the TMDB IDs that are related to the given ID, such as sequels or prequels.
+    ///
+    /// The returned IDs are sorted, deduplicated, and never include the given ID.
+    pub fn by_tmdb_id(&self, id: tmdb::Id) -> Vec<tmdb::Id> {
+        self.tmdb.get(&id).cloned().unwrap_or_default()
+    }
 }
 
 impl Default for Relations {
@@ -256,6 +311,7 @@ impl Default for Relations {
             last_modified: time::OffsetDateTime::UNIX_EPOCH.date(),
             created_at: time::OffsetDateTime::UNIX_EPOCH,
             data: HashMap::new(),
+            tmdb: HashMap::new(),
         }
     }
 }
@@ -272,4 +328,34 @@ mod tests {
         println!("{:?}", relations.data.len());
         println!("{:?}", relations.find(153152, 13));
     }
+
+    #[test]
+    fn test_related_ids() {
+        let relations = Relations::new(
+            "- last_modified: 2024-01-01\n\
+             - 1|1|100:14-26 -> 2|2|200:1-13!\n\
+             - 3|3|300:1-12 -> ~|~|~:13-24\n\
+             - 4|4|400:13 -> 1|1|100:0\n",
+        )
+        .unwrap();
+
+        assert_eq!(relations.related(100), vec![200, 400]);
+        assert_eq!(relations.related(200), vec![100]);
+        assert_eq!(relations.related(300), Vec::<u32>::new());
+        assert_eq!(relations.related(999), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_tmdb_relations() {
+        let mut relations = Relations::default();
+        let first = tmdb::Id::Movie { id: 10 };
+        let second = tmdb::Id::Movie { id: 20 };
+        let third = tmdb::Id::Movie { id: 30 };
+        relations.add_tmdb_group(&[second, first]);
+        relations.add_tmdb_group(&[first, second, third]);
+
+        assert_eq!(relations.by_tmdb_id(first), vec![second, third]);
+        assert_eq!(relations.by_tmdb_id(third), vec![first, second]);
+        assert_eq!(relations.by_tmdb_id(tmdb::Id::Tv { id: 10 }), Vec::new());
+    }
 }
@@ -1,29 +1,39 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
     audit,
     download::{validate_path, DownloadResponse},
     filters,
-    logging::RequestLogEntry,
+    key::to_hex,
+    logging::{RequestLogEntry, RouteHit, RouteStats},
     utils::logs_directory,
 };
 use askama::Template;
 use axum::{
+    body::Body,
     extract::{Path, Query, Request, State},
-    http::StatusCode,
-    response::Redirect,
-    routing::get,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, patch, post},
     Extension, Json, Router,
 };
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use tower::ServiceExt as _;
 use tower_http::services::ServeFile;
 
+use super::entry::{raw_create_directory_entry, CreateDirectoryEntry, MAX_FEATURED_ENTRIES};
 use crate::{
     cached::BodyCache,
+    database::Table,
     error::ApiError,
-    models::Account,
+    models::{Account, Ban, EntryFlags, EntrySubmission, Report, ReportStatus, SubmissionStatus},
+    ratelimit::BucketSnapshot,
+    state::ScraperState,
     trash::{Trash, TrashListing},
     AppState,
 };
@@ -85,6 +95,122 @@ async fn get_last_logs(
     ))
 }
 
+/// Exports the request logs as CSV.
+///
+/// The rows are streamed from the database rather than buffered since this can be quite large.
+async fn export_logs(
+    account: Account,
+    State(state): State<AppState>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Response, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let (begin, end) = query.limit();
+    let rows = state
+        .requests
+        .stream_query(
+            "SELECT * FROM request WHERE ts >= ? AND ts <= ? ORDER BY id",
+            (begin, end),
+        )
+        .map(|row| row.map(|entry| entry.to_csv_row()));
+    let header = futures_util::stream::once(async { Ok(RequestLogEntry::CSV_HEADER.to_owned()) });
+    let headers = [
+        (CONTENT_TYPE, "text/csv; charset=utf-8"),
+        (CONTENT_DISPOSITION, "attachment; filename=\"requests.csv\""),
+    ];
+    Ok((headers, Body::from_stream(header.chain(rows))).into_response())
+}
+
+async fn get_route_stats(
+    account: Account,
+    State(state): State<AppState>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Json<Vec<RouteStats>>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let (begin, _) = query.limit();
+    Ok(Json(state.requests.aggregated_stats(begin).await?))
+}
+
+/// Returns server metrics in the Prometheus text exposition format.
+///
+/// Scrapers can't log in, so an admin API key in the `Authorization` header works as well.
+async fn get_metrics(
+    State(state): State<AppState>,
+    account: Option<Account>,
+    auth: Option<crate::ApiToken>,
+    Extension(cache): Extension<BodyCache>,
+) -> Result<Response, ApiError> {
+    let account = match (account, auth) {
+        (Some(account), _) => Some(account),
+        (None, Some(auth)) => state.get_account(auth.id).await,
+        (None, None) => None,
+    };
+    let Some(account) = account else {
+        return Err(ApiError::unauthorized());
+    };
+
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let (hits, misses) = cache.stats();
+    let body = format!(
+        "# HELP jimaku_cache_hits_total Responses served from the body cache.\n\
+         # TYPE jimaku_cache_hits_total gauge\n\
+         jimaku_cache_hits_total {hits}\n\
+         # HELP jimaku_cache_misses_total Responses that bypassed or missed the body cache.\n\
+         # TYPE jimaku_cache_misses_total gauge\n\
+         jimaku_cache_misses_total {misses}\n"
+    );
+    Ok((
+        [(CONTENT_TYPE, HeaderValue::from_static("text/plain; version=0.0.4"))],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct TopRoutesQuery {
+    /// The UNIX timestamp in milliseconds, defaults to a day ago.
+    after: Option<i64>,
+    #[serde(default = "default_top_routes_limit")]
+    limit: usize,
+}
+
+fn default_top_routes_limit() -> usize {
+    20
+}
+
+#[derive(Serialize)]
+struct TopRoutes {
+    routes: Vec<RouteHit>,
+    paths: Vec<RouteHit>,
+}
+
+async fn get_top_routes(
+    account: Account,
+    State(state): State<AppState>,
+    Query(query): Query<TopRoutesQuery>,
+) -> Result<Json<TopRoutes>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let after = query
+        .after
+        .unwrap_or_else(|| datetime_to_unix_ms(OffsetDateTime::now_utc() - time::Duration::days(1)));
+    let limit = query.limit.clamp(1, 100);
+    Ok(Json(TopRoutes {
+        routes: state.requests.top_routes(limit, after).await?,
+        paths: state.requests.top_paths(limit, after).await?,
+    }))
+}
+
 async fn get_server_logs(account: Account) -> Result<Json<serde_json::Value>, ApiError> {
     if !account.flags.is_admin() {
         return Err(ApiError::forbidden());
@@ -138,7 +264,7 @@ async fn invalidate_caches(
     Extension(cache): Extension<BodyCache>,
 ) -> Redirect {
     if account.flags.is_admin() {
-        state.cached_directories().invalidate().await;
+        state.cached_directories().invalidate();
         state.clear_account_cache();
         state.clear_session_cache();
         cache.invalidate_all();
@@ -146,6 +272,88 @@ async fn invalidate_caches(
     Redirect::to("/")
 }
 
+/// Reloads the config file, returning the names of the settings that changed.
+///
+/// Only settings that are safe to swap at runtime are applied.
+///
+/// This is a POST so the `SameSite=Lax` session cookie isn't sent on cross-site requests.
+async fn reload_config(State(state): State<AppState>, account: Account) -> Result<Json<Vec<&'static str>>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    match state.reload_config() {
+        Ok(changed) => Ok(Json(changed)),
+        Err(e) => Err(ApiError::new(e.to_string())),
+    }
+}
+
+#[derive(Serialize)]
+struct ScraperStatus {
+    kitsunekko: ScraperState,
+    jpsubbers: ScraperState,
+}
+
+/// Returns the current state of every scraper.
+async fn scraper_status(State(state): State<AppState>, account: Account) -> Result<Json<ScraperStatus>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    Ok(Json(ScraperStatus {
+        kitsunekko: state.scraper_status(audit::ScrapeSource::Kitsunekko),
+        jpsubbers: state.scraper_status(audit::ScrapeSource::Jpsubbers),
+    }))
+}
+
+#[derive(Deserialize)]
+struct RateLimitQuery {
+    ip: Option<String>,
+    account: Option<i64>,
+    route: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RateLimitBucket {
+    route: String,
+    key: String,
+    #[serde(flatten)]
+    snapshot: BucketSnapshot,
+    throttled: bool,
+}
+
+/// Returns the rate limit buckets matching the key and route.
+///
+/// If no key or route is given then only the currently throttled buckets are returned.
+async fn get_ratelimits(
+    State(state): State<AppState>,
+    account: Account,
+    Query(query): Query<RateLimitQuery>,
+) -> Result<Json<Vec<RateLimitBucket>>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let key = query.ip.or(query.account.map(|id| format!("account:{id}")));
+    let only_throttled = key.is_none() && query.route.is_none();
+    let buckets = state.ratelimits().filter(|route, k, snapshot| {
+        key.as_deref().is_none_or(|key| key == k)
+            && query.route.as_deref().is_none_or(|r| r == route)
+            && (!only_throttled || snapshot.is_throttled())
+    });
+    Ok(Json(
+        buckets
+            .into_iter()
+            .map(|(route, key, snapshot)| RateLimitBucket {
+                route,
+                key,
+                throttled: snapshot.is_throttled(),
+                snapshot,
+            })
+            .collect(),
+    ))
+}
+
 #[derive(Template)]
 #[template(path = "admin_trash.html")]
 struct AdminTrashTemplate {
@@ -202,26 +410,54 @@ async fn trash_management(
 
     let trash = Trash::new()?;
     let mut response = TrashResponse::default();
+    let restore = payload.action == TrashRequestAction::Restore;
     let mut data = audit::TrashAction {
-        restore: payload.action == TrashRequestAction::Restore,
+        restore,
         files: Vec::with_capacity(payload.files.len()),
     };
+    // Restorations are logged per entry, files without a known entry fall back to the generic action
+    let mut restored: HashMap<i64, audit::RestoreFiles> = HashMap::new();
     for name in payload.files {
         let filename = PathBuf::from(&name);
         let result = match payload.action {
             TrashRequestAction::Delete => trash.delete(filename).await,
             TrashRequestAction::Restore => trash.restore(filename).await,
         };
-        data.add_file(name, result.is_err());
+        let failed = result.is_err();
+        match Trash::entry_id(&name).filter(|_| restore) {
+            Some(entry_id) => restored
+                .entry(entry_id)
+                .or_insert_with(|| audit::RestoreFiles::new(entry_id))
+                .add_file(name, failed),
+            None => data.add_file(name, failed),
+        }
         match result {
             Ok(()) => response.success += 1,
             Err(_) => response.failed += 1,
         }
     }
 
-    state
-        .audit(audit::AuditLogEntry::new(data).with_account(account.id))
-        .await;
+    for (entry_id, data) in restored {
+        let count = data.files.iter().filter(|f| !f.failed).count();
+        if count != 0 {
+            let _ = state
+                .database()
+                .execute(
+                    "UPDATE directory_entry SET file_count = file_count + ? WHERE id = ?",
+                    (count, entry_id),
+                )
+                .await;
+            state.cached_directories().invalidate();
+        }
+        state
+            .audit(audit::AuditLogEntry::full(data, entry_id, account.id))
+            .await;
+    }
+    if !data.files.is_empty() {
+        state
+            .audit(audit::AuditLogEntry::new(data).with_account(account.id))
+            .await;
+    }
     Ok(Json(response))
 }
 
@@ -243,6 +479,555 @@ async fn download_trash(account: Account, Path(path): Path<String>, req: Request
     }
 }
 
+#[derive(Deserialize)]
+struct PruneAuditQuery {
+    /// The UNIX timestamp, in seconds, to prune audit log entries up to.
+    before: i64,
+}
+
+#[derive(Serialize)]
+struct PruneAuditResponse {
+    deleted: usize,
+}
+
+async fn prune_audit_logs(
+    State(state): State<AppState>,
+    account: Account,
+    Query(query): Query<PruneAuditQuery>,
+) -> Result<Json<PruneAuditResponse>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let before_ms = query.before.saturating_mul(1000);
+    let deleted = state.prune_audit_logs(before_ms).await?;
+    Ok(Json(PruneAuditResponse { deleted }))
+}
+
+/// The default amount of time, in seconds, that an invite code is valid for.
+const DEFAULT_INVITE_EXPIRY: i64 = 604800;
+
+#[derive(Deserialize, Default)]
+struct CreateInvitePayload {
+    /// The amount of seconds until the invite expires. `0` means it never expires.
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct CreateInviteResponse {
+    code: String,
+    expires_at: Option<i64>,
+}
+
+async fn create_invite(
+    State(state): State<AppState>,
+    account: Account,
+    payload: Option<Json<CreateInvitePayload>>,
+) -> Result<Json<CreateInviteResponse>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let Json(payload) = payload.unwrap_or_else(|| Json(Default::default()));
+    let expires_at = match payload.expires_in.unwrap_or(DEFAULT_INVITE_EXPIRY) {
+        0 => None,
+        n if n < 0 => return Err(ApiError::new("expiry must be a positive number")),
+        n => Some(OffsetDateTime::now_utc().unix_timestamp().saturating_add(n)),
+    };
+
+    let mut bytes = [0u8; 12];
+    getrandom::getrandom(&mut bytes)?;
+    let code = to_hex(&bytes);
+    state
+        .database()
+        .execute(
+            "INSERT INTO invitation(code, created_by, expires_at) VALUES (?, ?, ?)",
+            (code.clone(), account.id, expires_at),
+        )
+        .await?;
+
+    Ok(Json(CreateInviteResponse { code, expires_at }))
+}
+
+#[derive(Deserialize)]
+struct BanPayload {
+    #[serde(default)]
+    reason: Option<String>,
+    duration_hours: u32,
+}
+
+async fn ban_account(
+    State(state): State<AppState>,
+    account: Account,
+    Path(user_id): Path<i64>,
+    Json(payload): Json<BanPayload>,
+) -> Result<Json<Ban>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    if payload.duration_hours == 0 {
+        return Err(ApiError::new("duration must be at least one hour"));
+    }
+
+    if payload.reason.as_ref().is_some_and(|r| r.len() > 512) {
+        return Err(ApiError::new("Reason can only be up to 512 characters long"));
+    }
+
+    let Some(target) = state.get_account(user_id).await else {
+        return Err(ApiError::not_found("Account not found"));
+    };
+
+    if target.id == account.id || target.flags.is_admin() {
+        return Err(ApiError::new("Cannot ban this account"));
+    }
+
+    let banned_at = OffsetDateTime::now_utc();
+    let Some(expires_at) = banned_at.checked_add(time::Duration::hours(payload.duration_hours as i64)) else {
+        return Err(ApiError::new("duration is too long"));
+    };
+    let ban: Ban = state
+        .database()
+        .get_row(
+            r#"
+            INSERT INTO ban(account_id, reason, banned_by, banned_at, expires_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(account_id) DO UPDATE
+            SET reason = EXCLUDED.reason, banned_by = EXCLUDED.banned_by,
+                banned_at = EXCLUDED.banned_at, expires_at = EXCLUDED.expires_at
+            RETURNING *
+            "#,
+            (
+                target.id,
+                payload.reason,
+                account.id,
+                banned_at.unix_timestamp(),
+                expires_at.unix_timestamp(),
+            ),
+            Ban::from_row,
+        )
+        .await?;
+    state.uncache_account_sessions(target.id).await;
+    Ok(Json(ban))
+}
+
+async fn unban_account(
+    State(state): State<AppState>,
+    account: Account,
+    Path(user_id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    let deleted = state
+        .database()
+        .execute("DELETE FROM ban WHERE account_id = ?", [user_id])
+        .await?;
+    if deleted == 0 {
+        return Err(ApiError::not_found("Account is not banned"));
+    }
+    state.uncache_account_sessions(user_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct ReportsQuery {
+    #[serde(default)]
+    status: ReportStatus,
+    #[serde(default)]
+    entry_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ReportWithReporter {
+    #[serde(flatten)]
+    report: Report,
+    /// The name of the account that made the report, if it still exists.
+    reporter: Option<String>,
+}
+
+async fn list_reports(
+    State(state): State<AppState>,
+    account: Account,
+    Query(query): Query<ReportsQuery>,
+) -> Result<Json<Vec<ReportWithReporter>>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let reports = state
+        .database()
+        .call(move |conn| -> rusqlite::Result<Vec<ReportWithReporter>> {
+            let mut stmt = conn.prepare_cached(
+                r#"SELECT report.*, account.name AS reporter
+                   FROM report LEFT JOIN account ON account.id = report.user_id
+                   WHERE report.status = ?1 AND (?2 IS NULL OR report.entry_id = ?2)
+                   ORDER BY report.id DESC"#,
+            )?;
+            let rows = stmt.query_map((query.status, query.entry_id), |row| {
+                Ok(ReportWithReporter {
+                    report: Report::from_row(row)?,
+                    reporter: row.get("reporter")?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await?;
+    Ok(Json(reports))
+}
+
+#[derive(Deserialize)]
+struct ReviewReportPayload {
+    status: ReportStatus,
+    #[serde(default, deserialize_with = "crate::utils::empty_string_is_none")]
+    response: Option<String>,
+}
+
+async fn review_report(
+    State(state): State<AppState>,
+    account: Account,
+    Path(report_id): Path<i64>,
+    Json(payload): Json<ReviewReportPayload>,
+) -> Result<Json<Report>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    if payload.status == ReportStatus::Pending {
+        return Err(ApiError::new("A report can only be resolved or rejected"));
+    }
+
+    if payload.response.as_ref().is_some_and(|s| s.len() > 512) {
+        return Err(ApiError::new("Response can only be up to 512 characters long"));
+    }
+
+    let report: Option<Report> = state
+        .database()
+        .get(
+            r#"UPDATE report SET status = ?, response = ?, resolved_by = ?, resolved_at = unixepoch()
+               WHERE id = ? AND status = 'pending' RETURNING *"#,
+            (payload.status, payload.response, account.id, report_id),
+        )
+        .await?;
+
+    let Some(report) = report else {
+        return Err(ApiError::not_found("Pending report not found."));
+    };
+
+    state.notifications().notify_answered_report(&report).await;
+    Ok(Json(report))
+}
+
+async fn list_pending_entries(
+    State(state): State<AppState>,
+    account: Account,
+) -> Result<Json<Vec<EntrySubmission>>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let submissions = state
+        .database()
+        .all("SELECT * FROM pending_entry WHERE status = 'pending' ORDER BY id", [])
+        .await?;
+    Ok(Json(submissions))
+}
+
+async fn get_pending_entry(state: &AppState, id: i64) -> Result<EntrySubmission, ApiError> {
+    let submission: Option<EntrySubmission> = state
+        .database()
+        .get("SELECT * FROM pending_entry WHERE id = ? AND status = 'pending'", [id])
+        .await?;
+    submission.ok_or_else(|| ApiError::not_found("Pending entry not found."))
+}
+
+#[derive(Serialize)]
+struct ApprovedEntry {
+    entry_id: i64,
+}
+
+async fn approve_pending_entry(
+    State(state): State<AppState>,
+    account: Account,
+    Path(id): Path<i64>,
+) -> Result<Json<ApprovedEntry>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let submission = get_pending_entry(&state, id).await?;
+    let payload: CreateDirectoryEntry = serde_json::from_value(submission.data.clone())?;
+
+    // Claim the submission first so two reviewers can't approve it at the same time
+    let claimed = state
+        .database()
+        .execute(
+            "UPDATE pending_entry SET status = ?, reviewed_by = ? WHERE id = ? AND status = 'pending'",
+            (SubmissionStatus::Approved, account.id, id),
+        )
+        .await?;
+    if claimed == 0 {
+        return Err(ApiError::not_found("Pending entry not found."));
+    }
+
+    let (entry_id, _) = match raw_create_directory_entry(&state, account, payload.into(), false).await {
+        Ok(created) => created,
+        Err(e) => {
+            state
+                .database()
+                .execute(
+                    "UPDATE pending_entry SET status = 'pending', reviewed_by = NULL WHERE id = ?",
+                    [id],
+                )
+                .await?;
+            return Err(e);
+        }
+    };
+
+    // The entry is created with the reviewer's permissions but it still belongs to the submitter
+    if let Some(submitter) = submission.submitted_by {
+        state
+            .database()
+            .execute(
+                "UPDATE directory_entry SET creator_id = ? WHERE id = ?",
+                (submitter, entry_id),
+            )
+            .await?;
+        state.cached_directories().invalidate();
+    }
+
+    state
+        .notifications()
+        .notify_approved_submission(&submission, entry_id)
+        .await;
+    Ok(Json(ApprovedEntry { entry_id }))
+}
+
+#[derive(Deserialize, Default)]
+struct RejectPendingEntryPayload {
+    #[serde(default, deserialize_with = "crate::utils::empty_string_is_none")]
+    reason: Option<String>,
+}
+
+async fn reject_pending_entry(
+    State(state): State<AppState>,
+    account: Account,
+    Path(id): Path<i64>,
+    payload: Option<Json<RejectPendingEntryPayload>>,
+) -> Result<StatusCode, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let Json(payload) = payload.unwrap_or_else(|| Json(Default::default()));
+    if payload.reason.as_ref().is_some_and(|s| s.len() > 512) {
+        return Err(ApiError::new("Reason can only be up to 512 characters long"));
+    }
+
+    let submission = get_pending_entry(&state, id).await?;
+    state
+        .database()
+        .execute(
+            "UPDATE pending_entry SET status = ?, reviewed_by = ? WHERE id = ?",
+            (SubmissionStatus::Rejected, account.id, id),
+        )
+        .await?;
+
+    state
+        .notifications()
+        .notify_rejected_submission(&submission, payload.reason)
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The maximum number of entries that can be modified in a single bulk edit.
+const MAX_BULK_EDIT_ENTRIES: usize = 500;
+
+/// The entries a bulk edit applies to.
+#[derive(Deserialize)]
+struct AffectedEntries {
+    ids: Vec<i64>,
+}
+
+/// The flags to change in a bulk edit, flags that are not given are left untouched.
+#[derive(Deserialize, Default)]
+struct PatchFlags {
+    #[serde(default)]
+    anime: Option<bool>,
+    #[serde(default)]
+    unverified: Option<bool>,
+    #[serde(default)]
+    movie: Option<bool>,
+    #[serde(default)]
+    adult: Option<bool>,
+    #[serde(default)]
+    hidden: Option<bool>,
+    #[serde(default)]
+    featured: Option<bool>,
+}
+
+impl PatchFlags {
+    fn changes(&self) -> [(&'static str, Option<bool>); 6] {
+        [
+            ("anime", self.anime),
+            ("unverified", self.unverified),
+            ("movie", self.movie),
+            ("adult", self.adult),
+            ("hidden", self.hidden),
+            ("featured", self.featured),
+        ]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changes().iter().all(|(_, value)| value.is_none())
+    }
+
+    fn apply(&self, mut flags: EntryFlags) -> EntryFlags {
+        if let Some(value) = self.anime {
+            flags.set_anime(value);
+        }
+        if let Some(value) = self.unverified {
+            flags.set_unverified(value);
+        }
+        if let Some(value) = self.movie {
+            flags.set_movie(value);
+        }
+        if let Some(value) = self.adult {
+            flags.set_adult(value);
+        }
+        if let Some(value) = self.hidden {
+            flags.set_hidden(value);
+        }
+        if let Some(value) = self.featured {
+            flags.set_featured(value);
+        }
+        flags
+    }
+
+    /// Returns a human readable description of the change, e.g. `set hidden, unset adult`.
+    fn describe(&self) -> String {
+        crate::utils::join_iter(
+            ", ",
+            self.changes()
+                .into_iter()
+                .filter_map(|(name, value)| value.map(|v| format!("{} {name}", if v { "set" } else { "unset" }))),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct BulkEditPayload {
+    entries: AffectedEntries,
+    flags: PatchFlags,
+}
+
+#[derive(Serialize)]
+struct BulkEditResponse {
+    affected_ids: Vec<i64>,
+}
+
+/// Returns the value shared by every item, if any.
+fn shared_value<T: PartialEq + Copy>(mut iter: impl Iterator<Item = T>) -> Option<T> {
+    let first = iter.next()?;
+    iter.all(|x| x == first).then_some(first)
+}
+
+async fn bulk_edit_entries(
+    State(state): State<AppState>,
+    account: Account,
+    Json(payload): Json<BulkEditPayload>,
+) -> Result<Json<BulkEditResponse>, ApiError> {
+    if !account.flags.is_admin() {
+        return Err(ApiError::forbidden());
+    }
+
+    if payload.flags.is_empty() {
+        return Err(ApiError::new("No flags to change were given"));
+    }
+
+    if payload.entries.ids.len() > MAX_BULK_EDIT_ENTRIES {
+        return Err(ApiError::new(format!(
+            "Cannot edit more than {MAX_BULK_EDIT_ENTRIES} entries at once"
+        )));
+    }
+
+    let description = payload.flags.describe();
+    let BulkEditPayload { entries, flags } = payload;
+    // (id, before, after) for every entry that changed
+    let changed = state
+        .database()
+        .call(move |conn| -> Result<Vec<(i64, EntryFlags, EntryFlags)>, ApiError> {
+            let tx = conn.transaction()?;
+            let mut changed = Vec::new();
+            // Featured entries per category, indexed by whether they're anime
+            let mut featured = [0usize; 2];
+            {
+                let mut stmt = tx.prepare_cached("SELECT flags FROM directory_entry")?;
+                let rows = stmt.query_map([], |row| row.get::<_, EntryFlags>(0))?;
+                for flags in rows {
+                    let flags = flags?;
+                    if flags.is_featured() {
+                        featured[flags.is_anime() as usize] += 1;
+                    }
+                }
+            }
+            let before_featured = featured;
+            {
+                let mut select = tx.prepare_cached("SELECT flags FROM directory_entry WHERE id = ?")?;
+                let mut update = tx.prepare_cached("UPDATE directory_entry SET flags = ? WHERE id = ?")?;
+                for id in entries.ids {
+                    let before: EntryFlags = match select.query_row([id], |row| row.get(0)) {
+                        Ok(before) => before,
+                        Err(rusqlite::Error::QueryReturnedNoRows) => continue,
+                        Err(e) => return Err(e),
+                    };
+                    let after = flags.apply(before);
+                    if before != after {
+                        if before.is_featured() {
+                            featured[before.is_anime() as usize] -= 1;
+                        }
+                        if after.is_featured() {
+                            featured[after.is_anime() as usize] += 1;
+                        }
+                        update.execute((after, id))?;
+                        changed.push((id, before, after));
+                    }
+                }
+            }
+            // Dropping the transaction without committing rolls the edit back
+            if featured
+                .iter()
+                .zip(before_featured)
+                .any(|(&now, before)| now > before && now > MAX_FEATURED_ENTRIES)
+            {
+                return Err(ApiError::new(format!(
+                    "Cannot feature more than {MAX_FEATURED_ENTRIES} entries."
+                )));
+            }
+            tx.commit()?;
+            Ok(changed)
+        })
+        .await?;
+
+    if !changed.is_empty() {
+        state.cached_directories().invalidate();
+        let data = audit::BulkEdit {
+            sql_template: description,
+            affected_ids: changed.iter().map(|(id, _, _)| *id).collect(),
+            before_flags: shared_value(changed.iter().map(|(_, before, _)| *before)),
+            after_flags: shared_value(changed.iter().map(|(_, _, after)| *after)),
+        };
+        state
+            .audit(audit::AuditLogEntry::new(data).with_account(account.id))
+            .await;
+    }
+
+    Ok(Json(BulkEditResponse {
+        affected_ids: changed.into_iter().map(|(id, _, _)| id).collect(),
+    }))
+}
+
 mod api {
     use super::*;
     use crate::ApiToken;
@@ -315,11 +1100,28 @@ pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/admin/logs", get(get_last_logs))
         .route("/admin/logs/server", get(get_server_logs))
+        .route("/admin/logs/export.csv", get(export_logs))
+        .route("/admin/stats/routes", get(get_route_stats))
+        .route("/admin/stats/top-routes", get(get_top_routes))
+        .route("/metrics", get(get_metrics))
         .route("/admin", get(admin_index))
         .route("/admin/user/:id", get(admin_user_by_id))
         .route("/admin/trash", get(show_trash).post(trash_management))
         .route("/admin/trash/download/*path", get(download_trash))
         .route("/admin/cache/invalidate", get(invalidate_caches))
+        .route("/admin/config/reload", post(reload_config))
+        .route("/admin/scraper/status", get(scraper_status))
+        .route("/admin/ratelimits", get(get_ratelimits))
+        .route("/admin/invites", post(create_invite))
+        .route("/admin/accounts/:id/ban", post(ban_account).delete(unban_account))
+        .route("/admin/audit", delete(prune_audit_logs))
+        .route("/admin/audit/:id/diff", get(super::audit::get_audit_log_diff))
+        .route("/admin/reports", get(list_reports))
+        .route("/admin/reports/:id", patch(review_report))
+        .route("/admin/entries/bulk-edit", post(bulk_edit_entries))
+        .route("/admin/pending", get(list_pending_entries))
+        .route("/admin/pending/:id/approve", post(approve_pending_entry))
+        .route("/admin/pending/:id/reject", post(reject_pending_entry))
         .route(
             "/admin/api/scrape-redirects",
             get(api::scrape_redirects).post(api::set_scrape_redirects),
@@ -31,14 +31,24 @@ impl FromRequestParts<AppState> for ApiToken {
     type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(token) = parts.extensions.get::<ApiToken>() {
+            return Ok(*token);
+        }
         extract_api_token_from_headers(&parts.headers, state)
             .await
             .ok_or_else(ApiError::unauthorized)
     }
 }
 
-pub async fn copy_api_token(State(state): State<AppState>, request: Request, next: Next) -> Response {
+/// Copies the API token, if any, into the request and response extensions.
+///
+/// The request extension is used by [`crate::ratelimit::RateLimitKey::AccountId`]
+/// while the response extension is used for logging.
+pub async fn copy_api_token(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
     let api_token = extract_api_token_from_headers(request.headers(), &state).await;
+    if let Some(token) = api_token {
+        request.extensions_mut().insert(token);
+    }
     let mut response = next.run(request).await;
     if let Some(token) = api_token {
         response.extensions_mut().insert(token);
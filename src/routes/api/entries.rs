@@ -1,16 +1,22 @@
-use axum::extract::{Multipart, State};
+use axum::{
+    extract::{Multipart, State},
+    http::{header::LINK, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     anilist::MediaTitle,
+    audit,
     error::{ApiError, ApiErrorCode},
     models::{DirectoryEntry, EntryFlags},
     relations::{Range as RelationRange, Relations},
     routes::entry::{
         get_file_entries, raw_create_directory_entry, raw_upload_file, FileEntry, PendingDirectoryEntry, UploadResult,
     },
+    state::MAX_POPULAR_ENTRIES,
     tmdb, AppState,
 };
 
@@ -19,6 +25,62 @@ use super::{
     utils::{ApiJson as Json, ApiPath as Path, ApiQuery as Query, RateLimitResponse},
 };
 
+const X_TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+const X_NEXT_CURSOR: HeaderName = HeaderName::from_static("x-next-cursor");
+
+/// The signed data stored in a search cursor.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SearchCursor {
+    /// The last modified time of the last entry, in UNIX milliseconds.
+    ts: i64,
+    /// The ID of the last entry.
+    id: i64,
+}
+
+impl SearchCursor {
+    fn new(entry: &DirectoryEntry) -> Self {
+        Self {
+            ts: unix_timestamp_ms(&entry.last_updated_at),
+            id: entry.id,
+        }
+    }
+
+    fn is_after(&self, entry: &DirectoryEntry) -> bool {
+        (unix_timestamp_ms(&entry.last_updated_at), entry.id) < (self.ts, self.id)
+    }
+}
+
+fn unix_timestamp_ms(dt: &time::OffsetDateTime) -> i64 {
+    (dt.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct EntryQuery {
+    /// A comma separated list of extra data to include with the entry.
+    ///
+    /// Currently the only supported value is `files`.
+    #[serde(default)]
+    include: Option<String>,
+}
+
+impl EntryQuery {
+    fn includes(&self, value: &str) -> bool {
+        self.include
+            .as_deref()
+            .is_some_and(|s| s.split(',').any(|s| s.trim() == value))
+    }
+}
+
+/// An entry with optionally included extra data.
+#[derive(Serialize, ToSchema)]
+pub struct EntryWithFiles {
+    #[serde(flatten)]
+    entry: DirectoryEntry,
+    /// The files of the entry. Only present if `include=files` is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<FileEntry>>,
+}
+
 /// Details
 ///
 /// Get the top level details of an entry by its ID.
@@ -26,14 +88,15 @@ use super::{
     get,
     path = "/api/entries/{id}",
     responses(
-        (status = 200, description = "Successfully retrieved entry", body = Entry),
+        (status = 200, description = "Successfully retrieved entry", body = EntryWithFiles),
         (status = 400, description = "Invalid ID given", body = ApiError),
         (status = 401, description = "User is unauthenticated", body = ApiError),
         (status = 404, description = "Entry not found", body = ApiError),
         (status = 429, response = RateLimitResponse),
     ),
     params(
-        ("id" = i64, Path, description = "The entry's ID")
+        ("id" = i64, Path, description = "The entry's ID"),
+        EntryQuery
     ),
     security(
         ("api_key" = [])
@@ -43,14 +106,74 @@ use super::{
 pub async fn get_entry_by_id(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-    _auth: ApiToken,
-) -> Result<Json<DirectoryEntry>, ApiError> {
-    match state.get_directory_entry(id).await {
-        Some(entry) => Ok(Json(entry)),
+    Query(query): Query<EntryQuery>,
+    auth: ApiToken,
+) -> Result<Json<EntryWithFiles>, ApiError> {
+    match state.get_visible_directory_entry(id, Some(auth.id)).await {
+        Some(entry) => {
+            let files = if query.includes("files") {
+                Some(get_file_entries(id, &entry.path)?)
+            } else {
+                None
+            };
+            Ok(Json(EntryWithFiles { entry, files }))
+        }
         None => Err(ApiError::not_found("This entry could not be found")),
     }
 }
 
+/// Relations
+///
+/// Get the entries that are related to an entry, as determined by the anime relations data.
+///
+/// If the entry is not backed by an AniList ID then this always returns an empty array.
+#[utoipa::path(
+    get,
+    path = "/api/entries/{id}/relations",
+    responses(
+        (status = 200, description = "Successful response", body = [Entry]),
+        (status = 400, description = "Invalid ID given", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 404, description = "Entry not found", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(
+        ("id" = i64, Path, description = "The entry's ID")
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn get_entry_relations(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    auth: ApiToken,
+) -> Result<Json<Vec<DirectoryEntry>>, ApiError> {
+    let Some(entry) = state.get_visible_directory_entry(id, Some(auth.id)).await else {
+        return Err(ApiError::not_found("This entry could not be found"));
+    };
+
+    let Some(anilist_id) = entry.anilist_id else {
+        return Ok(Json(Vec::new()));
+    };
+
+    let related = state.anime_relations().await.related(anilist_id);
+    if related.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let include_hidden = state.get_account(auth.id).await.is_some_and(|a| a.flags.is_editor());
+    let entries = state.directory_entries().await;
+    let result = entries
+        .iter()
+        .filter(|e| e.id != id && (include_hidden || !e.flags.is_hidden()))
+        .filter(|e| e.anilist_id.is_some_and(|a| related.contains(&a)))
+        .cloned()
+        .collect();
+    Ok(Json(result))
+}
+
 #[derive(Deserialize, IntoParams)]
 pub struct FilesQuery {
     /// Return files that match the given episode number.
@@ -60,6 +183,13 @@ pub struct FilesQuery {
     /// are movies.
     #[serde(default)]
     episode: Option<u16>,
+    /// Return files that have the given file extension, without the leading dot.
+    ///
+    /// Multiple formats can be given by separating them with a comma,
+    /// e.g. `ass,srt`. This is case-insensitive.
+    #[serde(deserialize_with = "crate::utils::empty_string_is_none")]
+    #[serde(default)]
+    format: Option<String>,
 }
 
 fn get_episode_range(filename: &str) -> Option<RelationRange> {
@@ -106,6 +236,18 @@ impl FilesQuery {
             });
         }
     }
+
+    fn filter_formats(&self, files: &mut Vec<FileEntry>) {
+        if let Some(formats) = self.format.as_deref() {
+            let formats = formats.split(',').map(|s| s.trim()).collect::<Vec<_>>();
+            files.retain(|f| {
+                std::path::Path::new(&f.name)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| formats.iter().any(|f| f.eq_ignore_ascii_case(ext)))
+            });
+        }
+    }
 }
 
 /// Files
@@ -134,11 +276,12 @@ pub async fn get_entry_files(
     State(state): State<AppState>,
     Path(id): Path<i64>,
     Query(query): Query<FilesQuery>,
-    _auth: ApiToken,
+    auth: ApiToken,
 ) -> Result<Json<Vec<FileEntry>>, ApiError> {
-    match state.get_directory_entry(id).await {
+    match state.get_visible_directory_entry(id, Some(auth.id)).await {
         Some(entry) => {
             let mut files = get_file_entries(id, &entry.path)?;
+            query.filter_formats(&mut files);
             if !entry.flags.is_movie() {
                 query.filter(&mut files, &entry, &state).await;
             }
@@ -152,11 +295,29 @@ pub async fn get_entry_files(
     }
 }
 
-#[derive(Default, Deserialize, IntoParams)]
+/// The order that search results are returned in.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// Sort by how well the entry matches the query, best match first.
+    ///
+    /// If no query is given then this is the same as `last_modified`.
+    #[default]
+    Score,
+    /// Sort by the entry's name, alphabetically.
+    Name,
+    /// Sort by when the entry was last modified, oldest first.
+    LastModified,
+    /// Sort by when the entry was last modified, newest first.
+    Recent,
+}
+
+#[derive(Default, Deserialize, IntoParams, ToSchema)]
 pub struct SearchQuery {
     /// Return entries that are anime.
     #[serde(default = "crate::utils::default_true")]
     #[param(default = true)]
+    #[schema(default = true)]
     pub anime: bool,
     /// Return the entry that has the given AniList ID.
     #[serde(default)]
@@ -166,6 +327,7 @@ pub struct SearchQuery {
     /// Check the documentation for TMDB ID encoding.
     #[serde(deserialize_with = "crate::utils::generic_empty_string_is_none")]
     #[param(pattern = r#"(tv|movie):(\d+)"#, value_type = Option<String>, example = "tv:12345")]
+    #[schema(pattern = r#"(tv|movie):(\d+)"#, value_type = Option<String>, example = "tv:12345")]
     #[serde(default)]
     pub tmdb_id: Option<tmdb::Id>,
     /// Return entries that match the given string.
@@ -183,6 +345,50 @@ pub struct SearchQuery {
     /// Return entries that are before this UNIX timestamp (in seconds).
     #[serde(default)]
     pub before: Option<i64>,
+
+    /// Return entries that are featured or not featured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub featured: Option<bool>,
+
+    /// Return entries that have the given tag.
+    #[serde(deserialize_with = "crate::utils::empty_string_is_none")]
+    #[serde(default)]
+    pub tag: Option<String>,
+
+    /// The order that the entries are returned in.
+    #[serde(default)]
+    #[param(inline)]
+    pub sort_by: SortBy,
+
+    /// Whether to reverse the order given by `sort_by`.
+    #[serde(default)]
+    pub sort_desc: bool,
+
+    /// The maximum number of entries to return.
+    ///
+    /// If not given then all matching entries are returned.
+    #[serde(default)]
+    pub limit: Option<usize>,
+
+    /// The number of matching entries to skip before returning results.
+    ///
+    /// This is mutually exclusive with `cursor`.
+    #[serde(default)]
+    pub offset: usize,
+
+    /// An opaque cursor to continue from, as returned by the `x-next-cursor` header.
+    ///
+    /// Only entries that were last modified before the cursor are returned.
+    /// Due to this, cursors can only be used when the most recently modified entries
+    /// come first, e.g. with the `recent` sort order. Other orders are rejected.
+    /// This is mutually exclusive with `offset`. If both are given then `cursor` takes precedence.
+    #[serde(deserialize_with = "crate::utils::generic_empty_string_is_none")]
+    #[serde(default)]
+    pub cursor: Option<String>,
+
+    /// Whether to return hidden entries. Only editors can see these.
+    #[serde(skip)]
+    pub include_hidden: bool,
 }
 
 impl SearchQuery {
@@ -195,6 +401,14 @@ impl SearchQuery {
         if let Some(target) = entry.japanese_name.as_deref() {
             max = max.max(sublime_fuzzy::best_match(query, target));
         }
+        // Allows romaji queries to match kana names
+        if let Some(target) = entry
+            .transliterated_japanese_name
+            .as_deref()
+            .filter(|_| query.is_ascii())
+        {
+            max = max.max(sublime_fuzzy::best_match(query, target));
+        }
         max
     }
 
@@ -203,6 +417,14 @@ impl SearchQuery {
             return None;
         }
 
+        if entry.flags.is_hidden() && !self.include_hidden {
+            return None;
+        }
+
+        if self.featured.is_some_and(|f| f != entry.flags.is_featured()) {
+            return None;
+        }
+
         if self.anilist_id.is_some() {
             return (self.anilist_id == entry.anilist_id).then_some(isize::MAX);
         }
@@ -230,6 +452,98 @@ impl SearchQuery {
 
         self.query.is_none().then_some(100)
     }
+
+    fn effective_sort_by(&self) -> SortBy {
+        match self.sort_by {
+            SortBy::Score if self.query.is_none() => SortBy::LastModified,
+            s => s,
+        }
+    }
+
+    /// Whether the most recently modified entries come first, which is the only order cursors work with.
+    fn is_newest_first(&self) -> bool {
+        match self.effective_sort_by() {
+            SortBy::Recent => !self.sort_desc,
+            SortBy::LastModified => self.sort_desc,
+            _ => false,
+        }
+    }
+
+    fn sort(&self, entries: &mut [(isize, DirectoryEntry)]) {
+        match self.effective_sort_by() {
+            SortBy::Score => entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score)),
+            SortBy::Name => entries.sort_by(|(_, a), (_, b)| a.sort_key.cmp(&b.sort_key)),
+            SortBy::LastModified => entries.sort_by_key(|(_, e)| (e.last_updated_at, e.id)),
+            SortBy::Recent => entries.sort_by_key(|(_, e)| std::cmp::Reverse((e.last_updated_at, e.id))),
+        }
+        if self.sort_desc {
+            entries.reverse();
+        }
+    }
+}
+
+/// An extended search query that is sent through a JSON body.
+#[derive(Deserialize, ToSchema)]
+pub struct SearchPayload {
+    #[serde(flatten)]
+    pub query: SearchQuery,
+    /// Do not return entries with these IDs.
+    #[serde(default)]
+    pub exclude_ids: Vec<i64>,
+}
+
+type SearchResponse = (HeaderMap, Json<Vec<DirectoryEntry>>);
+
+async fn search(
+    state: &AppState,
+    mut query: SearchQuery,
+    exclude_ids: &[i64],
+    auth: ApiToken,
+) -> Result<SearchResponse, ApiError> {
+    let cursor = match query.cursor.as_deref() {
+        Some(_) if !query.is_newest_first() => {
+            return Err(ApiError::new("Cursors can only be used with the recent sort order"))
+        }
+        Some(cursor) => match state.config().secret_key.verify::<SearchCursor>(cursor) {
+            Some(cursor) => Some(cursor),
+            None => return Err(ApiError::new("Invalid cursor given")),
+        },
+        None => None,
+    };
+    let tagged = match query.tag.clone() {
+        Some(tag) => Some(super::tags::get_tagged_entry_ids(state, tag).await?),
+        None => None,
+    };
+    query.include_hidden = state.get_account(auth.id).await.is_some_and(|a| a.flags.is_editor());
+    let entries = state.directory_entries().await;
+    let mut entries = entries
+        .iter()
+        .filter(|s| !exclude_ids.contains(&s.id))
+        .filter(|s| tagged.as_ref().is_none_or(|ids| ids.contains(&s.id)))
+        .filter_map(|s| query.apply(s).zip(Some(s.clone())))
+        .collect::<Vec<_>>();
+    query.sort(&mut entries);
+    let total = entries.len();
+    let offset = if cursor.is_some() { 0 } else { query.offset };
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let mut entries = entries
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .filter(|entry| cursor.is_none_or(|c| c.is_after(entry)))
+        .skip(offset);
+    let page = entries.by_ref().take(limit).collect::<Vec<_>>();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(X_TOTAL_COUNT, HeaderValue::from(total));
+    if query.is_newest_first() && entries.next().is_some() {
+        if let Some(last) = page.last() {
+            let next = state.config().secret_key.sign(&SearchCursor::new(last))?;
+            if let Ok(value) = HeaderValue::from_str(&next) {
+                headers.insert(X_NEXT_CURSOR, value);
+            }
+        }
+    }
+    Ok((headers, Json(page)))
 }
 
 /// Search
@@ -239,7 +553,11 @@ impl SearchQuery {
     get,
     path = "/api/entries/search",
     responses(
-        (status = 200, description = "Successful response", body = [Entry]),
+        (status = 200, description = "Successful response", body = [Entry], headers(
+            ("x-total-count" = usize, description = "The total number of matching entries, ignoring `limit` and `offset`"),
+            ("x-next-cursor" = String, description = "The cursor to pass to get the next page of results. Only present if there are more results and the most recently modified entries come first."),
+        )),
+        (status = 400, description = "Invalid cursor given or cursor used with an unsupported sort order", body = ApiError),
         (status = 401, description = "User is unauthenticated", body = ApiError),
         (status = 429, response = RateLimitResponse),
     ),
@@ -252,15 +570,265 @@ impl SearchQuery {
 pub async fn search_entries(
     State(state): State<AppState>,
     Query(query): Query<SearchQuery>,
-    _auth: ApiToken,
+    auth: ApiToken,
+) -> Result<SearchResponse, ApiError> {
+    search(&state, query, &[], auth).await
+}
+
+/// Search (JSON)
+///
+/// Returns all entries that meet a specific criteria.
+///
+/// This is the same as the `GET` variant except the query is
+/// sent as a JSON body, which allows for more complex queries.
+#[utoipa::path(
+    post,
+    path = "/api/entries/search",
+    request_body = SearchPayload,
+    responses(
+        (status = 200, description = "Successful response", body = [Entry], headers(
+            ("x-total-count" = usize, description = "The total number of matching entries, ignoring `limit` and `offset`"),
+            ("x-next-cursor" = String, description = "The cursor to pass to get the next page of results. Only present if there are more results and the most recently modified entries come first."),
+        )),
+        (status = 400, description = "Invalid cursor or body given", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn search_entries_with_body(
+    State(state): State<AppState>,
+    auth: ApiToken,
+    Json(payload): Json<SearchPayload>,
+) -> Result<SearchResponse, ApiError> {
+    search(&state, payload.query, &payload.exclude_ids, auth).await
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    50
+}
+
+/// The maximum number of entries that can be returned per page when listing entries.
+const MAX_ENTRIES_PER_PAGE: usize = 250;
+
+#[derive(Deserialize, IntoParams)]
+pub struct ListQuery {
+    /// Return entries that are anime.
+    #[serde(default = "crate::utils::default_true")]
+    #[param(default = true)]
+    anime: bool,
+    /// The page to return, starting from 1.
+    #[serde(default = "default_page")]
+    #[param(default = 1, minimum = 1)]
+    page: usize,
+    /// The number of entries per page.
+    #[serde(default = "default_per_page")]
+    #[param(default = 50, minimum = 1, maximum = 250)]
+    per_page: usize,
+}
+
+impl ListQuery {
+    fn link(&self, base: &str, page: usize, rel: &str) -> String {
+        format!(
+            "<{base}/api/entries?anime={}&page={page}&per_page={}>; rel=\"{rel}\"",
+            self.anime, self.per_page
+        )
+    }
+}
+
+/// List
+///
+/// Returns a page of all entries, sorted by when they were last modified with the most recent first.
+///
+/// Unlike search, this does not do any filtering or scoring.
+#[utoipa::path(
+    get,
+    path = "/api/entries",
+    responses(
+        (status = 200, description = "Successful response", body = [Entry], headers(
+            ("x-total-count" = usize, description = "The total number of entries"),
+            ("link" = String, description = "Links to the next and previous pages, if any, as described by RFC 5988"),
+        )),
+        (status = 400, description = "Invalid page given", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(ListQuery),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn list_entries(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+    auth: ApiToken,
+) -> Result<(HeaderMap, Json<Vec<DirectoryEntry>>), ApiError> {
+    if query.page == 0 {
+        return Err(ApiError::new("page must be at least 1"));
+    }
+
+    if query.per_page == 0 || query.per_page > MAX_ENTRIES_PER_PAGE {
+        return Err(ApiError::new(format!(
+            "per_page must be between 1 and {MAX_ENTRIES_PER_PAGE}"
+        )));
+    }
+
+    let include_hidden = state.get_account(auth.id).await.is_some_and(|a| a.flags.is_editor());
+    let entries = state.directory_entries().await;
+    let mut entries = entries
+        .iter()
+        .filter(|e| e.flags.is_anime() == query.anime && (include_hidden || !e.flags.is_hidden()))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|e| std::cmp::Reverse((e.last_updated_at, e.id)));
+
+    let total = entries.len();
+    let start = (query.page - 1).saturating_mul(query.per_page);
+    let page = entries
+        .into_iter()
+        .skip(start)
+        .take(query.per_page)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let base = state.config().canonical_url();
+    let mut links = Vec::new();
+    if start.saturating_add(query.per_page) < total {
+        links.push(query.link(&base, query.page + 1, "next"));
+    }
+    if query.page > 1 {
+        links.push(query.link(&base, query.page - 1, "prev"));
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(X_TOTAL_COUNT, HeaderValue::from(total));
+    if !links.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+            headers.insert(LINK, value);
+        }
+    }
+    Ok((headers, Json(page)))
+}
+
+fn default_recent_days() -> u8 {
+    7
+}
+
+/// The maximum number of days that can be requested for recent entries.
+const MAX_RECENT_DAYS: u8 = 30;
+
+/// The maximum number of recent entries returned.
+const MAX_RECENT_ENTRIES: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct RecentQuery {
+    /// Return entries that are anime.
+    #[serde(default = "crate::utils::default_true")]
+    #[param(default = true)]
+    anime: bool,
+    /// Return entries that were modified within this many days.
+    #[serde(default = "default_recent_days")]
+    #[param(default = 7, minimum = 1, maximum = 30)]
+    days: u8,
+}
+
+/// Recent
+///
+/// Returns up to 100 entries that were modified recently, with the most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/entries/recent",
+    responses(
+        (status = 200, description = "Successful response", body = [Entry]),
+        (status = 400, description = "Invalid number of days given", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(RecentQuery),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn recent_entries(
+    State(state): State<AppState>,
+    Query(query): Query<RecentQuery>,
+    auth: ApiToken,
 ) -> Result<Json<Vec<DirectoryEntry>>, ApiError> {
+    if query.days == 0 || query.days > MAX_RECENT_DAYS {
+        return Err(ApiError::new(format!("days must be between 1 and {MAX_RECENT_DAYS}")));
+    }
+
+    let include_hidden = state.get_account(auth.id).await.is_some_and(|a| a.flags.is_editor());
+    let cutoff = time::OffsetDateTime::now_utc() - time::Duration::days(query.days as i64);
     let entries = state.directory_entries().await;
     let mut entries = entries
         .iter()
-        .filter_map(|s| query.apply(s).zip(Some(s.clone())))
+        .filter(|e| {
+            e.flags.is_anime() == query.anime && (include_hidden || !e.flags.is_hidden()) && e.last_updated_at >= cutoff
+        })
+        .cloned()
         .collect::<Vec<_>>();
-    entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
-    Ok(Json(entries.into_iter().map(|(_, entry)| entry).collect()))
+    entries.sort_by_key(|e| std::cmp::Reverse((e.last_updated_at, e.id)));
+    entries.truncate(MAX_RECENT_ENTRIES);
+    Ok(Json(entries))
+}
+
+fn default_popular_limit() -> usize {
+    20
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct PopularQuery {
+    /// Return entries that are anime.
+    #[serde(default = "crate::utils::default_true")]
+    #[param(default = true)]
+    anime: bool,
+    /// The maximum number of entries to return.
+    #[serde(default = "default_popular_limit")]
+    #[param(default = 20, minimum = 1, maximum = 100)]
+    limit: usize,
+}
+
+/// Popular
+///
+/// Returns the entries with the most downloads, with the most downloaded first.
+#[utoipa::path(
+    get,
+    path = "/api/entries/popular",
+    responses(
+        (status = 200, description = "Successful response", body = [Entry]),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+        (status = 501, description = "Download tracking is not enabled", body = ApiError),
+    ),
+    params(PopularQuery),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn popular_entries(
+    State(state): State<AppState>,
+    Query(query): Query<PopularQuery>,
+    _auth: ApiToken,
+) -> Result<Response, ApiError> {
+    let limit = query.limit.clamp(1, MAX_POPULAR_ENTRIES);
+    match state.popular_entries(query.anime).await? {
+        Some(entries) => Ok(Json(entries.iter().take(limit).cloned().collect::<Vec<_>>()).into_response()),
+        None => Ok((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(ApiError::new("download tracking not enabled").with_code(ApiErrorCode::ServerError)),
+        )
+            .into_response()),
+    }
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -306,6 +874,8 @@ pub struct CreatePayload {
     english_name: Option<String>,
     /// Create an entry with the given flags.
     ///
+    /// The `featured` and `hidden` flags are ignored, they can only be set by editing the entry.
+    ///
     /// This is only available for API keys bound to editor users.
     #[serde(default, with = "crate::models::expand_flags::option")]
     flags: Option<EntryFlags>,
@@ -463,3 +1033,224 @@ pub async fn upload_files(
     }
     Ok(Json(result))
 }
+
+#[derive(Deserialize, IntoParams)]
+pub struct DeleteFileQuery {
+    /// The reason for deleting the file.
+    ///
+    /// This is required unless the API key belongs to an admin.
+    #[serde(deserialize_with = "crate::utils::empty_string_is_none")]
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Delete File
+///
+/// Deletes a single file from an entry.
+///
+/// This requires an editor API key.
+#[utoipa::path(
+    delete,
+    path = "/api/entries/{id}/files/{filename}",
+    responses(
+        (status = 204, description = "File deleted"),
+        (status = 400, description = "An error occurred", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 403, description = "The user does not have permission to do this", body = ApiError),
+        (status = 404, description = "Entry or file not found", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(
+        ("id" = i64, Path, description = "The entry's ID"),
+        ("filename" = String, Path, description = "The file's name"),
+        DeleteFileQuery
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn delete_file(
+    State(state): State<AppState>,
+    Path((entry_id, filename)): Path<(i64, String)>,
+    Query(query): Query<DeleteFileQuery>,
+    auth: ApiToken,
+) -> Result<StatusCode, ApiError> {
+    let Some(account) = state.get_account(auth.id).await else {
+        return Err(ApiError::unauthorized());
+    };
+
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    if !account.flags.is_admin() && query.reason.is_none() {
+        return Err(ApiError::new("Reason must be provided"));
+    }
+
+    if query.reason.as_ref().is_some_and(|r| r.len() > 512) {
+        return Err(ApiError::new("Reason can only be up to 512 characters long"));
+    }
+
+    let Some(entry) = state.get_directory_entry_path(entry_id).await else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let Some(path) = crate::download::join_path(&entry, &filename) else {
+        return Err(ApiError::new("Invalid filename given"));
+    };
+
+    if !path.is_file() {
+        return Err(ApiError::not_found("File not found"));
+    }
+
+    let result = if account.flags.is_admin() {
+        tokio::fs::remove_file(path).await
+    } else {
+        let trash = crate::trash::Trash::new()?;
+        trash.put(path, entry_id, query.reason.clone()).await
+    };
+
+    let mut audit_data = audit::DeleteFiles {
+        permanent: account.flags.is_admin(),
+        files: Vec::with_capacity(1),
+        reason: query.reason.clone(),
+    };
+    audit_data.add_file(filename.clone(), result.is_err());
+    if result.is_ok() {
+        let _ = state
+            .database()
+            .execute(
+                "UPDATE directory_entry SET file_count = MAX(file_count - 1, 0) WHERE id = ?",
+                [entry_id],
+            )
+            .await;
+        state.cached_directories().invalidate();
+    }
+    state
+        .audit(audit::AuditLogEntry::full(audit_data, entry_id, account.id))
+        .await;
+    state.send_alert(
+        crate::discord::Alert::error("Deleted Files")
+            .url(format!("/logs?entry_id={entry_id}"))
+            .description(format!("- {filename}"))
+            .account(account)
+            .field("Reason", query.reason.as_deref().unwrap_or("None"))
+            .field("Total", 1)
+            .field("Failed", if result.is_err() { 1 } else { 0 }),
+    );
+    result?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CopyFilePayload {
+    /// The ID of the entry to copy the file to.
+    target_entry_id: i64,
+}
+
+/// Copy File
+///
+/// Copies a single file from an entry to another entry.
+///
+/// This requires an editor API key.
+#[utoipa::path(
+    post,
+    path = "/api/entries/{id}/files/{filename}/copy",
+    request_body = CopyFilePayload,
+    responses(
+        (status = 204, description = "File copied"),
+        (status = 400, description = "An error occurred", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 403, description = "The user does not have permission to do this", body = ApiError),
+        (status = 404, description = "Entry or file not found", body = ApiError),
+        (status = 409, description = "The file already exists in the target entry", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(
+        ("id" = i64, Path, description = "The entry's ID"),
+        ("filename" = String, Path, description = "The file's name"),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn copy_file(
+    State(state): State<AppState>,
+    Path((entry_id, filename)): Path<(i64, String)>,
+    auth: ApiToken,
+    Json(payload): Json<CopyFilePayload>,
+) -> Result<StatusCode, ApiError> {
+    let Some(account) = state.get_account(auth.id).await else {
+        return Err(ApiError::unauthorized());
+    };
+
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let target_id = payload.target_entry_id;
+    if target_id == entry_id {
+        return Err(ApiError::new("Cannot copy a file to the same entry"));
+    }
+
+    let Some(entry) = state.get_directory_entry_path(entry_id).await else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let Some(target) = state.get_directory_entry_path(target_id).await else {
+        return Err(ApiError::not_found("Target entry not found"));
+    };
+
+    let (Some(source), Some(destination)) = (
+        crate::download::validate_path(&entry, &filename),
+        crate::download::join_path(&target, &filename),
+    ) else {
+        return Err(ApiError::new("Invalid filename given"));
+    };
+
+    if !source.is_file() {
+        return Err(ApiError::not_found("File not found"));
+    }
+
+    if destination.exists() {
+        return Err(ApiError::new("File already exists in the target entry").with_code(ApiErrorCode::FileAlreadyExists));
+    }
+
+    let result = tokio::fs::copy(source, destination).await;
+    let mut data = audit::Upload {
+        files: Vec::with_capacity(1),
+        api: true,
+    };
+    data.add_file(filename.clone(), result.is_err());
+    state
+        .audit(audit::AuditLogEntry::full(data, target_id, account.id))
+        .await;
+    result?;
+
+    let _ = state
+        .database()
+        .execute(
+            "UPDATE directory_entry SET last_updated_at = CURRENT_TIMESTAMP WHERE id IN (?, ?)",
+            (entry_id, target_id),
+        )
+        .await;
+    let _ = state
+        .database()
+        .execute(
+            "UPDATE directory_entry SET file_count = file_count + 1 WHERE id = ?",
+            [target_id],
+        )
+        .await;
+    state.cached_directories().invalidate();
+    state.send_alert(
+        crate::discord::Alert::info("Copied File")
+            .url(format!("/logs?entry_id={target_id}"))
+            .description(format!("- {filename}"))
+            .account(account)
+            .field("From", format!("[{entry_id}](/entry/{entry_id})"))
+            .field("To", format!("[{target_id}](/entry/{target_id})")),
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,18 +1,30 @@
 mod auth;
 mod entries;
+mod reports;
+mod tags;
 pub mod utils;
 
-use crate::{filters, models::Account, ratelimit::RateLimit, AppState};
+use std::sync::OnceLock;
+
+use crate::{
+    filters,
+    key::to_hex,
+    models::Account,
+    ratelimit::{ApiKeyExtractor, RateLimit},
+    AppState,
+};
 use askama::Template;
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::{
-        header::{AUTHORIZATION, USER_AGENT},
-        Method,
+        header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK, USER_AGENT},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
     },
-    routing::{get, post},
-    Json, Router,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Router,
 };
+use sha2::{Digest, Sha256};
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use utoipa::{
     openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
@@ -33,15 +45,35 @@ pub use entries::SearchQuery;
     paths(
         entries::get_entry_by_id,
         entries::get_entry_files,
+        entries::get_entry_relations,
         entries::search_entries,
+        entries::search_entries_with_body,
+        entries::list_entries,
+        entries::recent_entries,
+        entries::popular_entries,
         entries::create_entry,
         entries::upload_files,
+        entries::delete_file,
+        entries::copy_file,
+        tags::list_tags,
+        tags::add_tags,
+        tags::remove_tag,
+        reports::create_report,
     ),
     components(
         schemas(
             ApiError,
             crate::models::EntryFlags,
             crate::models::DirectoryEntry,
+            entries::SortBy,
+            entries::SearchQuery,
+            entries::SearchPayload,
+            entries::EntryWithFiles,
+            entries::CopyFilePayload,
+            tags::TagCount,
+            tags::AddTagsPayload,
+            reports::CreateReportPayload,
+            reports::CreatedReport,
             crate::routes::entry::FileEntry,
             crate::routes::entry::UploadResult,
         ),
@@ -71,10 +103,67 @@ impl Modify for RequiredAuthentication {
 #[template(path = "api.html")]
 struct ApiDocumentation {
     api_key: String,
+    spec_url: String,
+    url: String,
+}
+
+/// The serialized OpenAPI spec along with its versioned file name and ETag.
+struct SerializedSpec {
+    json: String,
+    /// The file name the spec is served under, e.g. `vbeta-0123456789abcdef.json`.
+    file: String,
+    etag: HeaderValue,
+}
+
+fn serialized_spec() -> &'static SerializedSpec {
+    static SPEC: OnceLock<SerializedSpec> = OnceLock::new();
+    SPEC.get_or_init(|| {
+        let openapi = Schema::openapi();
+        let json = openapi.to_json().expect("OpenAPI spec should serialize");
+        let digest = to_hex(&Sha256::digest(json.as_bytes()));
+        let etag = format!("\"sha256:{digest}\"");
+        SerializedSpec {
+            file: format!("v{}-{}.json", openapi.info.version, &digest[..16]),
+            etag: HeaderValue::from_str(&etag).expect("ETag should be a valid header value"),
+            json,
+        }
+    })
+}
+
+fn spec_response(headers: &HeaderMap, cache_control: &'static str) -> Response {
+    let spec = serialized_spec();
+    let cache_control = HeaderValue::from_static(cache_control);
+    if headers.get(IF_NONE_MATCH).is_some_and(|tag| *tag == spec.etag) {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(CACHE_CONTROL, cache_control), (ETAG, spec.etag.clone())],
+        )
+            .into_response();
+    }
+    (
+        [
+            (CONTENT_TYPE, HeaderValue::from_static("application/json")),
+            (CACHE_CONTROL, cache_control),
+            (ETAG, spec.etag.clone()),
+        ],
+        spec.json.clone(),
+    )
+        .into_response()
 }
 
-async fn spec() -> Json<utoipa::openapi::OpenApi> {
-    Json(Schema::openapi())
+async fn spec(headers: HeaderMap) -> Response {
+    spec_response(&headers, "public, max-age=3600")
+}
+
+/// Serves the spec under `/openapi/v<version>-<hash>.json`.
+///
+/// The hash is taken from the spec's contents, so the URL changes whenever the spec does
+/// and it can be cached indefinitely.
+async fn versioned_spec(Path(file): Path<String>, headers: HeaderMap) -> Response {
+    if file != serialized_spec().file {
+        return ApiError::not_found("Unknown OpenAPI spec version").into_response();
+    }
+    spec_response(&headers, "public, max-age=31536000, immutable")
 }
 
 async fn docs(State(state): State<AppState>, account: Option<Account>) -> ApiDocumentation {
@@ -83,24 +172,51 @@ async fn docs(State(state): State<AppState>, account: Option<Account>) -> ApiDoc
     } else {
         String::new()
     };
-    ApiDocumentation { api_key }
+    let spec_url = format!("/api/openapi/{}", serialized_spec().file);
+    ApiDocumentation {
+        api_key,
+        spec_url,
+        url: state.config().canonical_url(),
+    }
 }
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/openapi.json", get(spec))
+        .route("/openapi/:file", get(versioned_spec))
         .route("/docs", get(docs))
+        .route("/entries/recent", get(entries::recent_entries))
+        .route("/entries/popular", get(entries::popular_entries))
         .route("/entries/:id", get(entries::get_entry_by_id))
         .route("/entries/:id/files", get(entries::get_entry_files))
-        .route("/entries/search", get(entries::search_entries))
-        .route("/entries", post(entries::create_entry))
+        .route("/entries/:id/relations", get(entries::get_entry_relations))
+        .route(
+            "/entries/search",
+            get(entries::search_entries).post(entries::search_entries_with_body),
+        )
+        .route("/entries", get(entries::list_entries).post(entries::create_entry))
         .route("/entries/:id/upload", post(entries::upload_files))
+        .route("/entries/:id/files/:filename", delete(entries::delete_file))
+        .route("/entries/:id/files/:filename/copy", post(entries::copy_file))
+        .route("/entries/:id/tags", post(tags::add_tags))
+        .route("/entries/:id/tags/:tag", delete(tags::remove_tag))
+        .route("/tags", get(tags::list_tags))
+        .route(
+            "/reports",
+            post(reports::create_report)
+                .layer(RateLimit::default().extractor(ApiKeyExtractor).quota(5, 3600.0).build()),
+        )
         .route_layer(RateLimit::default().quota(25, 60.0).build())
         .route_layer(
             CorsLayer::new()
-                .allow_methods([Method::GET, Method::POST])
+                .allow_methods([Method::GET, Method::POST, Method::DELETE])
                 .allow_credentials(true)
                 .allow_origin(AllowOrigin::mirror_request())
-                .allow_headers([AUTHORIZATION, USER_AGENT]),
+                .allow_headers([AUTHORIZATION, USER_AGENT])
+                .expose_headers([
+                    HeaderName::from_static("x-total-count"),
+                    HeaderName::from_static("x-next-cursor"),
+                    LINK,
+                ]),
         )
 }
@@ -0,0 +1,75 @@
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    error::ApiError,
+    routes::entry::{raw_report_entry, ReportPayload},
+    AppState,
+};
+
+use super::{
+    auth::ApiToken,
+    utils::{ApiJson as Json, RateLimitResponse},
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateReportPayload {
+    /// The ID of the entry being reported.
+    entry_id: i64,
+    /// The files being reported. If not given then the whole entry is reported.
+    #[serde(default)]
+    files: Vec<String>,
+    /// The reason for the report. This can be up to 512 characters long.
+    reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedReport {
+    /// The ID of the report that was created.
+    id: i64,
+}
+
+/// Report
+///
+/// Reports an entry, or specific files in an entry, to the editors.
+///
+/// This is limited to 5 reports per hour.
+#[utoipa::path(
+    post,
+    path = "/api/reports",
+    request_body = CreateReportPayload,
+    responses(
+        (status = 201, description = "Report created", body = CreatedReport),
+        (status = 400, description = "An error occurred", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 403, description = "The user does not have permission to do this", body = ApiError),
+        (status = 404, description = "Entry not found", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn create_report(
+    State(state): State<AppState>,
+    auth: ApiToken,
+    Json(payload): Json<CreateReportPayload>,
+) -> Result<(StatusCode, Json<CreatedReport>), ApiError> {
+    let Some(account) = state.get_account(auth.id).await else {
+        return Err(ApiError::unauthorized());
+    };
+
+    let report = raw_report_entry(
+        &state,
+        payload.entry_id,
+        account,
+        ReportPayload {
+            files: payload.files,
+            reason: payload.reason,
+        },
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(CreatedReport { id: report.id })))
+}
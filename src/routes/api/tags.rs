@@ -0,0 +1,249 @@
+use axum::{extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{audit, error::ApiError, models::EntryFlags, AppState};
+
+use super::{
+    auth::ApiToken,
+    utils::{ApiJson as Json, ApiPath as Path, RateLimitResponse},
+};
+
+/// The maximum length of a tag, in bytes.
+const MAX_TAG_LENGTH: usize = 32;
+
+/// Returns `true` if the tag only has lowercase alphanumeric characters or hyphens.
+pub fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= MAX_TAG_LENGTH
+        && tag
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Returns the tags of an entry, sorted alphabetically.
+async fn get_entry_tags(state: &AppState, entry_id: i64) -> rusqlite::Result<Vec<String>> {
+    state
+        .database()
+        .call(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT tag FROM entry_tag WHERE entry_id = ? ORDER BY tag")?;
+            let rows = stmt.query_map([entry_id], |row| row.get(0))?;
+            rows.collect()
+        })
+        .await
+}
+
+/// Returns the IDs of the entries that have the given tag.
+pub async fn get_tagged_entry_ids(state: &AppState, tag: String) -> rusqlite::Result<Vec<i64>> {
+    state
+        .database()
+        .call(move |conn| {
+            let mut stmt = conn.prepare_cached("SELECT entry_id FROM entry_tag WHERE tag = ?")?;
+            let rows = stmt.query_map([tag], |row| row.get(0))?;
+            rows.collect()
+        })
+        .await
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TagCount {
+    /// The tag's name.
+    tag: String,
+    /// The number of entries that have this tag.
+    count: u64,
+}
+
+/// List Tags
+///
+/// Returns every tag that is in use, along with the number of entries that have it.
+/// Hidden entries are not counted.
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    responses(
+        (status = 200, description = "Successful response", body = [TagCount]),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn list_tags(State(state): State<AppState>, _auth: ApiToken) -> Result<Json<Vec<TagCount>>, ApiError> {
+    let tags = state
+        .database()
+        .call(|conn| -> rusqlite::Result<Vec<TagCount>> {
+            let mut stmt = conn.prepare_cached(
+                r#"
+                SELECT entry_tag.tag, COUNT(*) AS count
+                FROM entry_tag
+                INNER JOIN directory_entry ON directory_entry.id = entry_tag.entry_id
+                WHERE directory_entry.flags & ? = 0
+                GROUP BY entry_tag.tag
+                ORDER BY count DESC, entry_tag.tag
+                "#,
+            )?;
+            let rows = stmt.query_map([EntryFlags::HIDDEN], |row| {
+                Ok(TagCount {
+                    tag: row.get("tag")?,
+                    count: row.get("count")?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await?;
+    Ok(Json(tags))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddTagsPayload {
+    /// The tags to add to the entry.
+    ///
+    /// Tags must be lowercase alphanumeric characters or hyphens and
+    /// at most 32 characters long.
+    tags: Vec<String>,
+}
+
+/// Add Tags
+///
+/// Adds tags to an entry. Tags the entry already has are replaced, which makes the
+/// editor the one that added them.
+///
+/// This requires an editor API key. The response is every tag the entry has.
+#[utoipa::path(
+    post,
+    path = "/api/entries/{id}/tags",
+    request_body = AddTagsPayload,
+    responses(
+        (status = 200, description = "Tags added", body = [String]),
+        (status = 400, description = "An invalid tag was given", body = ApiError),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 403, description = "The user does not have permission to do this", body = ApiError),
+        (status = 404, description = "Entry not found", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(
+        ("id" = i64, Path, description = "The entry's ID")
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn add_tags(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+    auth: ApiToken,
+    Json(payload): Json<AddTagsPayload>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let Some(account) = state.get_account(auth.id).await else {
+        return Err(ApiError::unauthorized());
+    };
+
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    if let Some(tag) = payload.tags.iter().find(|t| !is_valid_tag(t)) {
+        return Err(ApiError::new(format!("Invalid tag: {tag}")));
+    }
+
+    if state.get_directory_entry(entry_id).await.is_none() {
+        return Err(ApiError::not_found("Entry not found"));
+    }
+
+    let account_id = account.id;
+    let added = state
+        .database()
+        .call(move |conn| -> rusqlite::Result<Vec<String>> {
+            let tx = conn.transaction()?;
+            let mut added = Vec::new();
+            {
+                let mut exists = tx.prepare_cached("SELECT 1 FROM entry_tag WHERE entry_id = ? AND tag = ?")?;
+                let mut stmt =
+                    tx.prepare_cached("INSERT OR REPLACE INTO entry_tag(entry_id, tag, created_by) VALUES (?, ?, ?)")?;
+                for tag in payload.tags {
+                    let is_new = !exists.exists((entry_id, &tag))?;
+                    stmt.execute((entry_id, &tag, account_id))?;
+                    if is_new {
+                        added.push(tag);
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(added)
+        })
+        .await?;
+
+    if !added.is_empty() {
+        let data = audit::EditTags {
+            added,
+            removed: Vec::new(),
+        };
+        state
+            .audit(audit::AuditLogEntry::full(data, entry_id, account_id))
+            .await;
+    }
+
+    Ok(Json(get_entry_tags(&state, entry_id).await?))
+}
+
+/// Remove Tag
+///
+/// Removes a tag from an entry.
+///
+/// This requires an editor API key.
+#[utoipa::path(
+    delete,
+    path = "/api/entries/{id}/tags/{tag}",
+    responses(
+        (status = 204, description = "Tag removed"),
+        (status = 401, description = "User is unauthenticated", body = ApiError),
+        (status = 403, description = "The user does not have permission to do this", body = ApiError),
+        (status = 404, description = "Entry does not have this tag", body = ApiError),
+        (status = 429, response = RateLimitResponse),
+    ),
+    params(
+        ("id" = i64, Path, description = "The entry's ID"),
+        ("tag" = String, Path, description = "The tag to remove")
+    ),
+    security(
+        ("api_key" = [])
+    ),
+    tag = "entries"
+)]
+pub async fn remove_tag(
+    State(state): State<AppState>,
+    Path((entry_id, tag)): Path<(i64, String)>,
+    auth: ApiToken,
+) -> Result<StatusCode, ApiError> {
+    let Some(account) = state.get_account(auth.id).await else {
+        return Err(ApiError::unauthorized());
+    };
+
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let deleted = state
+        .database()
+        .execute(
+            "DELETE FROM entry_tag WHERE entry_id = ? AND tag = ?",
+            (entry_id, tag.clone()),
+        )
+        .await?;
+
+    if deleted == 0 {
+        return Err(ApiError::not_found("Entry does not have this tag"));
+    }
+
+    let data = audit::EditTags {
+        added: Vec::new(),
+        removed: vec![tag],
+    };
+    state
+        .audit(audit::AuditLogEntry::full(data, entry_id, account.id))
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use askama::Template;
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
     response::Redirect,
     routing::get,
     Json, Router,
@@ -10,13 +11,18 @@ use axum::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    audit::{AuditLogData, AuditLogEntry},
+    audit::{AuditLogData, AuditLogEntry, FieldDiff},
     database::Table,
     error::ApiError,
     models::{Account, DirectoryEntry},
     AppState,
 };
 
+/// The number of audit log entries returned per page.
+const AUDIT_LOG_PAGE_SIZE: usize = 50;
+
+const X_NEXT_CURSOR: HeaderName = HeaderName::from_static("x-next-cursor");
+
 #[derive(Debug, Serialize)]
 struct EntryTitles {
     name: String,
@@ -24,6 +30,8 @@ struct EntryTitles {
     english_name: Option<String>,
 }
 
+/// Since audit log IDs are millisecond timestamps, `before` and `after` double as
+/// keyset pagination cursors.
 #[derive(Debug, Deserialize)]
 struct AuditLogQuery {
     #[serde(default)]
@@ -34,27 +42,34 @@ struct AuditLogQuery {
     before: Option<i64>,
     #[serde(default)]
     after: Option<i64>,
+    /// The type of audit log data, e.g. `restore_files`.
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
 }
 
 impl AuditLogQuery {
-    fn to_sql(&self) -> (String, Vec<i64>) {
+    fn to_sql(&self) -> (String, Vec<rusqlite::types::Value>) {
         let mut filters = Vec::new();
         let mut params = Vec::new();
         if let Some(entry_id) = self.entry_id {
             filters.push("audit_log.entry_id = ?");
-            params.push(entry_id);
+            params.push(entry_id.into());
         }
         if let Some(account_id) = self.account_id {
             filters.push("audit_log.account_id = ?");
-            params.push(account_id);
+            params.push(account_id.into());
         }
         if let Some(before) = self.before {
             filters.push("audit_log.id < ?");
-            params.push(before);
+            params.push(before.into());
         }
         if let Some(after) = self.after {
             filters.push("audit_log.id > ?");
-            params.push(after);
+            params.push(after.into());
+        }
+        if let Some(kind) = &self.kind {
+            filters.push("json_extract(audit_log.data, '$.type') = ?");
+            params.push(kind.clone().into());
         }
 
         if filters.is_empty() {
@@ -76,7 +91,7 @@ async fn get_audit_logs(
     State(state): State<AppState>,
     Query(query): Query<AuditLogQuery>,
     account: Account,
-) -> Result<Json<AuditLogResult>, ApiError> {
+) -> Result<(HeaderMap, Json<AuditLogResult>), ApiError> {
     if !account.flags.is_editor() {
         return Err(ApiError::forbidden());
     }
@@ -98,7 +113,11 @@ async fn get_audit_logs(
         query.push_str("WHERE ");
         query.push_str(&filter);
     }
-    query.push_str("ORDER BY audit_log.id DESC LIMIT 100");
+    // One extra row is fetched to know whether there is a next page
+    query.push_str(&format!(
+        " ORDER BY audit_log.id DESC LIMIT {}",
+        AUDIT_LOG_PAGE_SIZE + 1
+    ));
 
     let mut result = state
         .database()
@@ -125,6 +144,14 @@ async fn get_audit_logs(
         })
         .await?;
 
+    let mut headers = HeaderMap::new();
+    if result.logs.len() > AUDIT_LOG_PAGE_SIZE {
+        result.logs.truncate(AUDIT_LOG_PAGE_SIZE);
+        if let Some(last) = result.logs.last() {
+            headers.insert(X_NEXT_CURSOR, HeaderValue::from(last.id));
+        }
+    }
+
     // Check what requires backfilling
     let mut backfilled_ids = Vec::new();
     for entry in result.logs.iter() {
@@ -163,7 +190,31 @@ async fn get_audit_logs(
         }
     }
 
-    Ok(Json(result))
+    Ok((headers, Json(result)))
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct EditDiff {
+    changes: Vec<FieldDiff>,
+}
+
+pub(super) async fn get_audit_log_diff(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    account: Account,
+) -> Result<Json<EditDiff>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let Some(entry) = state.database().get_by_id::<AuditLogEntry>(id).await? else {
+        return Err(ApiError::not_found("Audit log entry not found."));
+    };
+
+    match entry.data {
+        AuditLogData::EditEntry(edit) => Ok(Json(EditDiff { changes: edit.diff()? })),
+        _ => Err(ApiError::new("Audit log entry is not an entry edit")),
+    }
 }
 
 #[derive(Template)]
@@ -1,6 +1,9 @@
 use crate::{
+    audit,
     auth::{hash_password, validate_password},
-    database::is_unique_constraint_violation,
+    config::RegistrationMode,
+    database::{is_unique_constraint_violation, Table},
+    email::{generate_verification_code, hash_email, is_valid_email, send_email, VERIFICATION_CODE_EXPIRY_MINUTES},
     error::{ApiError, ApiErrorCode},
     filters,
     flash::{FlashMessage, Flasher, Flashes},
@@ -15,27 +18,42 @@ use crate::{
 };
 use askama::Template;
 use axum::{
-    extract::{Path, State},
-    http::{header::SET_COOKIE, HeaderValue, StatusCode},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{
+        header::{RETRY_AFTER, SET_COOKIE},
+        HeaderValue, StatusCode,
+    },
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Json, Router,
 };
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use cookie::Cookie;
+use hmac::Mac;
+use rusqlite::OptionalExtension;
+use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use time::OffsetDateTime;
 
 #[derive(Template)]
 #[template(path = "login.html")]
 struct LoginTemplate {
     account: Option<Account>,
     flashes: Flashes,
+    registration_mode: RegistrationMode,
 }
 
-async fn login(account: Option<Account>, flashes: Flashes) -> Response {
+async fn login(State(state): State<AppState>, account: Option<Account>, flashes: Flashes) -> Response {
     if account.is_some() {
         Redirect::to("/").into_response()
     } else {
-        LoginTemplate { account, flashes }.into_response()
+        LoginTemplate {
+            account,
+            flashes,
+            registration_mode: state.config().registration_mode,
+        }
+        .into_response()
     }
 }
 
@@ -65,6 +83,8 @@ struct Credentials {
     password: String,
     #[serde(deserialize_with = "crate::utils::empty_string_is_none")]
     session_description: Option<String>,
+    #[serde(default, deserialize_with = "crate::utils::empty_string_is_none")]
+    invite_code: Option<String>,
     action: AuthenticationAction,
 }
 
@@ -89,14 +109,54 @@ async fn register(state: &AppState, token: &Option<Token>, credentials: Credenti
         return Err(ApiError::new("password length must be 8 to 128 characters"));
     }
 
-    let password_hash = hash_password(&credentials.password)?;
-    let result: rusqlite::Result<Option<Account>> = state
-        .database()
-        .get(
-            "INSERT INTO account(name, password) VALUES (?, ?) RETURNING *",
-            [credentials.username, password_hash],
-        )
-        .await;
+    let password_hash = hash_password(&credentials.password, &state.config().argon2_params)?;
+    let result: rusqlite::Result<Option<Account>> = match state.config().registration_mode {
+        RegistrationMode::Open => {
+            state
+                .database()
+                .get(
+                    "INSERT INTO account(name, password) VALUES (?, ?) RETURNING *",
+                    [credentials.username, password_hash],
+                )
+                .await
+        }
+        RegistrationMode::InviteOnly => {
+            let Some(code) = credentials.invite_code else {
+                return Err(ApiError::new("an invite code is required to register"));
+            };
+            let username = credentials.username;
+            // The account creation and marking the invite as used has to be atomic
+            // `None` here means the invite code was invalid, so the transaction is rolled back
+            let result = state
+                .database()
+                .call(move |conn| -> rusqlite::Result<Option<Account>> {
+                    let tx = conn.transaction()?;
+                    let account = tx.query_row(
+                        "INSERT INTO account(name, password) VALUES (?, ?) RETURNING *",
+                        [username, password_hash],
+                        Account::from_row,
+                    )?;
+                    let now = OffsetDateTime::now_utc().unix_timestamp();
+                    let updated = tx.execute(
+                        r#"UPDATE invitation SET used_by = ?
+                           WHERE code = ? AND used_by IS NULL AND (expires_at IS NULL OR expires_at > ?)"#,
+                        (account.id, code, now),
+                    )?;
+                    if updated == 0 {
+                        return Ok(None);
+                    }
+                    tx.commit()?;
+                    Ok(Some(account))
+                })
+                .await;
+
+            match result {
+                Ok(None) => return Err(ApiError::new("invalid or expired invite code")),
+                r => r,
+            }
+        }
+        RegistrationMode::Closed => return Err(ApiError::forbidden()),
+    };
 
     match result {
         Ok(Some(account)) => {
@@ -119,7 +179,35 @@ async fn register(state: &AppState, token: &Option<Token>, credentials: Credenti
     }
 }
 
-async fn authenticate(state: &AppState, credentials: Credentials) -> Result<Response, ApiError> {
+async fn audit_failed_login(state: &AppState, account_id: Option<i64>, reason: &str) {
+    let mut entry = audit::AuditLogEntry::new(audit::LoginFailed {
+        reason: reason.to_owned(),
+    });
+    entry.account_id = account_id;
+    state.audit(entry).await;
+}
+
+/// Re-hashes a password that was hashed with outdated Argon2 parameters.
+///
+/// Failures are only logged since the login itself already succeeded.
+async fn upgrade_password_hash(state: &AppState, account_id: i64, password: &str) {
+    let hash = match hash_password(password, &state.config().argon2_params) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::warn!(error = %e, account_id, "could not re-hash password");
+            return;
+        }
+    };
+    if let Err(e) = state
+        .database()
+        .execute("UPDATE account SET password = ? WHERE id = ?", (hash, account_id))
+        .await
+    {
+        tracing::warn!(error = %e, account_id, "could not update re-hashed password");
+    }
+}
+
+async fn authenticate(state: &AppState, credentials: Credentials, ip: IpAddr) -> Result<Response, ApiError> {
     if !is_valid_username(&credentials.username) {
         return Err(ApiError::new("invalid username given"));
     }
@@ -139,18 +227,40 @@ async fn authenticate(state: &AppState, credentials: Credentials) -> Result<Resp
         .map(|a| &a.password)
         .unwrap_or(&state.incorrect_default_password_hash);
 
-    if validate_password(&credentials.password, hash).is_ok() {
+    if let Ok(status) = validate_password(&credentials.password, hash, &state.config().argon2_params) {
         match account {
             Some(acc) => {
+                if let Some(ban) = state.get_active_ban(acc.id).await {
+                    audit_failed_login(state, Some(acc.id), "banned").await;
+                    return Err(ApiError::banned(&ban));
+                }
+                if status.is_outdated() {
+                    upgrade_password_hash(state, acc.id, &credentials.password).await;
+                }
                 state.invalidate_account_cache(acc.id);
                 let token = Token::new(acc.id)?;
                 let cookie = token.to_cookie(&state.config().secret_key);
                 state.save_session(&token, credentials.session_description).await;
+                let data = audit::Login {
+                    api_key: false,
+                    ip: Some(ip.to_string()),
+                };
+                state.audit(audit::AuditLogEntry::new(data).with_account(acc.id)).await;
                 Ok(cookie_to_response(cookie))
             }
-            None => Err(ApiError::incorrect_login()),
+            None => {
+                audit_failed_login(state, None, "unknown account").await;
+                Err(ApiError::incorrect_login())
+            }
         }
     } else {
+        let account_id = account.map(|a| a.id);
+        let reason = if account_id.is_some() {
+            "incorrect password"
+        } else {
+            "unknown account"
+        };
+        audit_failed_login(state, account_id, reason).await;
         Err(ApiError::incorrect_login())
     }
 }
@@ -158,12 +268,18 @@ async fn authenticate(state: &AppState, credentials: Credentials) -> Result<Resp
 async fn logout(State(state): State<AppState>, token: Token) -> TokenRejection {
     state.invalidate_account_cache(token.id);
     state.invalidate_session(&token.base64()).await;
+    state
+        .audit(audit::AuditLogEntry::new(audit::Logout { all_sessions: false }).with_account(token.id))
+        .await;
     TokenRejection
 }
 
 async fn logout_all(State(state): State<AppState>, account: Account) -> TokenRejection {
     state.invalidate_account_cache(account.id);
     state.invalidate_account_sessions(account.id).await;
+    state
+        .audit(audit::AuditLogEntry::new(audit::Logout { all_sessions: true }).with_account(account.id))
+        .await;
     TokenRejection
 }
 
@@ -226,11 +342,11 @@ async fn change_password(
         }
     };
 
-    if validate_password(&form.old_password, &account.password).is_err() {
+    if validate_password(&form.old_password, &account.password, &state.config().argon2_params).is_err() {
         return flasher.add("Invalid password").bail(&url);
     }
 
-    let Ok(changed_hash) = hash_password(&form.new_password) else {
+    let Ok(changed_hash) = hash_password(&form.new_password, &state.config().argon2_params) else {
         return flasher
             .add("Failed to hash password somehow. Try again later?")
             .bail(&url);
@@ -260,13 +376,47 @@ async fn change_password(
 
 async fn login_form(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     token: Option<Token>,
     flasher: Flasher,
     Form(credentials): Form<Credentials>,
 ) -> Response {
     let result = match credentials.action {
-        AuthenticationAction::Login => authenticate(&state, credentials).await,
-        AuthenticationAction::Register => register(&state, &token, credentials).await,
+        AuthenticationAction::Login => authenticate(&state, credentials, addr.ip()).await,
+        AuthenticationAction::Register => {
+            let registration_mode = state.config().registration_mode;
+            if registration_mode.is_closed() {
+                let template = LoginTemplate {
+                    account: None,
+                    flashes: vec![FlashMessage::error("Registration is currently closed.")].into(),
+                    registration_mode,
+                };
+                return (StatusCode::FORBIDDEN, template).into_response();
+            }
+
+            if let Some(retry_after) = state.registration_retry_after(addr.ip()) {
+                let flashes = vec![FlashMessage::error(
+                    "Too many accounts have been registered from this IP. Try again later.",
+                )];
+                let template = LoginTemplate {
+                    account: None,
+                    flashes: flashes.into(),
+                    registration_mode,
+                };
+                let mut response = (StatusCode::TOO_MANY_REQUESTS, template).into_response();
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs().max(1)));
+                response.extensions_mut().insert(BadRequestReason::RateLimited);
+                return response;
+            }
+
+            let result = register(&state, &token, credentials).await;
+            if result.is_ok() {
+                state.record_registration(addr.ip());
+            }
+            result
+        }
     };
     match result {
         Ok(r) => r,
@@ -286,7 +436,7 @@ struct AccountInfoTemplate {
     entries: Vec<DirectoryEntry>,
     sessions: Vec<Session>,
     current_session: Option<Session>,
-    api_key: Option<String>,
+    api_key: Option<Session>,
     key: SecretKey,
 }
 
@@ -317,7 +467,7 @@ impl AccountInfoTemplate {
         let api_key = sessions
             .iter()
             .position(|s| s.api_key)
-            .map(|idx| sessions.swap_remove(idx).id);
+            .map(|idx| sessions.swap_remove(idx));
 
         sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
         let key = state.config().secret_key;
@@ -362,18 +512,23 @@ struct EditAccountPayload {
     #[serde(default)]
     editor: Option<bool>,
     #[serde(default)]
+    restricted: Option<bool>,
+    #[serde(default)]
+    trusted: Option<bool>,
+    #[serde(default)]
     anilist_username: Patch<String>,
 }
 
 async fn edit_account(
     State(state): State<AppState>,
-    mut account: Account,
+    account: Account,
     Path(id): Path<i64>,
     Json(payload): Json<EditAccountPayload>,
 ) -> Result<StatusCode, ApiError> {
-    // Admin accounts are the only ones that can toggle the editor flag
+    // Admin accounts are the only ones that can toggle the editor, restricted, and trusted flags
     // Admins and regular users can change their anilist_username
-    if payload.editor.is_some() && !account.flags.is_admin() {
+    let changes_flags = payload.editor.is_some() || payload.restricted.is_some() || payload.trusted.is_some();
+    if changes_flags && !account.flags.is_admin() {
         return Err(ApiError::forbidden());
     }
 
@@ -381,10 +536,27 @@ async fn edit_account(
         return Err(ApiError::forbidden());
     }
 
+    let mut account = if account.id == id {
+        account
+    } else {
+        state
+            .get_account(id)
+            .await
+            .ok_or_else(|| ApiError::not_found("Account not found"))?
+    };
+
     if let Some(toggle) = payload.editor {
         account.flags.set_editor(toggle);
     }
 
+    if let Some(toggle) = payload.restricted {
+        account.flags.set_restricted(toggle);
+    }
+
+    if let Some(toggle) = payload.trusted {
+        account.flags.set_trusted(toggle);
+    }
+
     // At some point it might make sense to verify that this username is valid
     // But there's no current security risk in just letting this be as-is,
     // outside of some extraneous 404s being formed.
@@ -415,6 +587,8 @@ async fn edit_account(
 #[derive(Deserialize)]
 struct GenerateApiKey {
     new: bool,
+    #[serde(default)]
+    description: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -424,16 +598,307 @@ struct GeneratedApiKey {
 
 async fn generate_api_key(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     account: Account,
     Json(payload): Json<GenerateApiKey>,
 ) -> Result<Json<GeneratedApiKey>, ApiError> {
+    let Some(description) = payload
+        .description
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+    else {
+        return Err(ApiError::new("API keys require a description"));
+    };
+    if description.len() > 128 {
+        return Err(ApiError::new("Description can only be up to 128 characters long"));
+    }
+
     if !payload.new {
         state.invalidate_api_keys(account.id).await;
     }
-    let token = state.generate_api_key(account.id).await?;
+    let token = state.generate_api_key(account.id, description).await?;
+    let data = audit::Login {
+        api_key: true,
+        ip: Some(addr.ip().to_string()),
+    };
+    state
+        .audit(audit::AuditLogEntry::new(data).with_account(account.id))
+        .await;
     Ok(Json(GeneratedApiKey { token }))
 }
 
+#[derive(Deserialize)]
+struct SetEmailPayload {
+    email: SecretString,
+}
+
+async fn set_email(
+    State(state): State<AppState>,
+    account: Account,
+    Json(payload): Json<SetEmailPayload>,
+) -> Result<StatusCode, ApiError> {
+    let Some(smtp) = state.config().smtp_config.clone() else {
+        return Err(ApiError::new("Emails are not supported on this server"));
+    };
+
+    if !is_valid_email(&payload.email) {
+        return Err(ApiError::new("Invalid email address"));
+    }
+
+    let hash = hash_email(&state.config().secret_key, &payload.email);
+    let code = generate_verification_code()?;
+    state
+        .database()
+        .upsert(
+            r#"
+            INSERT INTO email_verification(account_id, email, code) VALUES (?, ?, ?)
+            ON CONFLICT DO UPDATE
+            SET email = EXCLUDED.email, code = EXCLUDED.code, created_at = CURRENT_TIMESTAMP
+            "#,
+            (account.id, hash, code.clone()),
+        )
+        .await?;
+
+    let body = format!(
+        "Your Jimaku verification code is {code}.\n\n\
+         This code expires in {VERIFICATION_CODE_EXPIRY_MINUTES} minutes. \
+         If you did not request this then you can safely ignore this email."
+    );
+    send_email(&smtp, &payload.email, "Jimaku email verification", body).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct VerifyEmailPayload {
+    code: String,
+}
+
+async fn verify_email(
+    State(state): State<AppState>,
+    account: Account,
+    Json(payload): Json<VerifyEmailPayload>,
+) -> Result<StatusCode, ApiError> {
+    let query = format!(
+        r#"
+        SELECT email FROM email_verification
+        WHERE account_id = ? AND code = ?
+          AND created_at >= datetime('now', '-{VERIFICATION_CODE_EXPIRY_MINUTES} minutes')
+        "#
+    );
+    let email: Option<String> = state
+        .database()
+        .get_row(query, (account.id, payload.code), |row| row.get(0))
+        .await
+        .optional()?;
+
+    let Some(email) = email else {
+        return Err(ApiError::new("Invalid or expired verification code"));
+    };
+
+    let account_id = account.id;
+    let result = state
+        .database()
+        .call(move |conn| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "UPDATE account SET email = ?, email_verified = 1 WHERE id = ?",
+                (email, account_id),
+            )?;
+            tx.execute("DELETE FROM email_verification WHERE account_id = ?", [account_id])?;
+            tx.commit()
+        })
+        .await;
+
+    match result {
+        Ok(_) => {
+            state.invalidate_account_cache(account_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(e) if is_unique_constraint_violation(&e) => Err(ApiError::new("This email is already in use")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The amount of time, in seconds, that a password reset link is valid for.
+const PASSWORD_RESET_EXPIRY: i64 = 3600;
+
+/// A signed token sent in password reset emails.
+#[derive(Debug, Serialize, Deserialize)]
+struct PasswordResetToken {
+    /// The account ID
+    id: i64,
+    /// The UNIX timestamp (in seconds) that this token expires at.
+    expires: i64,
+    /// An HMAC of the current password hash.
+    ///
+    /// This makes the token single use since the hash changes once the password is reset.
+    /// The token payload is only signed, not encrypted, so the hash itself can't be used.
+    nonce: String,
+}
+
+impl PasswordResetToken {
+    fn password_nonce(key: &SecretKey, account: &Account) -> String {
+        let mut mac = key.hmac();
+        mac.update(b"password-reset:");
+        mac.update(account.password.as_bytes());
+        BASE64_URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    fn new(key: &SecretKey, account: &Account) -> Self {
+        Self {
+            id: account.id,
+            expires: OffsetDateTime::now_utc().unix_timestamp() + PASSWORD_RESET_EXPIRY,
+            nonce: Self::password_nonce(key, account),
+        }
+    }
+
+    fn is_valid_for(&self, key: &SecretKey, account: &Account) -> bool {
+        self.id == account.id
+            && self.expires >= OffsetDateTime::now_utc().unix_timestamp()
+            && self.nonce == Self::password_nonce(key, account)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "recover.html")]
+struct RecoverTemplate {
+    account: Option<Account>,
+    flashes: Flashes,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RecoverQuery {
+    #[serde(default)]
+    token: Option<String>,
+}
+
+async fn recover_page(account: Option<Account>, flashes: Flashes, Query(query): Query<RecoverQuery>) -> Response {
+    if account.is_some() {
+        return Redirect::to("/account").into_response();
+    }
+
+    RecoverTemplate {
+        account,
+        flashes,
+        token: query.token,
+    }
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct RecoverAccountForm {
+    email: SecretString,
+}
+
+async fn recover_account(
+    State(state): State<AppState>,
+    flasher: Flasher,
+    Form(form): Form<RecoverAccountForm>,
+) -> Response {
+    let Some(smtp) = state.config().smtp_config.clone() else {
+        return flasher
+            .add("Account recovery is not supported on this server.")
+            .bail("/account/recover");
+    };
+
+    if !is_valid_email(&form.email) {
+        return flasher.add("Invalid email address.").bail("/account/recover");
+    }
+
+    let hash = hash_email(&state.config().secret_key, &form.email);
+    let account: Option<Account> = state
+        .database()
+        .get("SELECT * FROM account WHERE email = ? AND email_verified = 1", [hash])
+        .await
+        .ok()
+        .flatten();
+
+    // Don't leak whether an account with this email exists, the email is sent in the
+    // background so the response takes the same time either way
+    if let Some(account) = account {
+        let config = state.config();
+        match config
+            .secret_key
+            .sign(&PasswordResetToken::new(&config.secret_key, &account))
+        {
+            Ok(token) => {
+                let url = config.url_to(format!("/account/recover?token={token}"));
+                let body = format!(
+                    "A password reset was requested for the Jimaku account {}.\n\n\
+                     Use the following link to reset your password, it expires in an hour:\n{url}\n\n\
+                     If you did not request this then you can safely ignore this email.",
+                    account.name
+                );
+                tokio::spawn(async move {
+                    if let Err(e) = send_email(&smtp, &form.email, "Jimaku password reset", body).await {
+                        tracing::error!(error = %e, "could not send password reset email");
+                    }
+                });
+            }
+            Err(e) => tracing::error!(error = %e, "could not sign password reset token"),
+        }
+    }
+
+    flasher.add(FlashMessage::success(
+        "If an account with this verified email exists, a password reset link has been sent to it.",
+    ));
+    Redirect::to("/account/recover").into_response()
+}
+
+#[derive(Deserialize)]
+struct ResetPasswordForm {
+    token: String,
+    password: String,
+}
+
+async fn reset_password(
+    State(state): State<AppState>,
+    flasher: Flasher,
+    Form(form): Form<ResetPasswordForm>,
+) -> Response {
+    let url = format!("/account/recover?token={}", form.token);
+    let Some(token) = state.config().secret_key.verify::<PasswordResetToken>(&form.token) else {
+        return flasher.add("Invalid password reset link.").bail("/account/recover");
+    };
+
+    let Some(account) = state.database().get_by_id::<Account>(token.id).await.ok().flatten() else {
+        return flasher.add("Invalid password reset link.").bail("/account/recover");
+    };
+
+    if !token.is_valid_for(&state.config().secret_key, &account) {
+        return flasher
+            .add("This password reset link has expired.")
+            .bail("/account/recover");
+    }
+
+    if !((8..=128).contains(&form.password.len())) {
+        return flasher.add("Password length must be 8 to 128 characters").bail(&url);
+    }
+
+    let Ok(hash) = hash_password(&form.password, &state.config().argon2_params) else {
+        return flasher
+            .add("Failed to hash password somehow. Try again later?")
+            .bail(&url);
+    };
+
+    match state
+        .database()
+        .execute("UPDATE account SET password = ? WHERE id = ?", (hash, account.id))
+        .await
+    {
+        Ok(_) => {
+            state.invalidate_account_cache(account.id);
+            state.invalidate_account_sessions(account.id).await;
+            flasher.add(FlashMessage::success(
+                "Successfully reset password, you can now log in.",
+            ));
+            Redirect::to("/login").into_response()
+        }
+        Err(e) => flasher.add(format!("SQL error: {e}")).bail(&url),
+    }
+}
+
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route(
@@ -452,4 +917,22 @@ pub fn routes() -> Router<AppState> {
         .route("/account/change_password", post(change_password))
         .route("/user/:name", get(show_other_account_info))
         .route("/account/:id/edit", post(edit_account))
+        .route(
+            "/account/email",
+            post(set_email).layer(RateLimit::default().quota(1, 60.0).build()),
+        )
+        .route(
+            "/account/email/verify",
+            post(verify_email).layer(RateLimit::default().quota(5, 60.0).build()),
+        )
+        .route(
+            "/account/recover",
+            post(recover_account)
+                .layer(RateLimit::default().quota(3, 60.0).build())
+                .get(recover_page),
+        )
+        .route(
+            "/account/reset",
+            post(reset_password).layer(RateLimit::default().quota(5, 60.0).build()),
+        )
 }
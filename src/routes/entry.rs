@@ -1,21 +1,24 @@
 use crate::anilist::{self, MediaTitle};
 use crate::database::{is_unique_constraint_violation, Table};
-use crate::download::{validate_path, DownloadResponse};
+use crate::download::{
+    sign_download, validate_path, verify_download, DownloadResponse, SignedDownload, SIGNED_DOWNLOAD_DURATION,
+};
 use crate::error::{ApiError, ApiErrorCode, InternalError};
 use crate::flash::{FlashMessage, Flasher, Flashes};
 use crate::headers::Referrer;
-use crate::models::{Account, AccountCheck, DirectoryEntry, EntryFlags};
-use crate::ratelimit::RateLimit;
-use crate::utils::{is_over_length, FRAGMENT};
+use crate::models::{Account, AccountCheck, DirectoryEntry, EntryFlags, Report};
+use crate::ratelimit::{RateLimit, RateLimitKey};
+use crate::subtitle::{read_metadata, read_preview, SubtitleMeta};
+use crate::utils::{extract_episode_number, is_over_length, FRAGMENT};
 use crate::{audit, filters};
-use crate::{tmdb, AppState};
+use crate::{tmdb, ApiToken, AppState, Config};
 use anyhow::{bail, Context};
 use askama::Template;
 use axum::body::{Body, Bytes};
 use axum::extract::multipart::Field;
 use axum::extract::{Json, Multipart, Query};
 use axum::http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE};
-use axum::http::{HeaderName, HeaderValue};
+use axum::http::{HeaderName, HeaderValue, StatusCode};
 use axum::response::Redirect;
 use axum::routing::{delete, get, post};
 use axum::{
@@ -56,6 +59,8 @@ struct EntryTemplate {
     entry: DirectoryEntry,
     files: Vec<FileEntry>,
     flashes: Flashes,
+    url: String,
+    allowed_extensions: Vec<String>,
 }
 
 pub(crate) fn get_file_entries(entry_id: i64, path: &std::path::Path) -> std::io::Result<Vec<FileEntry>> {
@@ -97,12 +102,18 @@ async fn get_entry(
     let Some(entry) = state.get_directory_entry(entry_id).await else {
         return Ok(Redirect::to("/").into_response());
     };
+    if entry.flags.is_hidden() && !account.as_ref().is_some_and(|a| a.flags.is_editor()) {
+        return Ok(Redirect::to("/").into_response());
+    }
     let files = get_file_entries(entry_id, &entry.path)?;
+    let config = state.config();
     Ok(EntryTemplate {
         account,
         entry,
         files,
         flashes,
+        url: config.canonical_url(),
+        allowed_extensions: config.allowed_extensions().to_vec(),
     }
     .into_response())
 }
@@ -110,9 +121,36 @@ async fn get_entry(
 async fn download_entry(
     State(state): State<AppState>,
     Path((entry_id, filename)): Path<(i64, String)>,
+    Query(signed): Query<SignedDownload>,
+    account: Option<Account>,
     req: Request,
 ) -> DownloadResponse {
-    let Some(base) = state.get_directory_entry_path(entry_id).await else {
+    // Signed links are optional, but if a token is given then it has to be valid
+    if let Some(token) = signed.token.as_deref() {
+        let Some(expires) = signed.expires else {
+            return DownloadResponse::InvalidToken;
+        };
+        if !verify_download(&state.config().secret_key, entry_id, &filename, expires, token) {
+            return DownloadResponse::InvalidToken;
+        }
+        if OffsetDateTime::now_utc().unix_timestamp() > expires {
+            return DownloadResponse::Expired;
+        }
+    }
+
+    // A valid signed link was handed out by someone who could see the entry
+    let base = if signed.token.is_some() {
+        state.get_directory_entry_path(entry_id).await
+    } else {
+        let account_id = account
+            .map(|a| a.id)
+            .or_else(|| req.extensions().get::<ApiToken>().map(|t| t.id));
+        state
+            .get_visible_directory_entry(entry_id, account_id)
+            .await
+            .map(|e| e.path)
+    };
+    let Some(base) = base else {
         return DownloadResponse::NotFound;
     };
 
@@ -126,8 +164,146 @@ async fn download_entry(
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct CreateDirectoryEntry {
+async fn get_file_metadata(
+    State(state): State<AppState>,
+    Path((entry_id, filename)): Path<(i64, String)>,
+    account: Option<Account>,
+) -> Result<Json<SubtitleMeta>, ApiError> {
+    let Some(base) = state
+        .get_visible_directory_entry(entry_id, account.map(|a| a.id))
+        .await
+        .map(|e| e.path)
+    else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let Some(path) = validate_path(&base, filename.as_str()).filter(|p| p.is_file()) else {
+        return Err(ApiError::not_found("File not found"));
+    };
+
+    if let Some(meta) = state.cached_file_metadata(&path) {
+        return Ok(Json(meta));
+    }
+
+    let cloned = path.clone();
+    let meta = tokio::task::spawn_blocking(move || read_metadata(&cloned)).await??;
+    state.cache_file_metadata(path, meta.clone());
+    Ok(Json(meta))
+}
+
+#[derive(Serialize)]
+struct EpisodeCoverage {
+    /// The total number of episodes, this is missing for ongoing series.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<u32>,
+    found: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    missing: Option<Vec<u16>>,
+}
+
+async fn get_missing_episodes(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+    account: Account,
+) -> Result<Response, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let Some(entry) = state.get_directory_entry(entry_id).await else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let Some(anilist_id) = entry.anilist_id else {
+        let error = ApiError::new("Missing episodes can only be checked for entries with an AniList ID");
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(error)).into_response());
+    };
+
+    let total = state.anilist_episode_count(anilist_id).await?;
+    let found = get_file_entries(entry_id, &entry.path)?
+        .iter()
+        .filter_map(|f| extract_episode_number(&f.name))
+        .collect::<std::collections::BTreeSet<_>>();
+    let missing = total.map(|total| {
+        (1..=total)
+            .filter_map(|ep| u16::try_from(ep).ok())
+            .filter(|ep| !found.contains(ep))
+            .collect()
+    });
+    Ok(Json(EpisodeCoverage {
+        total,
+        found: found.into_iter().collect(),
+        missing,
+    })
+    .into_response())
+}
+
+async fn preview_file(
+    State(state): State<AppState>,
+    Path((entry_id, filename)): Path<(i64, String)>,
+    account: Account,
+) -> Result<Response, ApiError> {
+    let Some(base) = state
+        .get_visible_directory_entry(entry_id, Some(account.id))
+        .await
+        .map(|e| e.path)
+    else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let Some(path) = validate_path(&base, filename.as_str()).filter(|p| p.is_file()) else {
+        return Err(ApiError::not_found("File not found"));
+    };
+
+    match tokio::task::spawn_blocking(move || read_preview(&path)).await?? {
+        Some(preview) => Ok(([(CONTENT_TYPE, "text/plain; charset=utf-8")], preview).into_response()),
+        None => Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()),
+    }
+}
+
+#[derive(Deserialize)]
+struct GenerateDownloadLink {
+    filename: String,
+}
+
+#[derive(Serialize)]
+struct DownloadLink {
+    url: String,
+    #[serde(with = "time::serde::timestamp")]
+    expires_at: OffsetDateTime,
+}
+
+async fn generate_download_link(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+    account: Account,
+    Json(payload): Json<GenerateDownloadLink>,
+) -> Result<Json<DownloadLink>, ApiError> {
+    let Some(base) = state
+        .get_visible_directory_entry(entry_id, Some(account.id))
+        .await
+        .map(|e| e.path)
+    else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    let filename = payload.filename.trim_start_matches('/');
+    if !validate_path(&base, filename).is_some_and(|p| p.is_file()) {
+        return Err(ApiError::not_found("File not found"));
+    }
+
+    let expires_at = OffsetDateTime::now_utc() + SIGNED_DOWNLOAD_DURATION;
+    let expires = expires_at.unix_timestamp();
+    let token = sign_download(&state.config().secret_key, entry_id, filename, expires);
+    let url = format!(
+        "/entry/{entry_id}/download/{}?token={token}&expires={expires}",
+        percent_encode(filename.as_bytes(), FRAGMENT)
+    );
+    Ok(Json(DownloadLink { url, expires_at }))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct CreateDirectoryEntry {
     #[serde(deserialize_with = "crate::utils::empty_string_is_none")]
     #[serde(default)]
     anilist_url: Option<String>,
@@ -234,9 +410,13 @@ pub async fn raw_create_directory_entry(
     pending: PendingDirectoryEntry,
     api: bool,
 ) -> Result<(i64, PathBuf), ApiError> {
+    if account.flags.is_restricted() {
+        return Err(ApiError::forbidden());
+    }
+
     let creator_id = account.id;
 
-    let (names, flags) = match pending.get_info(state).await? {
+    let (names, mut flags) = match pending.get_info(state).await? {
         Some(title) => title,
         None if account.flags.is_editor() => {
             if let Some(name) = pending.name.clone() {
@@ -250,6 +430,10 @@ pub async fn raw_create_directory_entry(
         None => return Err(ApiError::new("Missing anilist_id or tmdb_id for directory.")),
     };
 
+    // Featuring and hiding go through editing the entry, which enforces who can do it
+    flags.set_featured(false);
+    flags.set_hidden(false);
+
     let path = pending.path(&names.romaji, pending.anime, state);
     if path.exists() {
         return Err(ApiError::new("Path already exists.").with_code(ApiErrorCode::EntryAlreadyExists));
@@ -339,7 +523,9 @@ pub async fn raw_create_directory_entry(
                 .field("AniList URL", anilist_url)
                 .field("TMDB URL", tmdb_url),
         );
-        state.cached_directories().invalidate().await;
+        state.cached_directories().invalidate();
+        state.body_cache().invalidate_prefix("index");
+        state.body_cache().invalidate_prefix("dramas");
     }
     response
 }
@@ -358,6 +544,41 @@ async fn create_directory_entry(
     }
 }
 
+async fn submit_directory_entry(
+    State(state): State<AppState>,
+    account: Account,
+    flasher: Flasher,
+    Referrer(url): Referrer,
+    Form(payload): Form<CreateDirectoryEntry>,
+) -> Result<Response, InternalError> {
+    if account.flags.is_restricted() {
+        return Ok(flasher
+            .add(FlashMessage::error("You are not allowed to submit entries."))
+            .bail(&url));
+    }
+
+    if payload.anilist_url.is_none() && payload.tmdb_url.is_none() && payload.name.is_none() {
+        return Ok(flasher
+            .add(FlashMessage::error("Missing name, AniList URL, or TMDB URL for entry."))
+            .bail(&url));
+    }
+
+    state
+        .database()
+        .execute(
+            "INSERT INTO pending_entry(submitted_by, data) VALUES (?, ?)",
+            (account.id, serde_json::to_string(&payload)?),
+        )
+        .await?;
+
+    Ok(flasher
+        .add(FlashMessage::success("Your entry has been submitted for review."))
+        .bail(&url))
+}
+
+/// The maximum number of featured entries per category (i.e. anime or live action).
+pub(super) const MAX_FEATURED_ENTRIES: usize = 20;
+
 #[derive(Deserialize)]
 struct EditDirectoryEntry {
     name: String,
@@ -379,10 +600,16 @@ struct EditDirectoryEntry {
     movie: bool,
     #[serde(default)]
     anime: bool,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    featured: bool,
 }
 
 impl EditDirectoryEntry {
     fn apply_flags(&self, mut flags: EntryFlags) -> EntryFlags {
+        flags.set_hidden(self.hidden);
+        flags.set_featured(self.featured);
         flags.set_unverified(self.unverified);
         flags.set_adult(self.adult);
         flags.set_movie(self.movie);
@@ -447,7 +674,7 @@ where
     match opt {
         None | Some("") => Ok(None),
         Some(s) => tmdb::get_tmdb_id(s)
-            .ok_or_else(|| serde::de::Error::custom("Invalid TMDB URL provided"))
+            .ok_or_else(|| serde::de::Error::custom("Invalid TMDB URL or ID provided"))
             .map(Some),
     }
 }
@@ -480,7 +707,25 @@ async fn edit_directory_entry(
     let mut columns = Vec::with_capacity(11);
     let mut params: Vec<Box<dyn rusqlite::ToSql + Send>> = Vec::with_capacity(11);
     let mut audit_data = audit::EditEntry::default();
-    let flags = payload.apply_flags(entry.flags);
+    let mut flags = payload.apply_flags(entry.flags);
+    if !account.flags.is_admin() {
+        // Only admins can feature entries, so keep whatever it was before
+        flags.set_featured(entry.flags.is_featured());
+    }
+
+    if flags.is_featured() && !entry.flags.is_featured() {
+        let featured = state
+            .directory_entries()
+            .await
+            .iter()
+            .filter(|e| e.flags.is_featured() && e.flags.is_anime() == flags.is_anime())
+            .count();
+        if featured >= MAX_FEATURED_ENTRIES {
+            return flasher
+                .add(format!("Cannot feature more than {MAX_FEATURED_ENTRIES} entries."))
+                .bail(&url);
+        }
+    }
 
     if entry.name != payload.name {
         columns.push("name");
@@ -535,7 +780,7 @@ async fn edit_directory_entry(
             .await
         {
             Ok(_) => {
-                state.cached_directories().invalidate().await;
+                state.cached_directories().invalidate();
                 state
                     .audit(audit::AuditLogEntry::full(audit_data, entry_id, account.id))
                     .await;
@@ -596,6 +841,21 @@ async fn search_directory_entries(
         .map(sanitise_file_name::sanitise)
         .and_then(|x| state.config().subtitle_path.join(x).to_str().map(String::from));
 
+    // Lookups by name alone can be answered from the cache with the same conditions as the query below
+    if let (Some(name), None, None) = (params.name.as_deref(), params.anilist_id, params.tmdb_id.as_deref()) {
+        let entry_id = state
+            .directory_entries()
+            .await
+            .iter()
+            .filter(|e| e.name == name || (path.is_some() && e.path.to_str() == path.as_deref()))
+            .map(|e| e.id)
+            .min();
+        return match entry_id {
+            Some(entry_id) => Ok(Json(SearchResult { entry_id })),
+            None => Err(ApiError::not_found("Entry not found.")),
+        };
+    }
+
     let entry = state
         .database()
         .get_row(
@@ -700,12 +960,19 @@ async fn move_directory_entries(
     let _ = state
         .database()
         .execute(
-            "UPDATE directory_entry SET last_updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-            [entry_id],
+            "UPDATE directory_entry SET last_updated_at = CURRENT_TIMESTAMP, file_count = file_count + ? WHERE id = ?",
+            (success, entry_id),
+        )
+        .await;
+    let _ = state
+        .database()
+        .execute(
+            "UPDATE directory_entry SET file_count = MAX(file_count - ?, 0) WHERE id = ?",
+            (success, from_entry_id),
         )
         .await;
 
-    state.cached_directories().invalidate().await;
+    state.cached_directories().invalidate();
     state
         .audit(audit::AuditLogEntry::full(audit_data, from_entry_id, account.id))
         .await;
@@ -766,7 +1033,7 @@ async fn bulk_delete_files(
                 |r| r.get("name"),
             )
             .await?;
-        state.cached_directories().invalidate().await;
+        state.cached_directories().invalidate();
         let result = tokio::fs::remove_dir_all(entry).await;
         state
             .audit(
@@ -800,6 +1067,16 @@ async fn bulk_delete_files(
                 Err(_) => failed += 1,
             }
         }
+        if success != 0 {
+            let _ = state
+                .database()
+                .execute(
+                    "UPDATE directory_entry SET file_count = MAX(file_count - ?, 0) WHERE id = ?",
+                    (success, entry_id),
+                )
+                .await;
+            state.cached_directories().invalidate();
+        }
         state
             .audit(audit::AuditLogEntry::full(audit_data, entry_id, account.id))
             .await;
@@ -822,17 +1099,22 @@ async fn bulk_delete_files(
 }
 
 #[derive(Deserialize)]
-struct ReportPayload {
-    files: Vec<String>,
-    reason: String,
+pub struct ReportPayload {
+    #[serde(default)]
+    pub files: Vec<String>,
+    pub reason: String,
 }
 
-async fn report_entry(
-    State(state): State<AppState>,
-    Path(entry_id): Path<i64>,
+pub async fn raw_report_entry(
+    state: &AppState,
+    entry_id: i64,
     account: Account,
-    Json(payload): Json<ReportPayload>,
-) -> Result<(), ApiError> {
+    payload: ReportPayload,
+) -> Result<Report, ApiError> {
+    if account.flags.is_restricted() {
+        return Err(ApiError::forbidden());
+    }
+
     let Some(entry) = state.get_directory_entry(entry_id).await else {
         return Err(ApiError::not_found("Directory entry not found."));
     };
@@ -845,6 +1127,23 @@ async fn report_entry(
     }
 
     let account_id = account.id;
+    let report: Option<Report> = state
+        .database()
+        .get(
+            "INSERT INTO report(entry_id, user_id, reason, files) VALUES (?, ?, ?, ?) RETURNING *",
+            (
+                entry_id,
+                account_id,
+                payload.reason.clone(),
+                serde_json::to_string(&payload.files)?,
+            ),
+        )
+        .await?;
+    let Some(report) = report else {
+        return Err(ApiError::new("Could not create report").with_code(ApiErrorCode::ServerError));
+    };
+    state.notifications().notify_new_report(&report).await;
+
     let mut alert = crate::discord::Alert::error(format!("Entry Reported: {}", entry.name))
         .url(format!("/entry/{entry_id}"))
         .field("Reason", &payload.reason)
@@ -877,6 +1176,16 @@ async fn report_entry(
         state.send_alert(alert);
     }
 
+    Ok(report)
+}
+
+async fn report_entry(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+    account: Account,
+    Json(payload): Json<ReportPayload>,
+) -> Result<(), ApiError> {
+    raw_report_entry(&state, entry_id, account, payload).await?;
     Ok(())
 }
 
@@ -962,42 +1271,55 @@ impl PendingFileEntry {
 
 struct ProcessedFiles {
     files: Vec<ProcessedFile>,
-    skipped: usize,
+    /// The file names that were skipped along with the reason why.
+    skipped: Vec<String>,
 }
 
-async fn verify_file(
-    entry_path: &std::path::Path,
-    file_name: PathBuf,
-    field: Field<'_>,
-) -> anyhow::Result<ProcessedFile> {
+/// Returns the path the file would be written to if it can be uploaded.
+fn verify_file_path(config: &Config, entry_path: &std::path::Path, file_name: PathBuf) -> anyhow::Result<PathBuf> {
     match file_name.extension().and_then(|ext| ext.to_str()) {
-        Some("srt" | "ass" | "ssa" | "zip" | "sub" | "sup" | "idx" | "7z") => {
+        Some(ext) if config.is_allowed_extension(ext) => {
             let path = entry_path.join(file_name);
             if path.exists() {
                 bail!("filename already exists")
             }
-            let bytes = field.bytes().await?;
-            Ok(ProcessedFile { path, bytes })
+            Ok(path)
         }
         _ => bail!("invalid file extension"),
     }
 }
 
-async fn process_files(entry_path: &std::path::Path, mut multipart: Multipart) -> anyhow::Result<ProcessedFiles> {
+async fn verify_file(
+    config: &Config,
+    entry_path: &std::path::Path,
+    file_name: PathBuf,
+    field: Field<'_>,
+) -> anyhow::Result<ProcessedFile> {
+    let path = verify_file_path(config, entry_path, file_name)?;
+    let bytes = field.bytes().await?;
+    Ok(ProcessedFile { path, bytes })
+}
+
+async fn process_files(
+    config: &Config,
+    entry_path: &std::path::Path,
+    mut multipart: Multipart,
+) -> anyhow::Result<ProcessedFiles> {
     let mut files = Vec::new();
-    let mut skipped = 0;
+    let mut skipped = Vec::new();
     while let Some(field) = multipart.next_field().await? {
         let Some(name) = field.file_name().map(sanitise_file_name::sanitise).map(PathBuf::from) else {
             tracing::debug!("Skipped file due to missing filename");
-            skipped += 1;
+            skipped.push("Skipped a file with no name".to_owned());
             continue;
         };
 
-        match verify_file(entry_path, name, field).await {
+        let display = name.display().to_string();
+        match verify_file(config, entry_path, name, field).await {
             Ok(file) => files.push(file),
             Err(e) => {
                 tracing::debug!(error=%e, "Skipped file due to validation issue");
-                skipped += 1
+                skipped.push(format!("Skipped {display}: {e}"));
             }
         }
     }
@@ -1005,7 +1327,7 @@ async fn process_files(entry_path: &std::path::Path, mut multipart: Multipart) -
 }
 
 /// The result of an upload operation.
-#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct UploadResult {
     /// The number of files that did not succeed due to a filesystem error.
     errors: usize,
@@ -1013,6 +1335,9 @@ pub struct UploadResult {
     total: usize,
     /// The number of files that were skipped due to some reason
     skipped: usize,
+    /// A line for every file that was skipped or failed, for showing to the user.
+    #[serde(skip)]
+    details: Vec<String>,
 }
 
 impl UploadResult {
@@ -1029,6 +1354,18 @@ impl UploadResult {
     }
 }
 
+/// Returns the number of files in a directory.
+async fn count_files(path: &std::path::Path) -> std::io::Result<usize> {
+    let mut count = 0;
+    let mut dir = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
 pub async fn raw_upload_file(
     state: AppState,
     entry_id: i64,
@@ -1036,11 +1373,15 @@ pub async fn raw_upload_file(
     multipart: Multipart,
     api: bool,
 ) -> Result<UploadResult, ApiError> {
+    if account.flags.is_restricted() {
+        return Err(ApiError::forbidden());
+    }
+
     let Some(entry) = state.get_directory_entry_path(entry_id).await else {
         return Err(ApiError::not_found("Entry not found"));
     };
 
-    let Ok(processed) = process_files(&entry, multipart).await else {
+    let Ok(processed) = process_files(&state.config(), &entry, multipart).await else {
         return Err(ApiError::new("Internal error when processing files").with_code(ApiErrorCode::ServerError));
     };
 
@@ -1048,6 +1389,17 @@ pub async fn raw_upload_file(
         return Err(ApiError::new("Did not upload any files."));
     }
 
+    let exempt = state.config().quota_exempt_editors && account.flags.is_editor();
+    if let Some(limit) = state.config().max_upload_files_per_entry.filter(|_| !exempt) {
+        let count = count_files(&entry).await?;
+        if count + processed.files.len() > limit {
+            return Err(ApiError::new(format!(
+                "This entry has {count} files and can only have up to {limit} files, so {} more cannot be uploaded.",
+                processed.files.len()
+            )));
+        }
+    }
+
     let mut errored = 0usize;
     let total = processed.files.len();
     let mut data = audit::Upload {
@@ -1073,7 +1425,27 @@ pub async fn raw_upload_file(
         }
     }
 
-    let successful = total > 0 && errored == 0 && processed.skipped == 0;
+    let skipped = processed.skipped.len();
+    let mut details = processed.skipped;
+    details.extend(
+        data.files
+            .iter()
+            .filter(|op| op.failed)
+            .map(|op| format!("Failed to save {}", op.name)),
+    );
+
+    // Partial uploads still write files to disk so the count has to follow
+    if total > errored {
+        let _ = state
+            .database()
+            .execute(
+                "UPDATE directory_entry SET file_count = file_count + ? WHERE id = ?",
+                ((total - errored) as u32, entry_id),
+            )
+            .await;
+    }
+
+    let successful = total > 0 && errored == 0 && skipped == 0;
     if successful && errored != total {
         let _ = state
             .database()
@@ -1082,7 +1454,28 @@ pub async fn raw_upload_file(
                 [entry_id],
             )
             .await;
-        state.cached_directories().invalidate().await;
+
+        // Uploads from regular users need to be checked by an editor
+        if !account.flags.is_editor() && !account.flags.is_trusted() {
+            if let Some(mut entry) = state
+                .get_directory_entry(entry_id)
+                .await
+                .filter(|e| !e.flags.is_unverified())
+            {
+                entry.flags.set_unverified(true);
+                let _ = state
+                    .database()
+                    .execute(
+                        "UPDATE directory_entry SET flags = ? WHERE id = ?",
+                        (entry.flags, entry_id),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    if total > errored {
+        state.cached_directories().invalidate();
     }
 
     state
@@ -1092,7 +1485,8 @@ pub async fn raw_upload_file(
     Ok(UploadResult {
         errors: errored,
         total,
-        skipped: processed.skipped,
+        skipped,
+        details,
     })
 }
 
@@ -1114,23 +1508,210 @@ async fn upload_file(
         FlashMessage::error("Upload failed.")
     } else {
         let successful = result.successful();
-        FlashMessage::warning(format!(
-            "Uploaded {successful} file{}, {} {} skipped and {} failed",
-            if successful == 1 { "" } else { "s" },
-            result.skipped,
-            if result.skipped == 1 { "was" } else { "were" },
-            result.errors,
-        ))
+        FlashMessage::warning(
+            format!(
+                "Uploaded {successful} file{}, {} {} skipped and {} failed",
+                if successful == 1 { "" } else { "s" },
+                result.skipped,
+                if result.skipped == 1 { "was" } else { "were" },
+                result.errors,
+            ),
+            Some(result.details),
+        )
     };
     flasher.add(message).bail(&url)
 }
 
+/// The MIME types that are accepted when importing a file from a URL.
+///
+/// Subtitle files are rarely served with a proper MIME type so this is fairly lenient,
+/// the file extension is still checked afterwards.
+const IMPORT_MIME_TYPES: &[&str] = &[
+    "text/plain",
+    "text/x-ssa",
+    "text/x-ass",
+    "application/x-subrip",
+    "application/x-ssa",
+    "application/x-ass",
+    "application/zip",
+    "application/x-zip-compressed",
+    "application/x-7z-compressed",
+    "application/vnd.rar",
+    "application/x-rar-compressed",
+    "application/octet-stream",
+];
+
+#[derive(Deserialize)]
+struct ImportUrlPayload {
+    url: reqwest::Url,
+    #[serde(default)]
+    filename: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImportedFile {
+    name: String,
+    size: usize,
+}
+
+/// Resolves the host of the URL and builds a client that can only connect to it.
+///
+/// Every resolved address must be publicly routable, so that imports can't be used to
+/// fetch internal resources. The client is pinned to the checked addresses so that the
+/// host can't resolve to a different address when connecting, and redirects are not
+/// followed since their target would not be checked.
+async fn import_client(url: &reqwest::Url) -> Result<reqwest::Client, ApiError> {
+    let host = url.host_str().ok_or_else(|| ApiError::new("URL must have a host"))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(std::time::Duration::from_secs(60));
+
+    // IPv6 hosts are surrounded by brackets
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<std::net::SocketAddr> = match literal.parse::<std::net::IpAddr>() {
+        Ok(ip) => vec![(ip, port).into()],
+        Err(_) => {
+            let addresses: Vec<_> = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| ApiError::new("Could not resolve the URL's host"))?
+                .collect();
+            builder = builder.resolve_to_addrs(host, &addresses);
+            addresses
+        }
+    };
+
+    if addresses.is_empty() || !addresses.iter().all(|addr| crate::utils::is_public_ip(addr.ip())) {
+        return Err(ApiError::new("URL must point to a public address"));
+    }
+
+    builder
+        .build()
+        .map_err(|_| ApiError::new("Could not create HTTP client").with_code(ApiErrorCode::ServerError))
+}
+
+async fn download_import_url(url: reqwest::Url) -> Result<Bytes, ApiError> {
+    let client = import_client(&url).await?;
+    let mut resp = client
+        .get(url)
+        .header(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static(crate::kitsunekko::USER_AGENT),
+        )
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| ApiError::new(format!("Could not download file: {e}")))?;
+
+    if resp.status().is_redirection() {
+        return Err(ApiError::new("Redirects are not followed, use the final URL instead"));
+    }
+
+    let mime = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase());
+    match mime {
+        Some(mime) if IMPORT_MIME_TYPES.contains(&mime.as_str()) => {}
+        Some(mime) => return Err(ApiError::new(format!("Unsupported content type {mime}"))),
+        None => return Err(ApiError::new("Missing content type")),
+    }
+
+    if resp.content_length().is_some_and(|size| size > crate::MAX_UPLOAD_SIZE) {
+        return Err(ApiError::new("File is too large"));
+    }
+
+    // The Content-Length header can be missing or lie, so check while reading as well
+    let mut buffer = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| ApiError::new(format!("Could not download file: {e}")))?
+    {
+        if (buffer.len() + chunk.len()) as u64 > crate::MAX_UPLOAD_SIZE {
+            return Err(ApiError::new("File is too large"));
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+async fn import_file_from_url(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+    account: Account,
+    Json(payload): Json<ImportUrlPayload>,
+) -> Result<Json<ImportedFile>, ApiError> {
+    if !account.flags.is_editor() {
+        return Err(ApiError::forbidden());
+    }
+
+    let Some(entry) = state.get_directory_entry_path(entry_id).await else {
+        return Err(ApiError::not_found("Entry not found"));
+    };
+
+    if !matches!(payload.url.scheme(), "http" | "https") {
+        return Err(ApiError::new("URL must be HTTP or HTTPS"));
+    }
+
+    let name = match payload.filename.filter(|s| !s.trim().is_empty()) {
+        Some(name) => name,
+        None => payload
+            .url
+            .path_segments()
+            .and_then(|mut s| s.next_back())
+            .map(|s| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| ApiError::new("Could not determine a filename from the URL"))?,
+    };
+    let name = sanitise_file_name::sanitise(&name);
+    let path =
+        verify_file_path(&state.config(), &entry, PathBuf::from(&name)).map_err(|e| ApiError::new(e.to_string()))?;
+
+    let bytes = download_import_url(payload.url).await?;
+    let size = bytes.len();
+    let file = ProcessedFile { path, bytes };
+    let failed = !matches!(
+        tokio::task::spawn_blocking(move || file.write_to_disk()).await,
+        Ok(Ok(()))
+    );
+
+    if !failed {
+        let _ = state
+            .database()
+            .execute(
+                "UPDATE directory_entry SET last_updated_at = CURRENT_TIMESTAMP, file_count = file_count + 1 \
+                 WHERE id = ?",
+                [entry_id],
+            )
+            .await;
+        state.cached_directories().invalidate();
+    }
+
+    let mut data = audit::Upload {
+        files: Vec::with_capacity(1),
+        api: false,
+    };
+    data.add_file(name.clone(), failed);
+    state
+        .audit(audit::AuditLogEntry::full(data, entry_id, account.id))
+        .await;
+
+    if failed {
+        return Err(ApiError::new("Could not write file to disk").with_code(ApiErrorCode::ServerError));
+    }
+    Ok(Json(ImportedFile { name, size }))
+}
+
 async fn bulk_download(
     State(state): State<AppState>,
     Path(entry_id): Path<i64>,
+    account: Option<Account>,
     Json(payload): Json<BulkFilesPayload>,
 ) -> Result<Response, ApiError> {
-    let Some(entry) = state.get_directory_entry(entry_id).await else {
+    let Some(entry) = state.get_visible_directory_entry(entry_id, account.map(|a| a.id)).await else {
         return Err(ApiError::not_found("Directory entry not found."));
     };
 
@@ -1320,7 +1901,13 @@ async fn get_pending_directory_entry(state: &AppState, anime: bool, name: String
             temporary.english_name = media.title.english;
         }
     } else {
-        let info = tmdb::find_match(&state.client, &state.config().tmdb_api_key, &name).await;
+        let info = tmdb::find_match(
+            &state.client,
+            &state.config().tmdb_api_key,
+            &name,
+            Some(state.tmdb_cache()),
+        )
+        .await;
         if let Ok(Some(info)) = info {
             temporary.tmdb_id = Some(info.id);
             temporary.flags.set_movie(info.id.is_movie());
@@ -1464,10 +2051,29 @@ pub fn routes() -> Router<AppState> {
             "/entry/:id/download/*path",
             get(download_entry).layer(CorsLayer::permissive()),
         )
+        .route("/entry/:id/files/:filename/meta", get(get_file_metadata))
+        .route("/entry/:id/missing-episodes", get(get_missing_episodes))
+        .route(
+            "/entry/:id/files/:filename/preview",
+            get(preview_file).layer(
+                RateLimit::default()
+                    .quota(20, 60.0)
+                    .key(RateLimitKey::AccountId)
+                    .build(),
+            ),
+        )
+        .route(
+            "/entry/:id/generate-link",
+            post(generate_download_link).layer(RateLimit::default().build()),
+        )
         .route(
             "/entry/create",
             post(create_directory_entry).layer(RateLimit::default().quota(5, 30.0).build()),
         )
+        .route(
+            "/entry/submit",
+            post(submit_directory_entry).layer(RateLimit::default().quota(5, 60.0).build()),
+        )
         .route("/entry/:id/edit", post(edit_directory_entry))
         .route("/entry/:id/move", post(move_directory_entries))
         .route("/entry/:id/rename", post(bulk_rename_files))
@@ -1481,6 +2087,15 @@ pub fn routes() -> Router<AppState> {
             "/entry/:id/upload",
             post(upload_file).layer(RateLimit::default().build()),
         )
+        .route(
+            "/entry/:id/files/import-url",
+            post(import_file_from_url).layer(
+                RateLimit::default()
+                    .quota(5, 300.0)
+                    .key(RateLimitKey::AccountId)
+                    .build(),
+            ),
+        )
         .route(
             "/entry/:id/bulk",
             post(bulk_download).layer(RateLimit::default().build()),
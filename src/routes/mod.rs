@@ -22,6 +22,7 @@ mod api;
 mod audit;
 mod auth;
 mod entry;
+mod notifications;
 mod opensearch;
 mod relations;
 
@@ -34,6 +35,7 @@ where
     It: Iterator<Item = &'a DirectoryEntry> + Clone,
 {
     account: Option<Account>,
+    featured: Vec<&'a DirectoryEntry>,
     entries: It,
     flashes: Flashes,
     url: String,
@@ -49,14 +51,22 @@ async fn index(
 ) -> impl IntoResponse {
     let entries = state.directory_entries().await;
     let bypass_cache = account.is_some();
+    let editor = account.as_ref().is_some_and(|a| a.flags.is_editor());
+    let visible = entries
+        .iter()
+        .filter(|e| e.flags.is_anime())
+        .filter(move |e| !e.flags.is_hidden() || editor);
     let template = ListingTemplate {
         account,
-        entries: entries.iter().filter(|e| e.flags.is_anime()),
+        featured: visible.clone().filter(|e| e.flags.is_featured()).collect(),
+        entries: visible.filter(|e| !e.flags.is_featured()),
         flashes,
         url: state.config().canonical_url(),
         anime: true,
     };
-    cacher.cache_template("index", template, encoding, bypass_cache).await
+    cacher
+        .cache_template("index", template, encoding, bypass_cache, None)
+        .await
 }
 
 async fn dramas(
@@ -68,34 +78,50 @@ async fn dramas(
 ) -> impl IntoResponse {
     let entries = state.directory_entries().await;
     let bypass_cache = account.is_some();
+    let editor = account.as_ref().is_some_and(|a| a.flags.is_editor());
+    let visible = entries
+        .iter()
+        .filter(|e| !e.flags.is_anime())
+        .filter(move |e| !e.flags.is_hidden() || editor);
     let template = ListingTemplate {
         account,
-        entries: entries.iter().filter(|e| !e.flags.is_anime()),
+        featured: visible.clone().filter(|e| e.flags.is_featured()).collect(),
+        entries: visible.filter(|e| !e.flags.is_featured()),
         flashes,
         url: state.config().url_to("/dramas"),
         anime: false,
     };
-    cacher.cache_template("dramas", template, encoding, bypass_cache).await
+    cacher
+        .cache_template("dramas", template, encoding, bypass_cache, None)
+        .await
 }
 
 #[derive(Template)]
 #[template(path = "help.html")]
 struct HelpTemplate {
     account: Option<Account>,
+    url: String,
 }
 
-async fn help_page(account: Option<Account>) -> impl IntoResponse {
-    HelpTemplate { account }
+async fn help_page(State(state): State<AppState>, account: Option<Account>) -> impl IntoResponse {
+    HelpTemplate {
+        account,
+        url: state.config().canonical_url(),
+    }
 }
 
 #[derive(Template)]
 #[template(path = "contact.html")]
 struct ContactTemplate {
     account: Option<Account>,
+    url: String,
 }
 
-async fn contact_page(account: Option<Account>) -> impl IntoResponse {
-    ContactTemplate { account }
+async fn contact_page(State(state): State<AppState>, account: Option<Account>) -> impl IntoResponse {
+    ContactTemplate {
+        account,
+        url: state.config().canonical_url(),
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -143,10 +169,19 @@ async fn bypass_download_zip_cors(
 struct AniListTemplate {
     account: Option<Account>,
     user_name: String,
+    url: String,
 }
 
-async fn show_anilist_page(account: Option<Account>, Path(user_name): Path<String>) -> impl IntoResponse {
-    AniListTemplate { account, user_name }
+async fn show_anilist_page(
+    State(state): State<AppState>,
+    account: Option<Account>,
+    Path(user_name): Path<String>,
+) -> impl IntoResponse {
+    AniListTemplate {
+        account,
+        user_name,
+        url: state.config().canonical_url(),
+    }
 }
 
 pub fn all() -> Router<AppState> {
@@ -163,5 +198,6 @@ pub fn all() -> Router<AppState> {
         .merge(audit::routes())
         .merge(relations::routes())
         .merge(opensearch::routes())
+        .merge(notifications::routes())
         .nest("/api", api::routes())
 }
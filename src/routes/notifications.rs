@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::Stream;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    error::ApiError,
+    models::Account,
+    notification::{Notification, NOTIFICATION_EVENT_TYPES},
+    AppState,
+};
+
+async fn get_notifications(
+    State(state): State<AppState>,
+    account: Account,
+) -> Result<Json<Vec<Notification>>, ApiError> {
+    let notifications = state
+        .database()
+        .all(
+            "SELECT * FROM notification WHERE user_id = ? ORDER BY ts DESC, id DESC LIMIT 100",
+            [account.id],
+        )
+        .await?;
+    Ok(Json(notifications))
+}
+
+async fn mark_notification_read(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    account: Account,
+) -> Result<StatusCode, ApiError> {
+    let updated = state
+        .database()
+        .execute(
+            "UPDATE notification SET read_at = COALESCE(read_at, unixepoch()) WHERE id = ? AND user_id = ?",
+            (id, account.id),
+        )
+        .await?;
+    if updated == 0 {
+        return Err(ApiError::not_found("Notification not found."));
+    }
+    state.notifications().publish_unread_count(account.id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Marks every notification as read.
+async fn mark_all_notifications_read(State(state): State<AppState>, account: Account) -> Result<StatusCode, ApiError> {
+    state
+        .database()
+        .execute(
+            "UPDATE notification SET read_at = unixepoch() WHERE user_id = ? AND read_at IS NULL",
+            [account.id],
+        )
+        .await?;
+    state.notifications().publish_unread_count(account.id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The interval between heartbeat events in the notification event stream.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+fn unread_event(count: u64) -> Event {
+    Event::default().data(format!(r#"{{"unread":{count}}}"#))
+}
+
+/// Streams the user's unread notification count whenever it changes.
+///
+/// The current count is sent as soon as the stream is opened.
+async fn notification_events(
+    State(state): State<AppState>,
+    account: Account,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let subscription = state.subscribe_notifications(account.id);
+    let initial = state.get_notification_count(account.id).await.unwrap_or_default();
+    let start = tokio::time::Instant::now() + HEARTBEAT_INTERVAL;
+    let heartbeat = tokio::time::interval_at(start, HEARTBEAT_INTERVAL);
+    let stream = futures_util::stream::unfold(
+        (subscription, heartbeat, Some(initial)),
+        |(mut subscription, mut heartbeat, initial)| async move {
+            if let Some(count) = initial {
+                return Some((Ok(unread_event(count)), (subscription, heartbeat, None)));
+            }
+
+            let event = loop {
+                tokio::select! {
+                    result = subscription.receiver.recv() => match result {
+                        Ok(count) => break unread_event(count),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    },
+                    _ = heartbeat.tick() => break Event::default().event("heartbeat").data("{}"),
+                }
+            };
+            Some((Ok(event), (subscription, heartbeat, None)))
+        },
+    );
+    Sse::new(stream)
+}
+
+/// A mapping of notification event type to whether it's enabled.
+type NotificationPreferences = BTreeMap<String, bool>;
+
+async fn fetch_preferences(state: &AppState, user_id: i64) -> rusqlite::Result<NotificationPreferences> {
+    let mut preferences: NotificationPreferences =
+        NOTIFICATION_EVENT_TYPES.iter().map(|s| (s.to_string(), true)).collect();
+    let stored = state
+        .database()
+        .call(move |conn| -> rusqlite::Result<Vec<(String, bool)>> {
+            let mut stmt =
+                conn.prepare_cached("SELECT event_type, enabled FROM notification_preferences WHERE user_id = ?")?;
+            let rows = stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })
+        .await?;
+    for (event_type, enabled) in stored {
+        if let Some(value) = preferences.get_mut(&event_type) {
+            *value = enabled;
+        }
+    }
+    Ok(preferences)
+}
+
+async fn get_notification_preferences(
+    State(state): State<AppState>,
+    account: Account,
+) -> Result<Json<NotificationPreferences>, ApiError> {
+    Ok(Json(fetch_preferences(&state, account.id).await?))
+}
+
+async fn update_notification_preferences(
+    State(state): State<AppState>,
+    account: Account,
+    Json(payload): Json<NotificationPreferences>,
+) -> Result<Json<NotificationPreferences>, ApiError> {
+    if let Some(unknown) = payload.keys().find(|k| !NOTIFICATION_EVENT_TYPES.contains(&k.as_str())) {
+        return Err(ApiError::new(format!("Unknown notification type {unknown:?}")));
+    }
+
+    let user_id = account.id;
+    state
+        .database()
+        .call(move |conn| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    r#"
+                    INSERT INTO notification_preferences(user_id, event_type, enabled) VALUES (?, ?, ?)
+                    ON CONFLICT(user_id, event_type) DO UPDATE SET enabled = excluded.enabled
+                    "#,
+                )?;
+                for (event_type, enabled) in payload {
+                    stmt.execute((user_id, event_type, enabled))?;
+                }
+            }
+            tx.commit()
+        })
+        .await?;
+    Ok(Json(fetch_preferences(&state, user_id).await?))
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/notifications", get(get_notifications))
+        .route("/notifications/read", post(mark_all_notifications_read))
+        .route("/notifications/sse", get(notification_events))
+        .route("/notifications/:id/read", post(mark_notification_read))
+        .route(
+            "/account/notifications/preferences",
+            get(get_notification_preferences).patch(update_notification_preferences),
+        )
+}
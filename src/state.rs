@@ -1,16 +1,31 @@
+use crossbeam_channel::Sender;
+use dashmap::DashMap;
 use quick_cache::sync::Cache;
-use std::{path::PathBuf, sync::Arc, time::Duration};
-use tokio::sync::{RwLock, RwLockReadGuard};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, RwLock, RwLockReadGuard};
 
 use crate::{
-    audit::AuditLogEntry,
+    audit::{AuditLogEntry, ScrapeSource},
     auth::hash_password,
-    cached::TimedCachedValue,
+    cached::{BodyCache, TimedCache, TimedCachedValue},
     database::Table,
+    japanese::japanese_sort_key,
     logging::RequestLogger,
-    models::{Account, DirectoryEntry, Session},
+    models::{Account, Ban, DirectoryEntry, Session},
+    notification::{NotificationService, NotificationSubscription},
+    ratelimit::RateLimitRegistry,
     relations::Relations,
+    subtitle::SubtitleMeta,
+    tmdb::TmdbCache,
     token::MAX_TOKEN_AGE,
+    utils::transliterate_filename,
     Config, Database,
 };
 
@@ -19,6 +34,8 @@ pub struct SessionInfo {
     pub id: i64,
     pub api_key: bool,
     pub created_at: time::OffsetDateTime,
+    /// When the account's active ban expires, if it has one.
+    pub banned_until: Option<time::OffsetDateTime>,
 }
 
 impl From<Session> for SessionInfo {
@@ -27,6 +44,7 @@ impl From<Session> for SessionInfo {
             id: value.account_id,
             api_key: value.api_key,
             created_at: value.created_at,
+            banned_until: None,
         }
     }
 }
@@ -36,15 +54,138 @@ impl SessionInfo {
     pub fn is_expired(&self) -> bool {
         !self.api_key && time::OffsetDateTime::now_utc() > (self.created_at + MAX_TOKEN_AGE)
     }
+
+    /// Returns `true` if the account had an active ban when the session was cached and it hasn't expired yet
+    pub fn is_banned(&self) -> bool {
+        self.banned_until
+            .is_some_and(|until| time::OffsetDateTime::now_utc() < until)
+    }
+}
+
+/// The state of a scraper, as of its most recent run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScraperState {
+    /// Whether the scraper is currently running.
+    pub running: bool,
+    /// When the last run finished.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub last_run_at: Option<time::OffsetDateTime>,
+    /// The number of entries scraped during the last successful run.
+    pub last_run_entries: usize,
+    /// The error the last run failed with, if any.
+    pub last_error: Option<String>,
 }
 
+/// The maximum number of popular entries that are cached.
+pub const MAX_POPULAR_ENTRIES: usize = 100;
+
+/// Inserts an audit log entry.
+///
+/// The ID is a millisecond timestamp, so entries created within the same millisecond, e.g. from
+/// [`AppState::audit_many`], are bumped to one after the latest ID rather than failing to insert.
+const AUDIT_LOG_INSERT_QUERY: &str = r#"
+    INSERT INTO audit_log(id, entry_id, account_id, data)
+    VALUES ((SELECT MAX(?, COALESCE(MAX(id), 0) + 1) FROM audit_log), ?, ?, ?)
+"#;
+
+/// The maximum number of audit log entries that are buffered before they're written.
+const AUDIT_LOG_BATCH_SIZE: usize = 100;
+
+/// How long audit log entries are buffered before they're written.
+const AUDIT_LOG_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Inserts the audit log entries in a single transaction.
+async fn insert_audit_logs(database: &Database, entries: Vec<AuditLogEntry>) {
+    let err = database
+        .call(move |conn| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare(AUDIT_LOG_INSERT_QUERY)?;
+                for entry in entries {
+                    stmt.execute((entry.id, entry.entry_id, entry.account_id, entry.data))?;
+                }
+            }
+            tx.commit()
+        })
+        .await;
+
+    if let Err(e) = err {
+        tracing::error!(error=%e, "Could not insert audit log entries");
+    }
+}
+
+enum AuditLogMessage {
+    Entry(AuditLogEntry),
+    /// Writes the buffered entries immediately and notifies the sender once done.
+    Flush(tokio::sync::oneshot::Sender<()>),
+}
+
+/// Spawns the thread that batch inserts the audit log entries sent by [`AppState::audit`].
+///
+/// Entries are written every [`AUDIT_LOG_FLUSH_INTERVAL`] or once [`AUDIT_LOG_BATCH_SIZE`]
+/// entries are buffered, whichever comes first. The thread stops once the state is dropped.
+fn spawn_audit_log_worker(state: Weak<InnerState>) -> Sender<AuditLogMessage> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let handle = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let mut buffer = Vec::with_capacity(AUDIT_LOG_BATCH_SIZE);
+        while let Ok(msg) = receiver.recv() {
+            let deadline = Instant::now() + AUDIT_LOG_FLUSH_INTERVAL;
+            let mut next = Some(msg);
+            let mut flushed = None;
+            while let Some(msg) = next.take() {
+                match msg {
+                    AuditLogMessage::Entry(entry) => buffer.push(entry),
+                    AuditLogMessage::Flush(notify) => {
+                        flushed = Some(notify);
+                        break;
+                    }
+                }
+                if buffer.len() < AUDIT_LOG_BATCH_SIZE {
+                    next = receiver.recv_deadline(deadline).ok();
+                }
+            }
+
+            let Some(state) = state.upgrade() else {
+                break;
+            };
+            if !buffer.is_empty() {
+                handle.block_on(insert_audit_logs(&state.database, std::mem::take(&mut buffer)));
+            }
+            if let Some(notify) = flushed {
+                let _ = notify.send(());
+            }
+        }
+    });
+    sender
+}
+
+/// The window in which account registrations are counted per IP.
+const REGISTRATION_WINDOW: Duration = Duration::from_secs(86400);
+
+/// How long subtitle file metadata is cached for.
+const FILE_METADATA_TTL: Duration = Duration::from_secs(60 * 5);
+
 struct InnerState {
-    config: Config,
+    config: std::sync::RwLock<Arc<Config>>,
     database: Database,
     cached_directories: TimedCachedValue<Vec<DirectoryEntry>>,
+    body_cache: BodyCache,
+    /// Popular entries, keyed by whether they're anime or not.
+    popular_entries: TimedCache<bool, Vec<DirectoryEntry>>,
     relations: RwLock<Relations>,
     cached_users: Cache<i64, Account>,
     valid_sessions: Cache<String, SessionInfo>,
+    registrations: DashMap<IpAddr, (u32, Instant)>,
+    /// Per-user channels that receive the user's unread notification count whenever it changes.
+    notification_channels: Arc<DashMap<i64, broadcast::Sender<u64>>>,
+    scrapers: std::sync::RwLock<HashMap<ScrapeSource, ScraperState>>,
+    file_metadata: Cache<PathBuf, (SubtitleMeta, Instant)>,
+    /// The episode count of AniList media, keyed by AniList ID.
+    anilist_episode_counts: TimedCache<u32, Option<u32>>,
+    tmdb_cache: TmdbCache,
+    ratelimits: RateLimitRegistry,
+    audit_logs: Sender<AuditLogMessage>,
 }
 
 /// Global application state for the axum Router.
@@ -58,31 +199,116 @@ pub struct AppState {
 
 impl AppState {
     pub async fn new(config: Config, database: Database) -> Self {
-        let incorrect_default_password_hash =
-            hash_password("incorrect-default-password").expect("could not hash default password");
+        let incorrect_default_password_hash = hash_password("incorrect-default-password", &config.argon2_params)
+            .expect("could not hash default password");
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(600))
             .build()
             .expect("could not build HTTP client");
 
         let requests = RequestLogger::new().expect("could not build request logger");
-        Self {
-            inner: Arc::new(InnerState {
-                config,
+        let relations = Relations::load(&client).await.unwrap_or_default();
+        let state = Self {
+            inner: Arc::new_cyclic(|weak| InnerState {
+                config: std::sync::RwLock::new(Arc::new(config)),
                 database,
-                relations: RwLock::new(Relations::load(&client).await.unwrap_or_default()),
+                relations: RwLock::new(relations),
                 cached_directories: TimedCachedValue::new(Duration::from_secs(60 * 30)),
+                body_cache: BodyCache::new(Duration::from_secs(120)),
+                popular_entries: TimedCache::new(Duration::from_secs(60 * 10)),
                 cached_users: Cache::new(1000),
                 valid_sessions: Cache::new(1000),
+                registrations: DashMap::new(),
+                notification_channels: Arc::new(DashMap::new()),
+                scrapers: std::sync::RwLock::new(HashMap::new()),
+                file_metadata: Cache::new(1000),
+                anilist_episode_counts: TimedCache::new(Duration::from_secs(60 * 10)),
+                tmdb_cache: TmdbCache::new(10_000),
+                ratelimits: RateLimitRegistry::default(),
+                audit_logs: spawn_audit_log_worker(weak.clone()),
             }),
             client,
             requests,
             incorrect_default_password_hash,
+        };
+
+        // Warm up the directory entry cache so the first request doesn't have to wait for it
+        let warm = state.clone();
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let entries = warm.directory_entries().await;
+            tracing::info!(
+                entries = entries.len(),
+                elapsed = ?start.elapsed(),
+                "warmed directory entry cache"
+            );
+        });
+        state
+    }
+
+    /// Returns the current configuration.
+    ///
+    /// Since the configuration can be reloaded, this should not be held onto for long.
+    pub fn config(&self) -> Arc<Config> {
+        self.inner.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns the current state of the given scraper.
+    pub fn scraper_status(&self, source: ScrapeSource) -> ScraperState {
+        let guard = self.inner.scrapers.read().unwrap_or_else(|e| e.into_inner());
+        guard.get(&source).cloned().unwrap_or_default()
+    }
+
+    /// Marks the given scraper as running.
+    ///
+    /// Returns `false` if the scraper is already running, in which case it should not be started.
+    pub fn start_scrape(&self, source: ScrapeSource) -> bool {
+        let mut guard = self.inner.scrapers.write().unwrap_or_else(|e| e.into_inner());
+        let state = guard.entry(source).or_default();
+        if state.running {
+            false
+        } else {
+            state.running = true;
+            true
+        }
+    }
+
+    /// Marks the given scraper as finished with the number of scraped entries or the error it failed with.
+    pub fn finish_scrape(&self, source: ScrapeSource, result: Result<usize, String>) {
+        let mut guard = self.inner.scrapers.write().unwrap_or_else(|e| e.into_inner());
+        let state = guard.entry(source).or_default();
+        state.running = false;
+        state.last_run_at = Some(time::OffsetDateTime::now_utc());
+        match result {
+            Ok(entries) => {
+                state.last_run_entries = entries;
+                state.last_error = None;
+            }
+            Err(e) => state.last_error = Some(e),
         }
     }
 
-    pub fn config(&self) -> &Config {
-        &self.inner.config
+    /// Reloads the configuration from disk.
+    ///
+    /// Only settings that are safe to change while the server is running are updated,
+    /// every other setting is ignored. Returns the names of the settings that changed.
+    pub fn reload_config(&self) -> anyhow::Result<Vec<&'static str>> {
+        let loaded = Config::load()?;
+        let mut guard = self.inner.config.write().unwrap_or_else(|e| e.into_inner());
+        let mut config = Config::clone(&guard);
+        let changed = config.apply_reloadable(loaded);
+        if !changed.is_empty() {
+            *guard = Arc::new(config);
+        }
+        drop(guard);
+
+        let fields = changed.join(", ");
+        tracing::info!(changed = %fields, "reloaded config");
+        self.send_alert(
+            crate::discord::Alert::info("Config Reloaded")
+                .field("Changed", if fields.is_empty() { "Nothing" } else { fields.as_str() }),
+        );
+        Ok(changed)
     }
 
     pub fn database(&self) -> &Database {
@@ -91,36 +317,156 @@ impl AppState {
 
     /// Sends an audit log entry.
     ///
+    /// The entry is buffered and written in a batch by a background thread shortly after.
     /// Errors are silently dropped, since they can't be handled anyway.
     pub async fn audit(&self, entry: AuditLogEntry) {
-        let err = self
-            .database()
-            .execute(
-                "INSERT INTO audit_log(id, entry_id, account_id, data) VALUES (?, ?, ?, ?)",
-                (entry.id, entry.entry_id, entry.account_id, entry.data),
-            )
-            .await;
+        if self.inner.audit_logs.send(AuditLogMessage::Entry(entry)).is_err() {
+            tracing::error!("Could not send audit log entry to the writer thread");
+        }
+    }
 
-        if let Err(e) = err {
-            tracing::error!(error=%e, "Could not insert audit log entry");
+    /// Waits until every audit log entry sent so far is written.
+    ///
+    /// This should be called before exiting so buffered entries aren't lost.
+    pub async fn flush_audit_logs(&self) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        if self.inner.audit_logs.send(AuditLogMessage::Flush(sender)).is_ok() {
+            let _ = receiver.await;
         }
     }
 
+    /// Inserts multiple audit log entries in a single transaction.
+    pub async fn audit_many(&self, entries: Vec<AuditLogEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+
+        insert_audit_logs(self.database(), entries).await;
+    }
+
+    /// Deletes all audit log entries created at or before the given UNIX timestamp in milliseconds.
+    ///
+    /// This is done in batches to avoid holding the write lock for too long.
+    /// Returns the total number of deleted entries.
+    pub async fn prune_audit_logs(&self, before_ms: i64) -> rusqlite::Result<usize> {
+        let mut total = 0;
+        loop {
+            let deleted = self
+                .database()
+                .execute(
+                    "DELETE FROM audit_log WHERE id IN (SELECT id FROM audit_log WHERE id <= ? LIMIT 1000)",
+                    [before_ms],
+                )
+                .await?;
+            total += deleted;
+            if deleted < 1000 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(total)
+    }
+
+    /// Returns the service used to send notifications to users.
+    pub fn notifications(&self) -> NotificationService<'_> {
+        NotificationService::new(self.database(), &self.inner.notification_channels)
+    }
+
+    /// Subscribes to changes of the user's unread notification count.
+    pub fn subscribe_notifications(&self, user_id: i64) -> NotificationSubscription {
+        NotificationSubscription::new(&self.inner.notification_channels, user_id)
+    }
+
     /// Sends an alert webhook with the given webhook payload.
     ///
     /// This sends the request in the background so there's no way to detect
     /// if it failed or not.
-    pub fn send_alert<T: serde::Serialize + Send + 'static>(&self, payload: T) {
-        if let Some(wh) = self.config().webhook.clone() {
+    pub fn send_alert(&self, alert: crate::discord::Alert) {
+        let config = self.config();
+        if let Some(wh) = config.webhook.clone() {
+            let payload = alert.resolve_urls(&config.canonical_url());
             let client = self.client.clone();
             tokio::spawn(async move { wh.prepare(payload).send(&client).await });
         }
     }
 
+    /// Returns how long the given IP has to wait before registering another account.
+    ///
+    /// If the IP is allowed to register then `None` is returned.
+    pub fn registration_retry_after(&self, ip: IpAddr) -> Option<Duration> {
+        let (count, started) = *self.inner.registrations.get(&ip)?;
+        let elapsed = started.elapsed();
+        if elapsed >= REGISTRATION_WINDOW || count < self.config().max_registrations_per_ip_per_day {
+            None
+        } else {
+            Some(REGISTRATION_WINDOW - elapsed)
+        }
+    }
+
+    /// Records a successful account registration from the given IP.
+    pub fn record_registration(&self, ip: IpAddr) {
+        let mut entry = self.inner.registrations.entry(ip).or_insert((0, Instant::now()));
+        let (count, started) = entry.value_mut();
+        if started.elapsed() >= REGISTRATION_WINDOW {
+            *count = 0;
+            *started = Instant::now();
+        }
+        *count += 1;
+    }
+
+    /// Removes registration counts whose window has already passed.
+    pub fn prune_registrations(&self) {
+        self.inner
+            .registrations
+            .retain(|_, (_, started)| started.elapsed() < REGISTRATION_WINDOW);
+    }
+
     pub fn cached_directories(&self) -> &TimedCachedValue<Vec<DirectoryEntry>> {
         &self.inner.cached_directories
     }
 
+    /// Returns the cached metadata of the subtitle file at the given path, unless it expired.
+    pub fn cached_file_metadata(&self, path: &std::path::Path) -> Option<SubtitleMeta> {
+        let (meta, cached_at) = self.inner.file_metadata.get(path)?;
+        (cached_at.elapsed() < FILE_METADATA_TTL).then_some(meta)
+    }
+
+    /// Caches the metadata of the subtitle file at the given path.
+    ///
+    /// Only a bounded number of files are kept, the least recently used ones are evicted first.
+    pub fn cache_file_metadata(&self, path: PathBuf, meta: SubtitleMeta) {
+        self.inner.file_metadata.insert(path, (meta, Instant::now()));
+    }
+
+    /// Returns the episode count of the AniList media, if known.
+    ///
+    /// This is cached for 10 minutes.
+    pub async fn anilist_episode_count(&self, anilist_id: u32) -> anyhow::Result<Option<u32>> {
+        if let Some(count) = self.inner.anilist_episode_counts.get(&anilist_id) {
+            return Ok(*count);
+        }
+
+        let media = crate::anilist::search_by_id(&self.client, anilist_id).await?;
+        let count = media.and_then(|m| m.episodes);
+        self.inner.anilist_episode_counts.insert(anilist_id, count);
+        Ok(count)
+    }
+
+    /// The cache for TMDB search results, keyed by the search query.
+    pub fn tmdb_cache(&self) -> &TmdbCache {
+        &self.inner.tmdb_cache
+    }
+
+    /// The cache for rendered pages.
+    pub fn body_cache(&self) -> &BodyCache {
+        &self.inner.body_cache
+    }
+
+    /// The last known state of every rate limit bucket.
+    pub fn ratelimits(&self) -> &RateLimitRegistry {
+        &self.inner.ratelimits
+    }
+
     pub async fn get_account(&self, id: i64) -> Option<Account> {
         match self.inner.cached_users.get_value_or_guard_async(&id).await {
             Ok(acc) => Some(acc),
@@ -134,6 +480,11 @@ impl AppState {
         }
     }
 
+    /// Returns the number of unread notifications the given user has.
+    pub async fn get_notification_count(&self, user_id: i64) -> rusqlite::Result<u64> {
+        self.notifications().unread_count(user_id).await
+    }
+
     pub fn invalidate_account_cache(&self, id: i64) {
         self.inner.cached_users.remove(&id);
     }
@@ -146,13 +497,29 @@ impl AppState {
         self.inner.valid_sessions.clear();
     }
 
+    /// Returns the account's currently active ban, if any.
+    pub async fn get_active_ban(&self, account_id: i64) -> Option<Ban> {
+        self.database()
+            .get(
+                Ban::select_query("account_id = ? AND expires_at > unixepoch()"),
+                [account_id],
+            )
+            .await
+            .ok()
+            .flatten()
+    }
+
     /// Returns if the session is valid (i.e. in the database or cache).
+    ///
+    /// Sessions of banned accounts are not considered valid.
     pub async fn is_session_valid(&self, session: &str) -> Option<SessionInfo> {
         match self.inner.valid_sessions.get_value_or_guard_async(session).await {
             Ok(info) => {
                 if info.is_expired() {
                     self.invalidate_session(session).await;
                     None
+                } else if info.is_banned() {
+                    None
                 } else {
                     Some(info)
                 }
@@ -165,13 +532,14 @@ impl AppState {
                 .flatten()
             {
                 Some(info) => {
-                    let info = SessionInfo::from(info);
+                    let mut info = SessionInfo::from(info);
                     if info.is_expired() {
                         self.invalidate_session(session).await;
                         None
                     } else {
+                        info.banned_until = self.get_active_ban(info.id).await.map(|b| b.expires_at);
                         let _ = guard.insert(info);
-                        Some(info)
+                        Some(info).filter(|info| !info.is_banned())
                     }
                 }
                 None => None,
@@ -189,6 +557,9 @@ impl AppState {
                     self.invalidate_session(session).await;
                     return None;
                 }
+                if info.is_banned() {
+                    return None;
+                }
                 let account = self.get_account(info.id).await;
                 if account.is_none() {
                     self.inner.valid_sessions.remove(session);
@@ -199,7 +570,8 @@ impl AppState {
                 let query = r#"
                     SELECT account.id AS id, account.name AS name, account.password AS password,
                            account.flags AS flags, session.api_key AS api_key, session.created_at AS created_at,
-                           account.anilist_username AS anilist_username
+                           account.anilist_username AS anilist_username, account.email AS email,
+                           account.email_verified AS email_verified
                     FROM account INNER JOIN session ON session.account_id = account.id
                     WHERE session.id = ? AND session.account_id = ? AND session.api_key = ?
                 "#;
@@ -214,6 +586,7 @@ impl AppState {
                                 id: account.id,
                                 api_key: row.get("api_key")?,
                                 created_at: row.get("created_at")?,
+                                banned_until: None,
                             };
                             Ok((account, info))
                         },
@@ -221,14 +594,15 @@ impl AppState {
                     .await
                     .ok()
                 {
-                    Some((account, info)) => {
+                    Some((account, mut info)) => {
                         if info.is_expired() {
                             self.invalidate_session(session).await;
                             None
                         } else {
+                            info.banned_until = self.get_active_ban(account.id).await.map(|b| b.expires_at);
                             let _ = guard.insert(info);
                             self.inner.cached_users.insert(account.id, account.clone());
-                            Some(account)
+                            Some(account).filter(|_| !info.is_banned())
                         }
                     }
                     None => None,
@@ -250,8 +624,8 @@ impl AppState {
 
     /// Saves the session given by the token to the database
     pub async fn save_session(&self, token: &crate::token::Token, description: Option<String>) {
-        let query =
-            "INSERT INTO session(id, account_id, description, api_key) VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING";
+        let mut query = Session::insert_query(["id", "account_id", "description", "api_key"]);
+        query.push_str(" ON CONFLICT DO NOTHING");
         let _ = self
             .database()
             .execute(query, (token.base64(), token.id, description, token.api_key))
@@ -273,14 +647,14 @@ impl AppState {
         }
     }
 
-    pub async fn generate_api_key(&self, id: i64) -> anyhow::Result<String> {
+    pub async fn generate_api_key(&self, id: i64, description: String) -> anyhow::Result<String> {
         let mut token = crate::token::Token::new(id)?;
         token.api_key = true;
         let key = token.base64();
         self.database()
             .execute(
-                "INSERT INTO session(id, account_id, description, api_key) VALUES (?, ?, 'API Key', 1)",
-                (key.clone(), id),
+                Session::insert_query(["id", "account_id", "description", "api_key"]),
+                (key.clone(), id, description, true),
             )
             .await?;
         Ok(key)
@@ -315,21 +689,108 @@ impl AppState {
         }
     }
 
-    pub async fn directory_entries(&self) -> RwLockReadGuard<'_, Vec<DirectoryEntry>> {
-        {
-            let reader = self.inner.cached_directories.get().await;
-            if let Some(lock) = reader {
-                return lock;
-            }
+    /// Removes every session of the account from the cache without invalidating them.
+    ///
+    /// This is used when the ban state of the account changes.
+    pub async fn uncache_account_sessions(&self, id: i64) {
+        let sessions: Vec<Session> = self
+            .database()
+            .all(Session::select_query("account_id = ?"), [id])
+            .await
+            .unwrap_or_default();
+
+        for session in sessions {
+            self.inner.valid_sessions.remove(&session.id);
+        }
+    }
+
+    pub async fn directory_entries(&self) -> Arc<Vec<DirectoryEntry>> {
+        if let Some(entries) = self.inner.cached_directories.value() {
+            return entries;
         }
 
         // Cache miss
-        let entries = self
+        let mut entries: Vec<DirectoryEntry> = match self.database().all(DirectoryEntry::select_query(""), []).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!(error=%e, "could not load directory entries");
+                Vec::new()
+            }
+        };
+        for entry in entries.iter_mut() {
+            entry.sort_key = japanese_sort_key(&entry.name);
+            entry.transliterated_japanese_name = entry.japanese_name.as_deref().map(transliterate_filename);
+        }
+        entries.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+        self.inner.cached_directories.set(entries)
+    }
+
+    /// Recounts the files of every directory entry from disk and updates the stored counts.
+    ///
+    /// Returns the number of entries whose count changed.
+    pub async fn sync_file_counts(&self) -> rusqlite::Result<usize> {
+        let mut updated = 0;
+        for entry in self.directory_entries().await.iter() {
+            let Ok(count) = entry.file_count().await else {
+                continue;
+            };
+            if count as u32 != entry.file_count {
+                updated += self
+                    .database()
+                    .execute(
+                        "UPDATE directory_entry SET file_count = ? WHERE id = ?",
+                        (count as u32, entry.id),
+                    )
+                    .await?;
+            }
+        }
+        if updated != 0 {
+            self.cached_directories().invalidate();
+        }
+        Ok(updated)
+    }
+
+    /// Returns the most downloaded entries, with the most downloaded first.
+    ///
+    /// This returns [`None`] if download tracking is not enabled. Hidden entries
+    /// are not included. The result is cached for 10 minutes.
+    pub async fn popular_entries(&self, anime: bool) -> anyhow::Result<Option<Arc<Vec<DirectoryEntry>>>> {
+        if let Some(entries) = self.inner.popular_entries.get(&anime) {
+            return Ok(Some(entries));
+        }
+
+        let enabled: bool = self
             .database()
-            .all("SELECT * FROM directory_entry ORDER BY name ASC", [])
-            .await
-            .unwrap_or_default();
-        self.inner.cached_directories.set(entries).await
+            .get_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'file_download')",
+                [],
+                |row| row.get(0),
+            )
+            .await?;
+        if !enabled {
+            return Ok(None);
+        }
+
+        let ids = self
+            .database()
+            .call(|conn| -> rusqlite::Result<Vec<i64>> {
+                let mut stmt = conn.prepare_cached(
+                    "SELECT entry_id, SUM(count) AS total FROM file_download GROUP BY entry_id ORDER BY total DESC",
+                )?;
+                let rows = stmt.query_map([], |row| row.get(0))?;
+                rows.collect()
+            })
+            .await?;
+
+        let all = self.directory_entries().await;
+        let entries = ids
+            .into_iter()
+            .filter_map(|id| all.iter().find(|e| e.id == id))
+            .filter(|e| e.flags.is_anime() == anime && !e.flags.is_hidden())
+            .take(MAX_POPULAR_ENTRIES)
+            .cloned()
+            .collect::<Vec<_>>();
+        Ok(Some(self.inner.popular_entries.insert(anime, entries)))
     }
 
     /// Gets the directory by ID via cache, if available.
@@ -339,7 +800,7 @@ impl AppState {
     ///
     /// All errors are coerced into None.
     pub async fn get_directory_entry(&self, id: i64) -> Option<DirectoryEntry> {
-        if let Some(guard) = self.cached_directories().get().await {
+        if let Some(guard) = self.cached_directories().value() {
             let found = guard.iter().find(|x| x.id == id);
             // Cache hit, return a copy
             if found.is_some() {
@@ -350,12 +811,34 @@ impl AppState {
         self.database().get_by_id(id).await.ok().flatten()
     }
 
+    /// Gets the directory entry by its ID if the given account is allowed to see it.
+    ///
+    /// Hidden entries are only visible to editors.
+    pub async fn get_visible_directory_entry(&self, id: i64, account_id: Option<i64>) -> Option<DirectoryEntry> {
+        let entry = self.get_directory_entry(id).await?;
+        if entry.flags.is_hidden() {
+            let account = self.get_account(account_id?).await?;
+            if !account.flags.is_editor() {
+                return None;
+            }
+        }
+        Some(entry)
+    }
+
+    /// Gets the directory entry by its name via cache.
+    ///
+    /// The cache holds every entry and is loaded from the database if it's empty,
+    /// so a miss means there's no entry with this name.
+    pub async fn get_directory_entry_by_name(&self, name: &str) -> Option<DirectoryEntry> {
+        self.directory_entries().await.iter().find(|e| e.name == name).cloned()
+    }
+
     /// Gets the directory entry's path.
     ///
     /// This is a small optimisation to avoid cloning the entire [`DirectoryEntry`] struct
     /// when the only thing needed is the path.
     pub async fn get_directory_entry_path(&self, id: i64) -> Option<PathBuf> {
-        if let Some(guard) = self.cached_directories().get().await {
+        if let Some(guard) = self.cached_directories().value() {
             let found = guard.iter().find(|x| x.id == id);
             // Cache hit, return a copy
             if let Some(hit) = found {
@@ -374,7 +857,7 @@ impl AppState {
 
     /// Gets the directory entry's path by its AniList ID.
     pub async fn get_anilist_directory_entry_path(&self, id: u32) -> Option<PathBuf> {
-        if let Some(guard) = self.cached_directories().get().await {
+        if let Some(guard) = self.cached_directories().value() {
             let found = guard.iter().find(|x| x.anilist_id == Some(id));
             // Cache hit, return a copy
             if let Some(hit) = found {
@@ -393,7 +876,7 @@ impl AppState {
 
     /// Gets the directory entry's path by its TMDB ID.
     pub async fn get_tmdb_directory_entry_path(&self, id: crate::tmdb::Id) -> Option<PathBuf> {
-        if let Some(guard) = self.cached_directories().get().await {
+        if let Some(guard) = self.cached_directories().value() {
             let found = guard.iter().find(|x| x.tmdb_id == Some(id));
             // Cache hit, return a copy
             if let Some(hit) = found {
@@ -415,9 +898,15 @@ impl AppState {
         self.inner.relations.read().await
     }
 
+    /// Records TMDB IDs that belong to the same series in the relations bound to this state
+    pub async fn add_tmdb_relations(&self, ids: &[crate::tmdb::Id]) {
+        self.inner.relations.write().await.add_tmdb_group(ids);
+    }
+
     /// Updates the anime relations to the one given
-    pub async fn set_anime_relations(&self, relations: Relations) {
+    pub async fn set_anime_relations(&self, mut relations: Relations) {
         let mut guard = self.inner.relations.write().await;
+        relations.take_tmdb_relations(&mut guard);
         *guard = relations;
     }
 }
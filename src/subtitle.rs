@@ -0,0 +1,282 @@
+//! Basic statistics and previews of subtitle files.
+//!
+//! This is not a full subtitle parser, it only understands enough of SRT and ASS
+//! to count the lines, figure out the time span of the file, and show a preview.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Metadata about a subtitle file.
+///
+/// Only `size` is filled for files that aren't text based subtitles, e.g. archives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubtitleMeta {
+    /// The number of subtitle lines, i.e. SRT cues or ASS dialogue events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<usize>,
+    /// The earliest start time, formatted as an SRT timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// The latest end time, formatted as an SRT timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// The file size in bytes.
+    pub size: u64,
+    /// The detected text encoding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+}
+
+/// The subtitle formats that are understood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Srt,
+    Ass,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "srt" => Some(Self::Srt),
+            "ass" | "ssa" => Some(Self::Ass),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the file contents, returning the text and the detected encoding.
+///
+/// Encodings other than UTF-8 and UTF-16 are decoded lossily, which is fine since
+/// only the ASCII timestamps are needed.
+fn decode(bytes: &[u8]) -> (String, &'static str) {
+    fn from_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|c| to_u16([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    }
+
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (String::from_utf8_lossy(rest).into_owned(), "utf-8")
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        (from_utf16(rest, u16::from_le_bytes), "utf-16le")
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        (from_utf16(rest, u16::from_be_bytes), "utf-16be")
+    } else {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => (s.to_owned(), "utf-8"),
+            Err(_) => (String::from_utf8_lossy(bytes).into_owned(), "unknown"),
+        }
+    }
+}
+
+/// Parses a timestamp in the `H:MM:SS,mmm` (SRT) or `H:MM:SS.cc` (ASS) format into milliseconds.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (hms, fraction) = s.trim().split_once([',', '.'])?;
+    let mut parts = hms.split(':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: u64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || fraction.is_empty() || fraction.len() > 3 {
+        return None;
+    }
+    // .5 is 500ms, .05 is 50ms, and .005 is 5ms
+    let millis = fraction.parse::<u64>().ok()? * 10u64.pow(3 - fraction.len() as u32);
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Formats milliseconds as an SRT timestamp, e.g. `01:23:45,678`.
+fn format_timestamp(ms: u64) -> String {
+    let (hours, rest) = (ms / 3_600_000, ms % 3_600_000);
+    let (minutes, rest) = (rest / 60_000, rest % 60_000);
+    let (seconds, millis) = (rest / 1000, rest % 1000);
+    format!("{hours:02}:{minutes:02}:{seconds:02},{millis:03}")
+}
+
+/// Returns the start and end time of every subtitle line in the text.
+fn parse_timings(text: &str, format: Format) -> Vec<(u64, u64)> {
+    let mut timings = Vec::new();
+    for line in text.lines() {
+        let timing = match format {
+            Format::Srt => line
+                .split_once("-->")
+                .and_then(|(start, end)| Some((parse_timestamp(start)?, parse_timestamp(end)?))),
+            Format::Ass => line.strip_prefix("Dialogue:").and_then(|rest| {
+                // Dialogue: Layer, Start, End, ...
+                let mut fields = rest.splitn(4, ',').skip(1);
+                Some((parse_timestamp(fields.next()?)?, parse_timestamp(fields.next()?)?))
+            }),
+        };
+        timings.extend(timing);
+    }
+    timings
+}
+
+/// The maximum number of non-empty lines in a preview.
+const MAX_PREVIEW_LINES: usize = 50;
+
+/// The maximum number of SRT cues or ASS dialogue lines in a preview.
+const MAX_PREVIEW_EVENTS: usize = 15;
+
+/// Builds a preview of the subtitle text.
+///
+/// For SRT this is the first few cue blocks. For ASS this is the `[Script Info]`
+/// section followed by the first few `Dialogue:` lines.
+fn build_preview(text: &str, format: Format) -> String {
+    let mut output = String::new();
+    let mut lines = 0;
+    let mut push = |output: &mut String, line: &str| {
+        output.push_str(line);
+        output.push('\n');
+        lines += 1;
+        lines < MAX_PREVIEW_LINES
+    };
+
+    match format {
+        Format::Srt => {
+            let mut cues = 0;
+            let mut in_cue = false;
+            for line in text.lines() {
+                if line.trim().is_empty() {
+                    in_cue = false;
+                    continue;
+                }
+                if !in_cue {
+                    if cues == MAX_PREVIEW_EVENTS {
+                        break;
+                    }
+                    if cues != 0 {
+                        output.push('\n');
+                    }
+                    cues += 1;
+                    in_cue = true;
+                }
+                if !push(&mut output, line) {
+                    break;
+                }
+            }
+        }
+        Format::Ass => {
+            let mut in_script_info = false;
+            let mut dialogues = 0;
+            for line in text.lines() {
+                let trimmed = line.trim();
+                if trimmed.starts_with('[') {
+                    in_script_info = trimmed.eq_ignore_ascii_case("[Script Info]");
+                }
+                let include = if trimmed.starts_with("Dialogue:") {
+                    if dialogues == 0 && !output.is_empty() {
+                        output.push('\n');
+                    }
+                    dialogues += 1;
+                    true
+                } else {
+                    in_script_info && !trimmed.is_empty()
+                };
+                if include && !push(&mut output, line) {
+                    break;
+                }
+                if dialogues == MAX_PREVIEW_EVENTS {
+                    break;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Reads the subtitle file and returns a plain text preview of it.
+///
+/// Returns [`None`] if the file is not a text based subtitle, e.g. an archive.
+/// This does blocking I/O so it should be run in [`tokio::task::spawn_blocking`].
+pub fn read_preview(path: &Path) -> std::io::Result<Option<String>> {
+    let Some(format) = Format::from_path(path) else {
+        return Ok(None);
+    };
+    let (text, _) = decode(&std::fs::read(path)?);
+    Ok(Some(build_preview(&text, format)))
+}
+
+/// Reads the subtitle file and returns its metadata.
+///
+/// This does blocking I/O so it should be run in [`tokio::task::spawn_blocking`].
+pub fn read_metadata(path: &Path) -> std::io::Result<SubtitleMeta> {
+    let size = path.metadata()?.len();
+    let Some(format) = Format::from_path(path) else {
+        return Ok(SubtitleMeta {
+            lines: None,
+            start: None,
+            end: None,
+            size,
+            encoding: None,
+        });
+    };
+
+    let (text, encoding) = decode(&std::fs::read(path)?);
+    let timings = parse_timings(&text, format);
+    Ok(SubtitleMeta {
+        lines: Some(timings.len()),
+        start: timings.iter().map(|(start, _)| *start).min().map(format_timestamp),
+        end: timings.iter().map(|(_, end)| *end).max().map(format_timestamp),
+        size,
+        encoding: Some(encoding),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:00:01,000"), Some(1000));
+        assert_eq!(parse_timestamp("01:23:45,678"), Some(5_025_678));
+        assert_eq!(parse_timestamp("0:00:01.50"), Some(1500));
+        assert_eq!(parse_timestamp(" 0:00:01.05 "), Some(1050));
+        assert_eq!(parse_timestamp("00:01,000"), None);
+        assert_eq!(parse_timestamp("garbage"), None);
+        assert_eq!(format_timestamp(5_025_678), "01:23:45,678");
+    }
+
+    #[test]
+    fn test_parse_timings() {
+        let srt = "1\n00:00:01,000 --> 00:00:04,000\nこんにちは\n\n\
+                   2\n00:00:05,500 --> 00:00:07,250\nさようなら\n";
+        assert_eq!(parse_timings(srt, Format::Srt), vec![(1000, 4000), (5500, 7250)]);
+
+        let ass = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Dialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,こんにちは, 世界\n\
+                   Comment: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,comment\n";
+        assert_eq!(parse_timings(ass, Format::Ass), vec![(1000, 4000)]);
+    }
+
+    #[test]
+    fn test_build_preview() {
+        let mut srt = String::new();
+        for i in 1..=20 {
+            srt.push_str(&format!(
+                "{i}\r\n00:00:{i:02},000 --> 00:00:{i:02},500\r\nline {i}\r\n\r\n"
+            ));
+        }
+        let preview = build_preview(&srt, Format::Srt);
+        assert!(preview.starts_with("1\n00:00:01,000 --> 00:00:01,500\nline 1\n\n2\n"));
+        assert!(preview.ends_with("line 15\n"));
+        assert_eq!(preview.lines().filter(|l| !l.is_empty()).count(), 45);
+
+        let ass = "[Script Info]\nTitle: Test\nScriptType: v4.00+\n\n[V4+ Styles]\nStyle: Default\n\n[Events]\n\
+                   Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,comment\n\
+                   Dialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,こんにちは\n";
+        assert_eq!(
+            build_preview(ass, Format::Ass),
+            "[Script Info]\nTitle: Test\nScriptType: v4.00+\n\n\
+             Dialogue: 0,0:00:01.00,0:00:04.00,Default,,0,0,0,,こんにちは\n"
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"\xEF\xBB\xBFabc"), ("abc".to_owned(), "utf-8"));
+        assert_eq!(decode(b"\xFF\xFEa\x00b\x00"), ("ab".to_owned(), "utf-16le"));
+        assert_eq!(decode(b"\x82\xa0"), ("\u{FFFD}\u{FFFD}".to_owned(), "unknown"));
+    }
+}
@@ -1,5 +1,10 @@
-use std::{str::FromStr, sync::OnceLock};
+use std::{
+    str::FromStr,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
+use quick_cache::sync::Cache;
 use regex::Regex;
 use rusqlite::{types::FromSql, ToSql};
 use serde::{Deserialize, Serialize};
@@ -9,14 +14,20 @@ use crate::{anilist::MediaTitle, borrowed::MaybeBorrowedString, japanese::is_jap
 fn url_parser_regex() -> &'static Regex {
     static REGEX: OnceLock<Regex> = OnceLock::new();
     REGEX.get_or_init(|| {
-        Regex::new(r#"https://(?:www\.)?themoviedb\.org/(tv|movie)/(\d+)(?:-[a-zA-Z0-9\-]+)?(/.*)?"#).unwrap()
+        // Each alternative has its own (kind, id) pair of capture groups
+        Regex::new(concat!(
+            r#"(?:https?://)?(?:www\.)?themoviedb\.org/(tv|movie|tv-show)/(\d+)"#,
+            r#"|(?:https?://)?tmdb\.to/3/(tv|movie)/(\d+)"#,
+            r#"|^(tv|movie):(\d+)$"#,
+        ))
+        .unwrap()
     })
 }
 
 /// A TMDB ID.
 ///
 /// These are scoped depending on the series.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Id {
     /// The type of the TMDB ID represents a TV series.
     Tv {
@@ -30,12 +41,18 @@ pub enum Id {
     },
 }
 
+/// Parses a TMDB ID out of a URL.
+///
+/// This understands `themoviedb.org` URLs, `tmdb.to` short links, and
+/// bare IDs such as `tv:12345`.
 pub fn get_tmdb_id(url: &str) -> Option<Id> {
-    let captures = url_parser_regex().captures(url)?;
-    let kind = captures.get(1)?.as_str();
-    let id = captures.get(2)?.as_str().parse().ok()?;
+    let captures = url_parser_regex().captures(url.trim())?;
+    let (kind, id) = [(1, 2), (3, 4), (5, 6)]
+        .into_iter()
+        .find_map(|(kind, id)| Some((captures.get(kind)?.as_str(), captures.get(id)?.as_str())))?;
+    let id = id.parse().ok()?;
     match kind {
-        "tv" => Some(Id::Tv { id }),
+        "tv" | "tv-show" => Some(Id::Tv { id }),
         "movie" => Some(Id::Movie { id }),
         _ => None,
     }
@@ -168,7 +185,7 @@ impl<'de> Deserialize<'de> for LangCode {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct AlternativeTitle {
     #[serde(rename = "iso_3166_1")]
     lang: LangCode,
@@ -193,7 +210,7 @@ impl AlternativeTitle {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct AlternativeTitles {
     #[serde(alias = "results")]
     titles: Vec<AlternativeTitle>,
@@ -205,7 +222,22 @@ impl AlternativeTitles {
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct CollectionInfo {
+    id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionPart {
+    id: u32,
+}
+
 #[derive(Debug, Deserialize)]
+struct Collection {
+    parts: Vec<CollectionPart>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Info {
     #[serde(skip)]
     pub id: Id,
@@ -219,6 +251,8 @@ pub struct Info {
     )]
     title: String,
     alternative_titles: AlternativeTitles,
+    #[serde(default)]
+    belongs_to_collection: Option<CollectionInfo>,
 }
 
 impl Info {
@@ -257,6 +291,13 @@ impl Info {
     pub fn is_adult(&self) -> bool {
         self.adult
     }
+
+    /// Returns the ID of the collection this movie belongs to, if any.
+    ///
+    /// TV series never belong to a collection, their seasons share the same ID instead.
+    pub fn collection_id(&self) -> Option<u32> {
+        self.belongs_to_collection.as_ref().map(|c| c.id)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -298,7 +339,69 @@ pub async fn get_media_info(client: &reqwest::Client, api_key: &str, id: Id) ->
     Ok(Some(info))
 }
 
-pub async fn find_match(client: &reqwest::Client, api_key: &str, query: &str) -> anyhow::Result<Option<Info>> {
+/// Returns the IDs of the movies that are part of a collection.
+pub async fn get_collection_parts(client: &reqwest::Client, api_key: &str, id: u32) -> anyhow::Result<Vec<Id>> {
+    let mut url = reqwest::Url::parse(&format!("https://api.themoviedb.org/3/collection/{id}"))?;
+    url.query_pairs_mut()
+        .append_pair("language", "en-US")
+        .append_pair("api_key", api_key);
+
+    let resp = client.get(url).header("accept", "application/json").send().await?;
+    if resp.status().as_u16() == 404 {
+        return Ok(Vec::new());
+    }
+
+    let collection = resp.error_for_status()?.json::<Collection>().await?;
+    Ok(collection.parts.into_iter().map(|p| Id::Movie { id: p.id }).collect())
+}
+
+/// How long a cached search result is kept for.
+const SEARCH_CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// A cache of [`find_match`] results, keyed by the search query.
+///
+/// Queries without a match are cached as well so they aren't looked up again.
+pub struct TmdbCache {
+    results: Cache<String, (Option<Info>, Instant)>,
+}
+
+impl TmdbCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            results: Cache::new(capacity),
+        }
+    }
+
+    /// Returns the cached result of the query, or [`None`] if it cannot be found or is expired.
+    pub fn get(&self, query: &str) -> Option<Option<Info>> {
+        let (info, created) = self.results.get(query)?;
+        if created.elapsed() >= SEARCH_CACHE_TTL {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// Sets the result of the query in the cache.
+    pub fn insert(&self, query: String, info: Option<Info>) {
+        self.results.insert(query, (info, Instant::now()));
+    }
+}
+
+/// Searches TMDB for the best match of the query.
+///
+/// If a cache is given then it's checked first and updated with the result. Failed
+/// requests, e.g. due to rate limiting, are not cached.
+pub async fn find_match(
+    client: &reqwest::Client,
+    api_key: &str,
+    query: &str,
+    cache: Option<&TmdbCache>,
+) -> anyhow::Result<Option<Info>> {
+    if let Some(hit) = cache.and_then(|c| c.get(query)) {
+        return Ok(hit);
+    }
+
     let mut url = reqwest::Url::parse("https://api.themoviedb.org/3/search/multi")?;
     url.query_pairs_mut()
         .append_pair("query", query)
@@ -313,12 +416,42 @@ pub async fn find_match(client: &reqwest::Client, api_key: &str, query: &str) ->
     }
 
     let mut info = resp.json::<PagedSearchResults>().await?.results;
-    if info.is_empty() {
-        Ok(None)
+    let result = if info.is_empty() {
+        None
     } else {
         match info.swap_remove(0).to_id() {
-            Some(id) => get_media_info(client, api_key, id).await,
-            None => Ok(None),
+            Some(id) => get_media_info(client, api_key, id).await?,
+            None => None,
         }
+    };
+
+    if let Some(cache) = cache {
+        cache.insert(query.to_owned(), result.clone());
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_tmdb_id() {
+        let tv = Some(Id::Tv { id: 12345 });
+        let movie = Some(Id::Movie { id: 678 });
+        assert_eq!(get_tmdb_id("https://www.themoviedb.org/tv/12345"), tv);
+        assert_eq!(get_tmdb_id("https://www.themoviedb.org/tv/12345-frieren/season/1"), tv);
+        assert_eq!(
+            get_tmdb_id("https://themoviedb.org/movie/678-your-name?language=ja"),
+            movie
+        );
+        assert_eq!(get_tmdb_id("https://www.themoviedb.org/tv-show/12345"), tv);
+        assert_eq!(get_tmdb_id("https://tmdb.to/3/tv/12345"), tv);
+        assert_eq!(get_tmdb_id("tmdb.to/3/movie/678"), movie);
+        assert_eq!(get_tmdb_id("tv:12345"), tv);
+        assert_eq!(get_tmdb_id(" movie:678 "), movie);
+        assert_eq!(get_tmdb_id("https://www.themoviedb.org/person/12345"), None);
+        assert_eq!(get_tmdb_id("anime:12345"), None);
+        assert_eq!(get_tmdb_id("12345"), None);
     }
 }
@@ -95,6 +95,13 @@ impl Trash {
         .map_err(std::io::Error::other)?
     }
 
+    /// Returns the entry ID a trashed file belongs to.
+    ///
+    /// The filename must be one returned by [`Self::list`].
+    pub fn entry_id(filename: &str) -> Option<i64> {
+        filename.split_once('_').and_then(|(id, _)| id.parse().ok())
+    }
+
     /// Returns everything that is in the trash
     pub async fn list(&self) -> std::io::Result<TrashListing> {
         let reader = self.info.read_dir()?;
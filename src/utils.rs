@@ -1,8 +1,17 @@
-use std::{path::PathBuf, str::FromStr, sync::OnceLock};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    str::FromStr,
+    sync::OnceLock,
+    time::Duration,
+};
 
 use percent_encoding::{AsciiSet, CONTROLS};
 use regex::Regex;
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::japanese::{kana_to_romaji, normalize_diacritics};
 
 /// The maximum amount of bytes an upload can have, in bytes.
 pub const MAX_UPLOAD_SIZE: u64 = 1024 * 1024 * 16;
@@ -20,6 +29,60 @@ pub const FRAGMENT: &AsciiSet = &CONTROLS
     .add(b';')
     .add(b'%');
 
+/// The delays between attempts in [`http_get_with_retry`], the last one is reused if there are more retries.
+const RETRY_DELAYS: [Duration; 2] = [Duration::from_secs(2), Duration::from_secs(10)];
+
+/// The longest a `Retry-After` header is honoured for.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Returns how long to wait before the given retry, starting from 1.
+fn retry_delay(retry: usize, retry_after: Option<&str>) -> Duration {
+    match retry_after.and_then(|s| s.trim().parse::<u64>().ok()) {
+        Some(secs) => Duration::from_secs(secs).min(MAX_RETRY_AFTER),
+        None => RETRY_DELAYS[retry.clamp(1, RETRY_DELAYS.len()) - 1],
+    }
+}
+
+/// Sends a GET request with the scraper user agent and returns the response body.
+///
+/// Server errors and 429 responses are retried up to `max_retries` times, waiting for the
+/// duration in the `Retry-After` header if there is one. Other error responses are not retried.
+pub async fn http_get_with_retry(client: &reqwest::Client, url: &str, max_retries: usize) -> anyhow::Result<String> {
+    let mut retry = 0;
+    loop {
+        let resp = client
+            .get(url)
+            .header(
+                reqwest::header::USER_AGENT,
+                reqwest::header::HeaderValue::from_static(crate::kitsunekko::USER_AGENT),
+            )
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if status.is_success() {
+            if retry > 0 {
+                tracing::info!("Retried successfully after attempt {}", retry + 1);
+            }
+            return Ok(resp.text().await?);
+        }
+
+        let retryable = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+        if !retryable || retry >= max_retries {
+            anyhow::bail!("GET {url} failed with status {status} after {} attempt(s)", retry + 1);
+        }
+
+        retry += 1;
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok());
+        let delay = retry_delay(retry, retry_after);
+        tracing::warn!(%status, url, retry, ?delay, "request failed, retrying");
+        tokio::time::sleep(delay).await;
+    }
+}
+
 /// This is mainly for use in forms.
 ///
 /// Since forms always receive strings, this uses FromStr for the internal type.
@@ -60,6 +123,61 @@ pub fn get_anilist_id(url: &str) -> Option<u32> {
     anilist_id_regex().captures(url)?.get(1)?.as_str().parse().ok()
 }
 
+fn file_extension_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"\.[a-z0-9]{2,4}$"#).unwrap())
+}
+
+fn filename_noise_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(concat!(
+            // Bracketed groups, e.g. [SubsPlease] or (1080p)
+            r#"\[[^\]]*\]|\([^)]*\)"#,
+            // Quality tags
+            r#"|\b(?:\d{3,4}p|[hx]\s?26[45]|hevc|avc|aac|flac|10-?bit)\b"#,
+            r#"|\b(?:web-?(?:dl|rip)|bd-?rip|blu-?ray|dvd-?rip|hdtv)\b"#,
+            // Episode numbers, e.g. S01E01, ep01, #01, 第1話 or - 01
+            r#"|\bs\d{1,2}e\d{1,4}\b|\bep?(?:isode)?\s?\d{1,4}\b"#,
+            r#"|#\s?\d{1,4}|第\d{1,4}[話回]|\s-\s*\d{1,4}(?:v\d)?\b"#,
+        ))
+        .unwrap()
+    })
+}
+
+/// Normalizes a subtitle filename for search indexing and fuzzy matching.
+///
+/// This applies Unicode NFKC normalization (which also turns full-width digits and
+/// punctuation into ASCII), normalizes diacritics, converts kana to romaji, removes
+/// the file extension, episode numbers and quality tags, and lowercases the result.
+///
+/// For example, `[SubsPlease] Sousou no Frieren - 01 (1080p).ass` becomes `sousou no frieren`.
+/// Kanji have no single reading so they are kept as-is.
+pub fn transliterate_filename(filename: &str) -> String {
+    let normalized = filename
+        .nfkc()
+        .map(|c| match c {
+            '【' => '[',
+            '】' => ']',
+            '\u{3000}' | '・' | '「' | '」' | '『' | '』' => ' ',
+            _ => c,
+        })
+        .collect::<String>();
+    let romaji = kana_to_romaji(&normalize_diacritics(&normalized)).to_lowercase();
+    let stripped = file_extension_regex().replace(&romaji, "").replace(['_', '.'], " ");
+    let cleaned = filename_noise_regex().replace_all(&stripped, " ");
+    join_iter(" ", cleaned.split_whitespace())
+}
+
+/// Returns the episode number of the file, if any.
+///
+/// If the file has an episode range, e.g. a batch archive, then the first episode is returned.
+pub fn extract_episode_number(filename: &str) -> Option<u16> {
+    let parsed = anitomy::parse(filename);
+    let episode = parsed.iter().find(|e| e.kind() == anitomy::ElementKind::Episode)?;
+    episode.value().parse().ok()
+}
+
 pub fn is_over_length<T: AsRef<str>>(opt: &Option<T>, length: usize) -> bool {
     opt.as_ref().map(|x| x.as_ref().len() >= length).unwrap_or_default()
 }
@@ -76,6 +194,46 @@ pub fn join_iter<T: ToString>(sep: impl AsRef<str>, mut iter: impl Iterator<Item
     buffer
 }
 
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        // 0.0.0.0/8 is "this network"
+        || a == 0
+        // 100.64.0.0/10 is shared address space used for carrier-grade NAT
+        || (a == 100 && (b & 0xc0) == 64))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(v4);
+    }
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 are unique local addresses
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 are link-local addresses
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// Checks whether the IP is publicly routable.
+///
+/// This is used to prevent requests made on behalf of users from reaching
+/// internal services, e.g. loopback, private, or link-local addresses.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
 /// Returns the directory where logs are stored
 pub fn logs_directory() -> PathBuf {
     dirs::state_dir()
@@ -195,3 +353,88 @@ where
         Option::deserialize(deserializer).map(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transliterate_filename() {
+        assert_eq!(
+            transliterate_filename("[SubsPlease] Sousou no Frieren - 01 (1080p).ass"),
+            "sousou no frieren"
+        );
+        assert_eq!(
+            transliterate_filename("ぼっち・ざ・ろっく！＃０１.srt"),
+            "botchi za rokku!"
+        );
+        assert_eq!(
+            transliterate_filename("Kimetsu_no_Yaiba_S01E05_1080p_WEB-DL.srt"),
+            "kimetsu no yaiba"
+        );
+        assert_eq!(transliterate_filename("Tendō.Episode.3.srt"), "tendou");
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        let public = ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111", "::ffff:1.1.1.1"];
+        for ip in public {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip} should be public");
+        }
+
+        let internal = [
+            // loopback
+            "127.0.0.1",
+            "::1",
+            // private
+            "10.0.0.1",
+            "172.16.5.4",
+            "192.168.1.1",
+            "fd12:3456::1",
+            // link-local, including cloud metadata endpoints
+            "169.254.169.254",
+            "fe80::1",
+            // unspecified
+            "0.0.0.0",
+            "::",
+            // multicast
+            "224.0.0.1",
+            "ff02::1",
+            // carrier-grade NAT
+            "100.64.0.1",
+            // IPv4-mapped IPv6
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::ffff:10.0.0.1",
+        ];
+        for ip in internal {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip} should not be public");
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1, None), Duration::from_secs(2));
+        assert_eq!(retry_delay(2, None), Duration::from_secs(10));
+        assert_eq!(retry_delay(3, None), Duration::from_secs(10));
+        assert_eq!(retry_delay(1, Some("5")), Duration::from_secs(5));
+        assert_eq!(retry_delay(1, Some("3600")), MAX_RETRY_AFTER);
+        assert_eq!(
+            retry_delay(2, Some("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn test_extract_episode_number() {
+        assert_eq!(
+            extract_episode_number("[SubsPlease] Sousou no Frieren - 01 (1080p).ass"),
+            Some(1)
+        );
+        assert_eq!(
+            extract_episode_number("Kimetsu_no_Yaiba_S01E05_1080p_WEB-DL.srt"),
+            Some(5)
+        );
+        assert_eq!(extract_episode_number("Sousou no Frieren.zip"), None);
+    }
+}
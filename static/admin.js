@@ -326,3 +326,14 @@ document.getElementById('update-anime-relations')?.addEventListener('click', asy
   });
   await updateAnimeRelations();
 });
+
+document.getElementById('create-invite')?.addEventListener('click', async () => {
+  let js = await callApi('/admin/invites', {
+    method: 'POST',
+    headers: {'content-type': 'application/json'},
+    body: JSON.stringify({}),
+  });
+  if (js) {
+    document.getElementById('invite-code').textContent = js.code;
+  }
+});
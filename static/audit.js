@@ -154,11 +154,23 @@ const auditLogTypes = Object.freeze({
       }
       let original_href = getOriginalHref(data.source, d.original_name);
       let original = html('a.original', d.original_name, {href: original_href});
+      if(d.failed) {
+        return html('li.file', link, ' (Original: ', original, ')', {class: 'failed'});
+      }
       return html('li', link, ' (Original: ', original, ')');
     });
     let contents = elements.length === 0 ? null : html('ul', elements);
     return auditLogEntry(log.id, title, contents);
   },
+  scrape_entry: (data, log, info) => {
+    let source = SCRAPE_SOURCES[data.source];
+    let title = [
+      `Scraped ${simplePlural(data.files_downloaded, 'new file')} from ${source} into `,
+      entryLink(log.entry_id, info, data.original_name),
+    ];
+    let original = html('a.original', data.original_name, {href: getOriginalHref(data.source, data.original_name)});
+    return auditLogEntry(log.id, title, html('span.reason', 'Original: ', original));
+  },
   create_entry: (data, log, info) => {
     let title = [
       data.api ? "[API] " : "",
@@ -292,6 +304,44 @@ const auditLogTypes = Object.freeze({
     let contents = html('span.reason', html('strong', 'Reason: '), data.reason);
     return auditLogEntry(log.id, title, contents);
   },
+  login: (data, log, info) => {
+    let title = [
+      userLink(log.account_id, info),
+      data.api_key ? " created an API key" : " logged in",
+    ];
+    let contents = data.ip != null ? html('span.reason', html('strong', 'IP: '), data.ip) : null;
+    return auditLogEntry(log.id, title, contents);
+  },
+  logout: (data, log, info) => {
+    let title = [
+      userLink(log.account_id, info),
+      data.all_sessions ? " logged out of all sessions" : " logged out",
+    ];
+    return auditLogEntry(log.id, title, null);
+  },
+  login_failed: (data, log, info) => {
+    let title = [
+      log.account_id != null ? userLink(log.account_id, info) : html('span.fallback', 'Unknown user'),
+      " failed to log in",
+    ];
+    let contents = html('span.reason', html('strong', 'Reason: '), data.reason);
+    return auditLogEntry(log.id, title, contents);
+  },
+  edit_tags: (data, log, info) => {
+    let title = [
+      userLink(log.account_id, info),
+      ' edited the tags of ',
+      entryLink(log.entry_id, info),
+    ];
+    let contents = [];
+    if(data.added.length !== 0) {
+      contents.push(html('span.reason', html('strong', 'Added: '), data.added.join(', ')));
+    }
+    if(data.removed.length !== 0) {
+      contents.push(html('span.reason', html('strong', 'Removed: '), data.removed.join(', ')));
+    }
+    return auditLogEntry(log.id, title, contents);
+  },
   trash_action: (data, log, info) => {
     let title = [
       userLink(log.account_id, info),
@@ -301,6 +351,32 @@ const auditLogTypes = Object.freeze({
     let files = data.files.map(fileToElement);
     return auditLogEntry(log.id, title, html('ul', files));
   },
+  restore_files: (data, log, info) => {
+    let title = [
+      userLink(log.account_id, info),
+      " restored ",
+      simplePlural(data.files.length, 'file'),
+      " in ",
+      entryLink(data.entry_id, info),
+    ];
+    let contents = [
+      html('span.reason', html('strong', 'Restored from trash')),
+      html('ul', data.files.map(fileToElement)),
+    ];
+    return auditLogEntry(log.id, title, contents);
+  },
+  bulk_edit: (data, log, info) => {
+    let title = [
+      userLink(log.account_id, info),
+      " bulk edited ",
+      data.affected_ids.length === 1 ? '1 entry' : `${data.affected_ids.length} entries`,
+    ];
+    let contents = [
+      html('span.reason', html('strong', 'Change: '), data.sql_template),
+      html('ul', data.affected_ids.map(id => html('li', entryLink(id, info)))),
+    ];
+    return auditLogEntry(log.id, title, contents);
+  },
   edit_entry: (data, log, info) => {
     let title = [
       userLink(log.account_id, info),
@@ -401,9 +477,10 @@ async function getAuditLogs(before) {
     return;
   }
 
+  let nextCursor = response.headers.get('x-next-cursor');
   let data = await response.json();
   await processData(data);
-  if(data.logs.length != 100) {
+  if(nextCursor === null) {
     if(before) {
       loadMore.disabled = true;
       loadMore.textContent = "No more entries";
@@ -415,10 +492,24 @@ async function getAuditLogs(before) {
     }
   } else {
     loadMore.textContent = "Load more";
-    loadMore.dataset.lastId = data.logs[data.logs.length - 1].id;
+    loadMore.dataset.lastId = nextCursor;
     loadMore.disabled = false;
   }
 }
 
-document.addEventListener('DOMContentLoaded', () => getAuditLogs());
-loadMore.addEventListener('click', () => getAuditLogs(loadMore.dataset.lastId))
+const loadNextPage = () => {
+  if(!loadMore.disabled && loadMore.dataset.lastId) {
+    getAuditLogs(loadMore.dataset.lastId);
+  }
+};
+
+// Load the next page once the user scrolls to the bottom
+const scrollObserver = new IntersectionObserver((entries) => {
+  if(entries.some(e => e.isIntersecting)) loadNextPage();
+});
+
+document.addEventListener('DOMContentLoaded', () => {
+  getAuditLogs();
+  scrollObserver.observe(loadMore);
+});
+loadMore.addEventListener('click', loadNextPage);
@@ -66,6 +66,42 @@ toggleEditor?.addEventListener('click', async () => {
   }
 })
 
+const toggleRestricted = document.getElementById('toggle-restricted');
+toggleRestricted?.addEventListener('click', async () => {
+  let restricted = toggleRestricted.dataset.restricted == 'false';
+  let resp = await callApi(toggleRestricted.dataset.endpoint, {
+    method: 'POST',
+    headers: {
+      'content-type': 'application/json',
+    },
+    body: JSON.stringify({restricted})
+  });
+  if (resp !== null) {
+    let content = restricted ? 'restricted user' : 'removed restriction from user';
+    showAlert({level: 'success', content: `Successfully ${content}.`});
+    await sleep(2000);
+    window.location.reload();
+  }
+})
+
+const toggleTrusted = document.getElementById('toggle-trusted');
+toggleTrusted?.addEventListener('click', async () => {
+  let trusted = toggleTrusted.dataset.trusted == 'false';
+  let resp = await callApi(toggleTrusted.dataset.endpoint, {
+    method: 'POST',
+    headers: {
+      'content-type': 'application/json',
+    },
+    body: JSON.stringify({trusted})
+  });
+  if (resp !== null) {
+    let content = trusted ? 'made user trusted' : 'removed trusted flag from user';
+    showAlert({level: 'success', content: `Successfully ${content}.`});
+    await sleep(2000);
+    window.location.reload();
+  }
+})
+
 document.getElementById('session-description')?.setAttribute('value', deviceDescription());
 
 document.querySelectorAll('.created[data-timestamp]').forEach(el => {
@@ -91,22 +127,68 @@ document.getElementById('copy-api-key')?.addEventListener('click', async (e) =>
 
 document.querySelector('#api-section button[type=submit][name="new"]')?.addEventListener('click', async (e) => {
   e.preventDefault();
+  const description = document.getElementById('api-key-description');
+  if(!description.checkValidity() || description.value.trim().length === 0) {
+    document.getElementById('api-section').reportValidity();
+    return;
+  }
   let response = await callApi('/account/api_key', {
     method: 'POST',
     headers: {
       'content-type': 'application/json',
     },
-    body: JSON.stringify({new: e.target.getAttribute('new') === 'true' })
+    body: JSON.stringify({new: e.target.getAttribute('new') === 'true', description: description.value.trim()})
   });
   let apiKey = document.getElementById('api-key');
   if(apiKey === null) {
     window.location.reload();
   } else {
     apiKey.textContent = response.token;
+    document.querySelector('label[for="api-key"]').textContent = description.value.trim();
     showAlert({level: 'success', content: 'Successfully regenerated API key.'})
   }
 })
 
+document.getElementById('submit-email')?.addEventListener('click', async (e) => {
+  e.preventDefault();
+  const email = document.getElementById('email');
+  if(!email.checkValidity() || email.value.length === 0) {
+    document.getElementById('email-section').reportValidity();
+    return;
+  }
+  let resp = await callApi('/account/email', {
+    method: 'POST',
+    headers: {
+      'content-type': 'application/json',
+    },
+    body: JSON.stringify({email: email.value.trim()})
+  });
+  if (resp !== null) {
+    showAlert({level: 'success', content: 'Sent a verification code to your email.'});
+  }
+});
+
+document.getElementById('verify-email')?.addEventListener('click', async (e) => {
+  e.preventDefault();
+  const code = document.getElementById('email-code');
+  if(!code.checkValidity() || code.value.length === 0) {
+    document.getElementById('email-section').reportValidity();
+    return;
+  }
+  let resp = await callApi('/account/email/verify', {
+    method: 'POST',
+    headers: {
+      'content-type': 'application/json',
+    },
+    body: JSON.stringify({code: code.value.trim()})
+  });
+  if (resp !== null) {
+    showAlert({level: 'success', content: 'Successfully verified email.'});
+    await sleep(2000);
+    window.location.reload();
+  }
+});
+
 document.getElementById('submit-anilist')?.addEventListener('click', async (e) => {
   e.preventDefault();
   const userName = document.getElementById('anilist-username');
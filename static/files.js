@@ -85,8 +85,8 @@ class TableSorter {
     let parent = entries[0].parentElement;
     entries.sort((a, b) => {
       if (attribute === 'data-name') {
-        let firstName = a.textContent;
-        let secondName = b.textContent;
+        let firstName = a.dataset.sortKey || a.textContent;
+        let secondName = b.dataset.sortKey || b.textContent;
         return ascending ? firstName.localeCompare(secondName) : secondName.localeCompare(firstName);
       } else {
         // The last two remaining sort options are either e.g. file.size or entry.last_modified